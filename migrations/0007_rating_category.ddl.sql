@@ -0,0 +1,13 @@
+BEGIN;
+CREATE TABLE IF NOT EXISTS rating_category (
+    rating_category_id uuid primary key default gen_random_uuid(),
+    chat_id bigint not null,
+    name varchar(32) not null,
+    triggers text[] not null default '{}',
+    UNIQUE (chat_id, name),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE
+);
+ALTER TABLE rating
+ADD COLUMN rating_category_id uuid,
+ADD constraint fk_rating_category foreign key (rating_category_id) references rating_category(rating_category_id) ON DELETE SET NULL;
+COMMIT;
@@ -0,0 +1,16 @@
+BEGIN;
+ALTER TABLE chat_user
+ADD COLUMN created_at timestamp not null default current_timestamp,
+ADD COLUMN messages_count bigint not null default 0;
+-- members who were there before the migration joined no later than their first rating record
+UPDATE chat_user cu
+SET created_at = COALESCE(
+    (SELECT MIN(r.created_at) FROM rating r WHERE r.chat_user_id = cu.chat_user_id),
+    '1970-01-01 00:00:00'
+);
+
+ALTER TABLE chat_settings
+ADD COLUMN voter_min_age integer not null default 0,
+ADD COLUMN voter_min_messages bigint not null default 0,
+ADD COLUMN voter_min_rating decimal not null default 0;
+COMMIT;
@@ -0,0 +1,4 @@
+BEGIN;
+ALTER TABLE chat_user
+ADD COLUMN is_rating_optout boolean not null default false;
+COMMIT;
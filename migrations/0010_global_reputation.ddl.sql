@@ -0,0 +1,5 @@
+BEGIN;
+ALTER TABLE chat_settings
+ADD COLUMN global_reputation_weight decimal not null default 1,
+ADD COLUMN is_global_reputation_start boolean not null default false;
+COMMIT;
@@ -0,0 +1,14 @@
+BEGIN;
+CREATE TABLE IF NOT EXISTS job (
+    job_id uuid primary key default gen_random_uuid(),
+    kind varchar(64) not null,
+    payload jsonb not null,
+    run_at timestamp not null,
+    attempts int not null default 0,
+    locked_until timestamp,
+    last_error text,
+    failed_at timestamp,
+    created_at timestamp not null default current_timestamp
+);
+CREATE INDEX IF NOT EXISTS job_due_idx ON job (run_at) WHERE failed_at IS NULL;
+COMMIT;
@@ -0,0 +1,22 @@
+BEGIN;
+CREATE TABLE IF NOT EXISTS chat_command (
+    chat_id bigint not null,
+    -- command name without the leading ! (src/lib/enums/command.rs)
+    command varchar(32) not null,
+    -- disabled, everyone, rating, moderators or admins
+    access varchar(16) not null,
+    -- set for the rating access only
+    min_rating decimal,
+    -- 0 for no cooldown
+    cooldown_seconds int not null default 0,
+    primary key (chat_id, command),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- commands_for_admin_only limited every command but !report and the rating opt-out ones to administrators
+INSERT INTO chat_command (chat_id, command, access)
+SELECT cs.chat_id, c.command, 'admins'
+FROM chat_setting cs CROSS JOIN (VALUES ('help'), ('me'), ('top'), ('rating_categories')) AS c(command)
+WHERE cs.name = 'commands_for_admin_only' AND cs.value = 'true';
+DELETE FROM chat_setting WHERE name = 'commands_for_admin_only';
+COMMIT;
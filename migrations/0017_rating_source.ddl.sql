@@ -0,0 +1,6 @@
+BEGIN;
+-- identifies imported ratings (file rows, history messages) so a repeated import skips them
+ALTER TABLE rating
+ADD COLUMN source_id varchar(128);
+CREATE UNIQUE INDEX IF NOT EXISTS rating_source_idx ON rating (chat_user_id, source_id);
+COMMIT;
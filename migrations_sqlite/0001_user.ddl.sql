@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "user" (
+    user_id text primary key,
+    telegram_id bigint unique not null,
+    username varchar(32),
+    first_name varchar(64) not null,
+    last_name varchar(64)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS chat (
+    chat_id bigint primary key,
+    title varchar(128) not null
+);
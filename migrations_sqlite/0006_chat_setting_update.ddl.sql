@@ -0,0 +1,2 @@
+ALTER TABLE chat_settings
+ADD COLUMN commands_for_admin_only boolean not null default false;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS rating_category (
+    rating_category_id text primary key,
+    chat_id bigint not null,
+    name varchar(32) not null,
+    -- JSON array of trigger strings
+    triggers text not null default '[]',
+    UNIQUE (chat_id, name),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE
+);
+ALTER TABLE rating
+ADD COLUMN rating_category_id text references rating_category(rating_category_id) ON DELETE SET NULL;
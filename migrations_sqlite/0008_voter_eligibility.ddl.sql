@@ -0,0 +1,18 @@
+-- SQLite doesn't accept a non-constant default in ADD COLUMN, so new rows set created_at explicitly
+ALTER TABLE chat_user
+ADD COLUMN created_at timestamp not null default '1970-01-01 00:00:00';
+-- members who were there before the migration joined no later than their first rating record
+UPDATE chat_user
+SET created_at = COALESCE(
+    (SELECT MIN(r.created_at) FROM rating r WHERE r.chat_user_id = chat_user.chat_user_id),
+    '1970-01-01 00:00:00'
+);
+ALTER TABLE chat_user
+ADD COLUMN messages_count bigint not null default 0;
+
+ALTER TABLE chat_settings
+ADD COLUMN voter_min_age integer not null default 0;
+ALTER TABLE chat_settings
+ADD COLUMN voter_min_messages bigint not null default 0;
+ALTER TABLE chat_settings
+ADD COLUMN voter_min_rating real not null default 0;
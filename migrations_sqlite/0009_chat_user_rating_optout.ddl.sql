@@ -0,0 +1,2 @@
+ALTER TABLE chat_user
+ADD COLUMN is_rating_optout boolean not null default false;
@@ -0,0 +1,4 @@
+ALTER TABLE chat_settings
+ADD COLUMN global_reputation_weight real not null default 1;
+ALTER TABLE chat_settings
+ADD COLUMN is_global_reputation_start boolean not null default false;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS job (
+    job_id text primary key,
+    kind varchar(64) not null,
+    -- JSON object with the job arguments
+    payload text not null,
+    run_at timestamp not null,
+    attempts int not null default 0,
+    locked_until timestamp,
+    last_error text,
+    failed_at timestamp,
+    created_at timestamp not null default current_timestamp
+);
+CREATE INDEX IF NOT EXISTS job_due_idx ON job (run_at) WHERE failed_at IS NULL;
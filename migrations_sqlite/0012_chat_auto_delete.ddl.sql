@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS chat_auto_delete (
+    chat_id bigint not null,
+    category varchar(32) not null,
+    -- 0 keeps the replies of the category
+    ttl_seconds int not null,
+    delete_trigger boolean not null default false,
+    primary key (chat_id, category),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
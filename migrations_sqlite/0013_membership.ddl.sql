@@ -0,0 +1,7 @@
+-- false once the bot was removed from the chat
+ALTER TABLE chat
+ADD COLUMN is_active boolean not null default true;
+ALTER TABLE chat_user
+ADD COLUMN left_at timestamp;
+ALTER TABLE chat_user
+ADD COLUMN rejoined_at timestamp;
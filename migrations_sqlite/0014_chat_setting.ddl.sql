@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS chat_setting (
+    chat_id bigint not null,
+    -- key of the setting in the registry (src/domains/chat/registry.rs)
+    name varchar(64) not null,
+    value text not null,
+    primary key (chat_id, name),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- only the values differing from the registry defaults are kept
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'rating_count', 'false' FROM chat_settings
+WHERE NOT is_rating_count AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'commands_for_admin_only', 'true' FROM chat_settings
+WHERE commands_for_admin_only AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'global_reputation_start', 'true' FROM chat_settings
+WHERE is_global_reputation_start AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'voter_min_age', CAST(voter_min_age AS TEXT) FROM chat_settings
+WHERE voter_min_age <> 0 AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'voter_min_messages', CAST(voter_min_messages AS TEXT) FROM chat_settings
+WHERE voter_min_messages <> 0 AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'voter_min_rating', CAST(voter_min_rating AS TEXT) FROM chat_settings
+WHERE voter_min_rating <> 0 AND chat_id IN (SELECT chat_id FROM chat);
+INSERT INTO chat_setting (chat_id, name, value)
+SELECT chat_id, 'global_reputation_weight', CAST(global_reputation_weight AS TEXT) FROM chat_settings
+WHERE global_reputation_weight <> 1 AND chat_id IN (SELECT chat_id FROM chat);
+
+ALTER TABLE chat_settings DROP COLUMN is_rating_count;
+ALTER TABLE chat_settings DROP COLUMN commands_for_admin_only;
+ALTER TABLE chat_settings DROP COLUMN is_global_reputation_start;
+ALTER TABLE chat_settings DROP COLUMN voter_min_age;
+ALTER TABLE chat_settings DROP COLUMN voter_min_messages;
+ALTER TABLE chat_settings DROP COLUMN voter_min_rating;
+ALTER TABLE chat_settings DROP COLUMN global_reputation_weight;
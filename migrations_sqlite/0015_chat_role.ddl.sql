@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS chat_role (
+    chat_id bigint not null,
+    telegram_id bigint not null,
+    -- owner, moderator or helper (src/lib/enums/moderator_role.rs)
+    role varchar(16) not null,
+    -- Telegram id of the member who granted the role
+    granted_by bigint not null,
+    primary key (chat_id, telegram_id),
+    constraint fk_chat foreign key (chat_id) references chat(chat_id) ON DELETE CASCADE ON UPDATE CASCADE
+);
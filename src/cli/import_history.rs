@@ -0,0 +1,133 @@
+use crate::{domains, injected, lib::config::SharedConfig};
+use anyhow::Context;
+use std::sync::Arc;
+
+/// Chat history as exported by Telegram Desktop (`result.json`).
+#[derive(serde::Deserialize)]
+struct ChatExport {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    chat_type: String,
+    id: i64,
+    messages: Vec<ExportedMessage>,
+}
+
+#[derive(serde::Deserialize)]
+struct ExportedMessage {
+    id: i64,
+    #[serde(rename = "type")]
+    message_type: String,
+    date: String,
+    date_unixtime: Option<String>,
+    from: Option<String>,
+    from_id: Option<String>,
+    reply_to_message_id: Option<i64>,
+    #[serde(default)]
+    text: serde_json::Value,
+}
+
+impl ChatExport {
+    fn bot_api_chat_id(&self) -> i64 {
+        match self.chat_type.as_str() {
+            "private_supergroup" | "public_supergroup" | "private_channel" | "public_channel" => {
+                -1_000_000_000_000 - self.id
+            }
+            _ => -self.id,
+        }
+    }
+}
+
+impl ExportedMessage {
+    fn user_id(&self) -> Option<i64> {
+        self.from_id
+            .as_deref()
+            .and_then(|from_id| from_id.strip_prefix("user"))
+            .and_then(|user_id| user_id.parse().ok())
+    }
+
+    fn date(&self) -> Option<chrono::NaiveDateTime> {
+        match &self.date_unixtime {
+            Some(timestamp) => timestamp
+                .parse()
+                .ok()
+                .and_then(|timestamp| chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0)),
+            None => chrono::NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%S").ok(),
+        }
+    }
+
+    /// Text is either a plain string or a list of plain strings and formatted entities.
+    fn plain_text(&self) -> String {
+        match &self.text {
+            serde_json::Value::String(text) => text.clone(),
+            serde_json::Value::Array(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    serde_json::Value::String(text) => Some(text.as_str()),
+                    serde_json::Value::Object(entity) => entity.get("text").and_then(|text| text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => String::new(),
+        }
+    }
+}
+
+async fn run_import(path: &str, chat_id: Option<i64>, config: SharedConfig) -> Result<(), anyhow::Error> {
+    let data = tokio::fs::read(path)
+        .await
+        .context(format!("Unable to read chat export {}", path))?;
+    let export: ChatExport = serde_json::from_slice(&data).context("Unable to parse chat export")?;
+    let chat_id = chat_id.unwrap_or_else(|| export.bot_api_chat_id());
+    let title = export.name.clone().unwrap_or_else(|| chat_id.to_string());
+
+    let messages = export
+        .messages
+        .iter()
+        .filter(|message| message.message_type == "message")
+        .filter_map(|message| {
+            Some(domains::user::HistoryMessage {
+                id: message.id,
+                user_tg_id: message.user_id()?,
+                full_name: message.from.clone().unwrap_or_default(),
+                date: message.date()?,
+                reply_to_message_id: message.reply_to_message_id,
+                text: message.plain_text(),
+            })
+        })
+        .collect::<Vec<_>>();
+    log::info!("Replaying {} messages of chat \"{}\" (id: {})", messages.len(), title, chat_id);
+
+    let app_environment = injected::setup_environment(config).await?;
+    let domain_holder = Arc::new(app_environment.domain_holder);
+
+    domain_holder.chat.controller.create_by_id(chat_id, &title).await?;
+    let summary = domain_holder.user.controller.replay_history(chat_id, messages).await?;
+    log::info!(
+        "Chat history import finished (messages: {}, created users: {}, ratings: {}, \
+        skipped by cooldown: {}, skipped by rating power: {}, skipped by opt-out: {}, already imported: {})",
+        summary.messages,
+        summary.created_users,
+        summary.ratings,
+        summary.skipped_by_cooldown,
+        summary.skipped_by_power,
+        summary.skipped_by_optout,
+        summary.skipped_as_imported,
+    );
+    Ok(())
+}
+
+/// Backfills users, message counts and ratings from a Telegram Desktop export.
+///
+/// Usage: `comparty-bot import-history <result.json> [chat_id]`
+pub async fn import_history(args: &[String], config: SharedConfig) -> Result<(), anyhow::Error> {
+    let (path, chat_id) = match args {
+        [path] => (path, None),
+        [path, chat_id] => match chat_id.parse::<i64>() {
+            Ok(chat_id) => (path, Some(chat_id)),
+            Err(_) => anyhow::bail!("Chat id must be a number, got: {}", chat_id),
+        },
+        _ => anyhow::bail!("Usage: comparty-bot import-history <result.json> [chat_id]"),
+    };
+
+    run_import(path, chat_id, config).await.context("Chat history import failed")
+}
@@ -0,0 +1,4 @@
+mod import_history;
+mod replay;
+
+pub use {import_history::import_history, replay::replay};
@@ -0,0 +1,146 @@
+use crate::{core, injected, lib};
+use anyhow::Context;
+use std::sync::Arc;
+
+/// Recorded updates, optionally with the users `getChatAdministrators` should return.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum ReplayInput {
+    Updates(Vec<serde_json::Value>),
+    WithAdmins {
+        #[serde(default)]
+        admins: Vec<teloxide::types::User>,
+        updates: Vec<serde_json::Value>,
+    },
+}
+
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum TranscriptEvent {
+    Call(lib::bot_actions::BotCall),
+    Error { error: String },
+}
+
+#[derive(serde::Serialize)]
+struct TranscriptEntry {
+    update_id: i32,
+    #[serde(flatten)]
+    event: TranscriptEvent,
+}
+
+async fn run_replay(
+    input_path: &str,
+    output_path: Option<&String>,
+    config: lib::config::SharedConfig,
+) -> Result<(), anyhow::Error> {
+    let data = tokio::fs::read(input_path)
+        .await
+        .context(format!("Unable to read updates {}", input_path))?;
+    let (admins, updates) = match serde_json::from_slice(&data).context("Unable to parse updates")? {
+        ReplayInput::Updates(updates) => (vec![], updates),
+        ReplayInput::WithAdmins { admins, updates } => (admins, updates),
+    };
+
+    let fake_bot = Arc::new(lib::bot_actions::RecordingBotActions::new().with_admins(admins));
+    let bot_actions: Arc<dyn lib::bot_actions::BotActions> = fake_bot.clone();
+
+    // the replay never touches the configured database or Redis
+    let storage = core::persistence::Storage::Memory(Arc::new(core::persistence::MemoryStore::default()));
+    let domain_holder = Arc::new(injected::new_domain_holder(&storage, config).await);
+
+    let mut transcript = vec![];
+    for value in updates {
+        let update: teloxide::types::Update = serde_json::from_value(value).context("Unable to parse update")?;
+        let update_id = update.id;
+        let result = match update.kind {
+            teloxide::types::UpdateKind::Message(message) => {
+                let cx: lib::types::MessageContext = teloxide::prelude::UpdateWithCx {
+                    requester: bot_actions.clone(),
+                    update: message,
+                };
+                core::handlers::message_handler(&cx, domain_holder.clone()).await
+            }
+            teloxide::types::UpdateKind::CallbackQuery(query) => {
+                let cx: lib::types::CallbackContext = teloxide::prelude::UpdateWithCx {
+                    requester: bot_actions.clone(),
+                    update: query,
+                };
+                core::handlers::callback_handler(&cx, domain_holder.clone()).await
+            }
+            teloxide::types::UpdateKind::ChatMember(update) => {
+                let cx: lib::types::ChatMemberContext = teloxide::prelude::UpdateWithCx {
+                    requester: bot_actions.clone(),
+                    update,
+                };
+                core::handlers::chat_member_handler(&cx, domain_holder.clone()).await
+            }
+            teloxide::types::UpdateKind::MyChatMember(update) => {
+                let cx: lib::types::ChatMemberContext = teloxide::prelude::UpdateWithCx {
+                    requester: bot_actions.clone(),
+                    update,
+                };
+                core::handlers::my_chat_member_handler(&cx, domain_holder.clone()).await
+            }
+            _ => Ok(()),
+        };
+
+        transcript.extend(
+            fake_bot
+                .take_calls()
+                .into_iter()
+                .map(|call| TranscriptEntry { update_id, event: TranscriptEvent::Call(call) }),
+        );
+        if let Err(err) = result {
+            transcript.push(TranscriptEntry {
+                update_id,
+                event: TranscriptEvent::Error { error: format!("{:?}", err) },
+            });
+        }
+    }
+
+    let transcript = serde_json::to_string_pretty(&transcript)?;
+    match output_path {
+        Some(output_path) => tokio::fs::write(output_path, transcript)
+            .await
+            .context(format!("Unable to write transcript {}", output_path))?,
+        None => println!("{}", transcript),
+    }
+    Ok(())
+}
+
+/// Feeds recorded updates through the handlers and writes every outgoing Bot API call as a transcript.
+///
+/// Usage: `comparty-bot replay <updates.json> [transcript.json]`
+pub async fn replay(args: &[String], config: lib::config::SharedConfig) -> Result<(), anyhow::Error> {
+    let (input_path, output_path) = match args {
+        [input_path] => (input_path, None),
+        [input_path, output_path] => (input_path, Some(output_path)),
+        _ => anyhow::bail!("Usage: comparty-bot replay <updates.json> [transcript.json]"),
+    };
+
+    run_replay(input_path, output_path, config).await.context("Updates replay failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn updates_are_replayed_against_memory_storage() {
+        let dir = std::env::temp_dir().join(format!("comparty-bot-replay-{}", sqlx::types::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let (input_path, output_path) = (dir.join("updates.json"), dir.join("transcript.json"));
+        let (voter, helper) = (lib::test_helpers::user(1, "Voter"), lib::test_helpers::user(2, "Helper"));
+        let update = json!({ "update_id": 1, "message": lib::test_helpers::reply(10, &voter, "+", &helper) });
+        std::fs::write(&input_path, json!([update]).to_string()).unwrap();
+
+        let output = output_path.to_str().unwrap().to_string();
+        run_replay(input_path.to_str().unwrap(), Some(&output), lib::test_helpers::config()).await.unwrap();
+        let transcript: Vec<serde_json::Value> = serde_json::from_slice(&std::fs::read(&output_path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(transcript.len(), 1);
+        assert_eq!(transcript[0]["update_id"], 1);
+        assert!(transcript[0]["text"].as_str().unwrap().contains("изменил рейтинг <b>Helper</b>"));
+    }
+}
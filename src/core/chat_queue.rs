@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::oneshot;
+
+/// Runs the updates of a chat one at a time in the order they arrived, while other chats go on in parallel.
+/// Every update waits for the one before it in the same chat to finish.
+#[derive(Default)]
+pub struct ChatQueue {
+    /// The last update of every busy chat, it's removed when that update is done.
+    tails: Mutex<HashMap<i64, (u64, oneshot::Receiver<()>)>>,
+    next_ticket: AtomicU64,
+}
+
+impl ChatQueue {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    fn tails(&self) -> MutexGuard<'_, HashMap<i64, (u64, oneshot::Receiver<()>)>> {
+        self.tails.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Takes the place of `task` in the chat queue right away, so it has to be called in the order updates arrive.
+    /// The returned future runs `task` once the previous update of the chat is done.
+    pub fn run<F: Future>(self: &Arc<Self>, chat_id: i64, task: F) -> impl Future<Output = F::Output> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let (done, tail) = oneshot::channel::<()>();
+        let previous = self.tails().insert(chat_id, (ticket, tail)).map(|(_, previous)| previous);
+        let queue = self.clone();
+        async move {
+            if let Some(previous) = previous {
+                // Dropping the sender also wakes us, so an error only means the previous update is gone.
+                let _ = previous.await;
+            }
+            let output = task.await;
+            {
+                let mut tails = queue.tails();
+                if tails.get(&chat_id).is_some_and(|(last_ticket, _)| *last_ticket == ticket) {
+                    tails.remove(&chat_id);
+                }
+            }
+            drop(done);
+            output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn updates_of_a_chat_run_in_order_and_chats_in_parallel() {
+        let queue = ChatQueue::new();
+        let log = Arc::new(Mutex::new(vec![]));
+        let task = |name: &'static str, delay_ms: u64| {
+            let log = log.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                log.lock().unwrap().push(name);
+            }
+        };
+
+        let first = tokio::spawn(queue.run(1, task("chat 1, first", 50)));
+        let second = tokio::spawn(queue.run(1, task("chat 1, second", 0)));
+        let other = tokio::spawn(queue.run(2, task("chat 2", 0)));
+        for handle in [first, second, other] {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(*log.lock().unwrap(), vec!["chat 2", "chat 1, first", "chat 1, second"]);
+        assert!(queue.tails().is_empty());
+    }
+}
@@ -4,14 +4,27 @@ use itertools::Itertools;
 pub async fn cancel_rating_handler(
     cx: &lib::types::CallbackContext,
     domain_holder: &injected::DomainHolder,
-) -> Result<(), lib::errors::UserError> {
+) -> Result<(), anyhow::Error> {
     if let Some(data) = &cx.update.data {
         if let [user_id, rating_record_id] = data.split_whitespace().collect_vec()[..] {
             if let Ok(user_id) = user_id.parse::<i64>() {
+                let can_adjust_rating = match &cx.update.message {
+                    Some(message) if cx.update.from.id != user_id => domain_holder
+                        .chat
+                        .controller
+                        .has_permission(
+                            &*cx.requester,
+                            message.chat_id(),
+                            cx.update.from.id,
+                            lib::enums::Permission::AdjustRating,
+                        )
+                        .await?,
+                    _ => false,
+                };
                 domain_holder
                     .user
                     .controller
-                    .delete_rating_record_by_user_request(cx, user_id, rating_record_id)
+                    .delete_rating_record_by_user_request(cx, user_id, rating_record_id, can_adjust_rating)
                     .await?;
             }
         }
@@ -1,2 +1,4 @@
 mod cancel_rating;
+mod settings_panel;
 pub use cancel_rating::cancel_rating_handler;
+pub use settings_panel::settings_panel_handler;
@@ -0,0 +1,17 @@
+use crate::{domains, injected, lib};
+
+pub async fn settings_panel_handler(
+    cx: &lib::types::CallbackContext,
+    domain_holder: &injected::DomainHolder,
+) -> Result<(), lib::errors::ChatError> {
+    if let Some(data) = &cx.update.data {
+        if data.starts_with(domains::chat::SETTINGS_CALLBACK_PREFIX) {
+            domain_holder
+                .chat
+                .controller
+                .handle_settings_callback(cx, data)
+                .await?;
+        }
+    }
+    Ok(())
+}
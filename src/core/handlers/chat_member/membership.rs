@@ -0,0 +1,17 @@
+use crate::{injected, lib};
+
+pub async fn member_status_handler(
+    cx: &lib::types::ChatMemberContext,
+    domain_holder: &injected::DomainHolder,
+) -> Result<(), anyhow::Error> {
+    domain_holder.chat.controller.member_changed(cx).await?;
+    domain_holder.user.controller.change_membership(cx).await?;
+    Ok(())
+}
+
+pub async fn bot_status_handler(
+    cx: &lib::types::ChatMemberContext,
+    domain_holder: &injected::DomainHolder,
+) -> Result<(), lib::errors::ChatError> {
+    domain_holder.chat.controller.bot_member_changed(cx).await
+}
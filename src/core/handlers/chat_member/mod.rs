@@ -0,0 +1,2 @@
+mod membership;
+pub use membership::{bot_status_handler, member_status_handler};
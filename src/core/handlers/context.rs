@@ -0,0 +1,124 @@
+use crate::{domains, injected, lib};
+use tokio::sync::OnceCell;
+
+/// What the handlers of one message share: the chat administrators, settings, the sender's role and rating are
+/// resolved on first use and reused by every next handler, so an update costs at most one `getChatAdministrators`
+/// call and one query of each. Settings changed by a command aren't reflected until the next update.
+pub struct UpdateContext<'a> {
+    pub cx: &'a lib::types::MessageContext,
+    pub domain_holder: &'a injected::DomainHolder,
+    admins: OnceCell<Vec<i64>>,
+    chat_settings: OnceCell<domains::chat::ChatSettings>,
+    role: OnceCell<Option<lib::enums::ModeratorRole>>,
+    rating: OnceCell<sqlx::types::BigDecimal>,
+}
+
+impl<'a> UpdateContext<'a> {
+    pub fn new(cx: &'a lib::types::MessageContext, domain_holder: &'a injected::DomainHolder) -> Self {
+        UpdateContext {
+            cx,
+            domain_holder,
+            admins: OnceCell::new(),
+            chat_settings: OnceCell::new(),
+            role: OnceCell::new(),
+            rating: OnceCell::new(),
+        }
+    }
+
+    /// Telegram ids of the chat administrators who aren't bots.
+    pub async fn admins(&self) -> Result<&[i64], lib::errors::ChatError> {
+        self.admins
+            .get_or_try_init(|| {
+                self.domain_holder.chat.controller.get_admins(&*self.cx.requester, self.cx.update.chat_id())
+            })
+            .await
+            .map(Vec::as_slice)
+    }
+
+    pub async fn is_admin(&self) -> Result<bool, lib::errors::ChatError> {
+        let admins = self.admins().await?;
+        Ok(self.cx.update.from().is_some_and(|user| admins.contains(&user.id)))
+    }
+
+    pub async fn chat_settings(&self) -> Result<&domains::chat::ChatSettings, lib::errors::ChatError> {
+        self.chat_settings
+            .get_or_try_init(|| self.domain_holder.chat.controller.get_chat_settings(self.cx))
+            .await
+    }
+
+    /// Bot-level role of the sender granted with `!promote`.
+    pub async fn role(&self) -> Result<Option<lib::enums::ModeratorRole>, lib::errors::ChatError> {
+        let user_id = match self.cx.update.from() {
+            Some(user) => user.id,
+            None => return Ok(None),
+        };
+        self.role
+            .get_or_try_init(|| self.domain_holder.chat.controller.get_role(self.cx.update.chat_id(), user_id))
+            .await
+            .copied()
+    }
+
+    /// Whether the command policy of the chat lets the sender use the command.
+    pub async fn can_use(&self, command: lib::enums::Command) -> Result<bool, anyhow::Error> {
+        use domains::chat::CommandAccess;
+
+        let access = self.chat_settings().await?.command_policy(command).access;
+        let is_admin = match access {
+            CommandAccess::Disabled | CommandAccess::Everyone => false,
+            _ => self.is_admin().await?,
+        };
+        match access {
+            CommandAccess::Disabled => Ok(false),
+            CommandAccess::Everyone => Ok(true),
+            _ if is_admin => Ok(true),
+            CommandAccess::Admins => Ok(self.role().await? == Some(lib::enums::ModeratorRole::Owner)),
+            CommandAccess::Moderators => Ok(self.role().await?.is_some()),
+            CommandAccess::MinRating(min_rating) => {
+                if self.role().await?.is_some() {
+                    return Ok(true);
+                }
+                let user_id = match self.cx.update.from() {
+                    Some(user) => user.id,
+                    None => return Ok(false),
+                };
+                let rating = self.rating
+                    .get_or_try_init(|| {
+                        self.domain_holder.user.controller.get_rating(self.cx.update.chat_id(), user_id)
+                    })
+                    .await?;
+                Ok(*rating >= min_rating)
+            }
+        }
+    }
+
+    /// Starts the sender's cooldown of the command, administrators and owners have none. Returns the seconds
+    /// left when the cooldown is already running.
+    pub async fn start_cooldown(&self, command: lib::enums::Command) -> Result<Option<i64>, anyhow::Error> {
+        let cooldown_seconds = self.chat_settings().await?.command_policy(command).cooldown_seconds;
+        let user_id = match self.cx.update.from() {
+            Some(user) if cooldown_seconds > 0 => user.id,
+            _ => return Ok(None),
+        };
+        if self.is_admin().await? || self.role().await? == Some(lib::enums::ModeratorRole::Owner) {
+            return Ok(None);
+        }
+        Ok(self.domain_holder
+            .chat
+            .controller
+            .start_command_cooldown(self.cx.update.chat_id(), user_id, command, cooldown_seconds)
+            .await)
+    }
+
+    /// Whether the sender should hear about the running cooldown, once per cooldown window.
+    pub async fn start_cooldown_notice(&self, command: lib::enums::Command, seconds_left: i64) -> bool {
+        let user_id = match self.cx.update.from() {
+            Some(user) => user.id,
+            None => return false,
+        };
+        self.domain_holder
+            .chat
+            .controller
+            .start_cooldown_notice(self.cx.update.chat_id(), user_id, command, seconds_left)
+            .await
+    }
+}
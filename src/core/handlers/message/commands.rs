@@ -1,10 +1,113 @@
-use crate::{domains, injected, lib};
+use crate::{
+    core::handlers::UpdateContext,
+    lib::{self, enums::{Command, ModeratorRole, Permission, ReplyCategory}},
+};
 use itertools::Itertools;
+use std::str::FromStr;
 
-pub async fn admin_commands_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder,
-) -> Result<(), anyhow::Error> {
+/// Bots can't delete messages older than 48 hours.
+const MAX_AUTO_DELETE_SECONDS: i32 = 48 * 60 * 60;
+
+fn parse_auto_delete(category: &str, time: &str) -> Result<(ReplyCategory, i32), String> {
+    let category = ReplyCategory::from_str(category).map_err(|_| format!(
+        "Неизвестная категория. Доступные категории: {}",
+        ReplyCategory::ALL.iter().map(|category| format!("<code>{}</code>", category.name())).join(", "),
+    ))?;
+    if time == "off" {
+        return Ok((category, 0));
+    }
+    match lib::enums::TimeUnits::from_str(time).map(|time| time.to_duration().num_seconds()) {
+        Ok(seconds) if seconds > 0 && seconds <= MAX_AUTO_DELETE_SECONDS as i64 => Ok((category, seconds as i32)),
+        _ => Err("Некорректный срок, пример, <code>5m</code>. Максимальный срок – <code>48h</code>".to_string()),
+    }
+}
+
+/// What lets the caller see a `!help` entry.
+enum HelpGate {
+    Command(Command),
+    /// A rating command, also hidden while the chat doesn't count rating.
+    RatingCommand(Command),
+    Permission(Permission),
+    ManageRoles,
+    /// Rating triggers, shown while the chat counts rating.
+    Rating,
+}
+
+/// Sections of `!help`. The caller sees only the entries they may use, and a section without such entries is skipped.
+const HELP_SECTIONS: [(&str, &[(HelpGate, &str)]); 3] = [
+    ("<b>Основные команды:</b>", &[
+        (HelpGate::Command(Command::Help), "<code>!help</code> – вывести данное сообщение"),
+        (HelpGate::Command(Command::Report), "<code>!report</code> – уведомить всех администраторов чата"),
+        (HelpGate::Permission(Permission::Ban), "<code>!ban</code> – бан ответом на сообщение требуемого пользователя"),
+        (
+            HelpGate::Permission(Permission::Warn),
+            "<code>!warn</code> – предупреждение ответом на сообщение требуемого пользователя",
+        ),
+        (HelpGate::ManageRoles, "<code>!promote [owner|moderator|helper]</code> – выдать роль бота ответом на сообщение участника. Владелец (<code>owner</code>) может всё, что и администратор, модератор (<code>moderator</code>) – всё, кроме изменения настроек, помощник (<code>helper</code>) – предупреждения и <code>!ro</code>. Выдавать роли могут администраторы и владельцы, владельцев – только администраторы"),
+        (HelpGate::ManageRoles, "<code>!demote</code> – снять роль бота ответом на сообщение участника"),
+        (HelpGate::Permission(Permission::Mute), "<code>!ro [time]</code> – read-only mode ответом на сообщение требуемого пользователя на введённое время, пример, <code>!ro 1h</code>.
+В качестве единиц возможно использовать <code>s</code> | <code>m</code> | <code>h</code> | <code>d</code> секунды, минуты, часы, дни соответственно.
+<i>Важно: при указании срока read-only меньше 30 секунд пользователь получит данный статус на неопределённый период!</i>"),
+    ]),
+    ("<b>Настройка чата:</b>", &[
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!settings</code>  – панель настроек чата. Нажимать кнопки панели могут только администраторы и владельцы"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!get [key]</code> – значение и описание настройки, без <code>key</code> – все настройки с их ключами"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!set [key] [value]</code> – изменить настройку, пример, <code>!set rating_count off</code>. Значения вкл/выкл: <code>on</code> | <code>off</code>. Проверки модерации (<code>cas</code>, <code>link</code>, <code>name</code>, <code>insult</code>, <code>politics</code>) включаются настройкой <code>[check]_check</code>, действие задаётся настройкой <code>[check]_action</code>, пример, <code>!set link_action delete</code>. Сроки задаются с единицами, пример, <code>!set voter_min_age 1d</code>"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!commands</code> – кто может использовать команды участников и как часто"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!command [name] [disabled|everyone|moderators|admins]</code> – кто может использовать команду, пример, <code>!command top admins</code>. <code>!command [name] rating [amount]</code> – участники с рейтингом не ниже указанного, модераторы и администраторы"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!command [name] cooldown [time|off]</code> – использовать команду не чаще раза в указанное время на участника, пример, <code>!command me cooldown 1m</code>. Администраторы и владельцы не ограничены"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!auto_delete</code> – текущие настройки автоудаления ответов бота"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!auto_delete [category] [time|off] [command]</code> – удалять ответы бота категории через указанное время, с <code>command</code> – вместе с командой, пример, <code>!auto_delete help 5m command</code>.
+Категории: <code>help</code>, <code>info</code>, <code>settings</code>, <code>warning</code>, <code>moderation</code>, <code>rating</code>, <code>error</code>. По умолчанию удаляются только ответы <code>rating</code> через 1m вместе с командой"),
+        (HelpGate::Permission(Permission::ViewGlobalReputation), "<code>!grep [@username]</code> – вывести глобальную репутацию пользователя по всем чатам, либо ответом на сообщение"),
+        (HelpGate::Permission(Permission::ImportExport), "<code>!export ratings [csv|json] [period]</code> – выгрузить записи рейтинга чата в личные сообщения, пример, <code>!export ratings json 30d</code>"),
+        (HelpGate::Permission(Permission::ImportExport), "<code>!export users [csv|json]</code> – выгрузить участников чата с их рейтингом в личные сообщения"),
+        (HelpGate::Permission(Permission::ImportExport), "<code>!import</code> – пробный импорт рейтинга из другого бота ответом на сообщение с файлом CSV или JSON с полями <code>telegram_id</code>, <code>name</code>, <code>amount</code> и необязательными <code>timestamp</code>, <code>by</code>"),
+        (HelpGate::Permission(Permission::ImportExport), "<code>!import commit</code> – записать импортируемый рейтинг ответом на сообщение с файлом"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!add_rating_category [name] [triggers]</code> – добавить или изменить категорию рейтинга с указанными триггерами через пробел, пример, <code>!add_rating_category humor 😂 🤣</code>"),
+        (HelpGate::Permission(Permission::ChangeSettings), "<code>!remove_rating_category [name]</code> – удалить категорию рейтинга. Записи рейтинга сохраняются без категории"),
+    ]),
+    ("<b>Рейтинг:</b>", &[
+        (HelpGate::RatingCommand(Command::Me), "<code>!me</code> – вывести свой рейтинг"),
+        (HelpGate::RatingCommand(Command::Top), "<code>!top [category]</code> – вывести топ-15 пользователей по рейтингу (общему или в указанной категории)"),
+        (HelpGate::RatingCommand(Command::RatingCategories), "<code>!rating_categories</code> – вывести категории рейтинга данного чата"),
+        (HelpGate::Command(Command::RatingOptout), "<code>!rating_optout</code> – отказаться от участия в рейтинге: ваш рейтинг нельзя изменить, и вы скрыты из топа. Существующие записи сохраняются"),
+        (HelpGate::Command(Command::RatingOptin), "<code>!rating_optin</code> – снова участвовать в рейтинге"),
+        (HelpGate::Rating, "<code>+</code> – добавить рейтинг ответом на сообщение требуемого пользователя.
+Валидные способы: <code>+</code>, <code>+1</code>, <code>+1.23</code>, <code>спасибо</code>, <code>спс</code>, <code>благодарю</code>, <code>thanks</code>, <code>thx</code>, <code>thank you</code>, <code>👍</code>"),
+        (HelpGate::Rating, "<code>-</code> (minus) – уменьшить рейтинг ответом на сообщение требуемого пользователя.
+Валидные способы: <code>-</code>, <code>-1</code>, <code>-1.23</code>, <code>👎</code>"),
+    ]),
+];
+
+const HELP_FOOTER: &str = "Также этот бот:
+- проверяет новых пользователей в чате в соответствии с <a href='https://cas.chat'>CAS</a> и общими ограничениями
+- проверяет ссылки в соответствии с общими ограничениями";
+
+/// Checks the command policy and starts the sender's cooldown. A running cooldown is reported to the sender
+/// on the first attempt only, the following ones are ignored until it ends.
+async fn allowed(ctx: &UpdateContext<'_>, command: Command) -> Result<bool, anyhow::Error> {
+    if !ctx.can_use(command).await? {
+        return Ok(false);
+    }
+    match ctx.start_cooldown(command).await? {
+        None => Ok(true),
+        Some(seconds_left) if ctx.start_cooldown_notice(command, seconds_left).await => {
+            lib::tg_helpers::reply_to(
+                ctx.cx,
+                &*ctx.domain_holder.chat.auto_delete,
+                ReplyCategory::Error,
+                format!("Вы слишком часто используете <code>!{}</code>. Подождите {}s", command.name(), seconds_left),
+            )
+                .await?;
+            Ok(false)
+        }
+        Some(_) => Ok(false),
+    }
+}
+
+pub async fn admin_commands_handler(ctx: &UpdateContext<'_>) -> Result<(), anyhow::Error> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
     let lowercased_vec = cx.update
         .text()
         .unwrap()
@@ -13,131 +116,181 @@ pub async fn admin_commands_handler(
         .collect_vec();
     let result = &lowercased_vec.iter().map(String::as_str).collect_vec()[..];
 
-    let is_admin = lib::helpers::is_admin(&cx)
+    let is_admin = ctx
+        .is_admin()
         .await
+        .map_err(anyhow::Error::new)
         .map_err(lib::errors::AdminCommandsControllerError::GetInfo)?;
-    let chat_id = cx.update.chat_id();
-    let chat_settings = domain_holder
-        .chat
-        .controller
-        .get_chat_settings(cx)
-        .await
-        .map_err(anyhow::Error::new)?;
+    let chat_settings = ctx.chat_settings().await?;
+    // Administrators don't need a role, and the role is looked up for commands only.
+    let role = match result.first() {
+        Some(command) if command.starts_with('!') && !is_admin => ctx.role().await?,
+        _ => None,
+    };
+    let can = |permission| is_admin || role.is_some_and(|role: ModeratorRole| role.permits(permission));
+    let can_manage_roles = is_admin || role == Some(ModeratorRole::Owner);
 
     match result {
-        ["!help"] if !chat_settings.commands_for_admin_only || is_admin => {
-            return lib::tg_helpers::reply_to(
-                cx, "\
-<b>Основные команды:</b>
-<code>!help</code> – вывести данное сообщение
-
-<code>!report</code> – уведомить всех администраторов чата
-
-<code>!ban</code> – бан ответом на сообщение требуемого пользователя
-
-<code>!ro [time]</code> – read-only mode ответом на сообщение требуемого пользователя на введённое время, пример, <code>!ro 1h</code>.
-В качестве единиц возможно использовать <code>s</code> | <code>m</code> | <code>h</code> | <code>d</code> секунды, минуты, часы, дни соответственно.
-<i>Важно: при указании срока read-only меньше 30 секунд пользователь получит данный статус на неопределённый период!</i>
-
-
-<b>Настройка чата:</b>
-<code>!settings</code>  – текущие настройки чата
-
-<code>!disable_rating_count</code>  – отключить подсчёт рейтинга. При отключении данные не стираются
-
-<code>!enable_rating_count</code> – включить подсчёт рейтинга (по умолчанию, включён)
-
-<code>!enable_commands_for_admin_only</code> – команды доступны исключительно администраторам чата (за исключением команды <code>!report</code>)
-
-<code>!disable_commands_for_admin_only</code> – команды доступны для всех участников (по умолчанию)
-
-<b>Рейтинг:</b>
-<code>!me</code> – вывести свой рейтинг
-
-<code>!top</code> – вывести топ-15 пользователей по рейтингу
-
-<code>+</code> – добавить рейтинг ответом на сообщение требуемого пользователя.
-Валидные способы: <code>+</code>, <code>+1</code>, <code>+1.23</code>, <code>спасибо</code>, <code>спс</code>, <code>благодарю</code>, <code>thanks</code>, <code>thx</code>, <code>thank you</code>, <code>👍</code>
-
-<code>-</code> (minus) – уменьшить рейтинг ответом на сообщение требуемого пользователя.
-Валидные способы: <code>-</code>, <code>-1</code>, <code>-1.23</code>, <code>👎</code>
-
-Также этот бот:
-- проверяет новых пользователей в чате в соответствии с <a href='https://cas.chat'>CAS</a> и общими ограничениями
-- проверяет ссылки в соответствии с общими ограничениями
-            ".to_string()
-            )
-                .await
+        ["!help"] if allowed(ctx, Command::Help).await? => {
+            let mut sections = Vec::new();
+            for (title, entries) in HELP_SECTIONS.iter() {
+                let mut lines = Vec::new();
+                for (gate, text) in entries.iter() {
+                    let visible = match gate {
+                        HelpGate::Command(command) => ctx.can_use(*command).await?,
+                        HelpGate::RatingCommand(command) => {
+                            chat_settings.is_rating_count() && ctx.can_use(*command).await?
+                        }
+                        HelpGate::Permission(permission) => can(*permission),
+                        HelpGate::ManageRoles => can_manage_roles,
+                        HelpGate::Rating => chat_settings.is_rating_count(),
+                    };
+                    if visible {
+                        lines.push(text.to_string());
+                    }
+                }
+                if !lines.is_empty() {
+                    sections.push(format!("{}\n{}", title, lines.join("\n\n")));
+                }
+            }
+            sections.push(HELP_FOOTER.to_string());
+            let msg_text = sections.join("\n\n\n");
+            return lib::tg_helpers::reply_to(cx, &*domain_holder.chat.auto_delete, ReplyCategory::Help, msg_text).await;
         }
-        ["!settings"] if is_admin => {
-            domain_holder.admin_commands.controller.get_settings(cx, chat_settings).await
+        ["!settings"] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.show_settings_panel(cx).await
         }
-        ["!report"] => {
-            domain_holder.admin_commands.controller.report(cx).await
+        ["!get"] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.get_setting(cx, None).await
         }
-        ["!ban"] if lib::helpers::is_admin(&cx).await? => {
+        ["!get", key] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.get_setting(cx, Some(*key)).await
+        }
+        ["!set", key, value] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.set_setting(cx, key, value).await
+        }
+        ["!report"] if allowed(ctx, Command::Report).await? => {
+            domain_holder.admin_commands.controller.report(cx, ctx.admins().await?).await
+        }
+        ["!warn"] if can(Permission::Warn) => {
+            domain_holder.admin_commands.controller.warn_user(cx).await
+        }
+        ["!promote", role_name] if can_manage_roles => {
+            domain_holder.chat.controller.promote(cx, role_name, is_admin).await
+        }
+        ["!demote"] if can_manage_roles => {
+            domain_holder.chat.controller.demote(cx, is_admin).await
+        }
+        ["!ban"] if can(Permission::Ban) => {
             domain_holder.admin_commands.controller.ban_user(cx).await
         }
-        ["!ro", time] if lib::helpers::is_admin(&cx).await? => {
+        ["!ro", time] if can(Permission::Mute) => {
             domain_holder
                 .admin_commands
                 .controller
                 .mute_user(cx, time)
                 .await
         }
-        ["!enable_rating_count"] if is_admin => {
+        ["!commands"] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.get_command_policies(cx).await
+        }
+        ["!command", command, "rating", min_rating] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.set_command_access(cx, command, "rating", Some(*min_rating)).await
+        }
+        ["!command", command, "cooldown", time] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.set_command_cooldown(cx, command, time).await
+        }
+        ["!command", command, access] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.set_command_access(cx, command, access, None).await
+        }
+        ["!auto_delete"] if can(Permission::ChangeSettings) => {
+            domain_holder.chat.controller.get_auto_delete(cx).await
+        }
+        ["!auto_delete", category, time, options @ ..] if can(Permission::ChangeSettings) && matches!(options, [] | ["command"]) => {
+            let (category, ttl_seconds) = match parse_auto_delete(category, time) {
+                Ok(parsed) => parsed,
+                Err(msg_text) => {
+                    let auto_delete = &*domain_holder.chat.auto_delete;
+                    return lib::tg_helpers::reply_to(cx, auto_delete, ReplyCategory::Error, msg_text).await;
+                }
+            };
             domain_holder
                 .chat
                 .controller
-                .change_chat_settings(cx, domains::chat::ChatSettings{
-                    chat_id,
-                    is_rating_count: true,
-                    commands_for_admin_only: chat_settings.commands_for_admin_only,
-                })
+                .change_auto_delete(cx, category, ttl_seconds, ttl_seconds > 0 && !options.is_empty())
                 .await
         }
-        ["!disable_rating_count"] if is_admin => {
-            domain_holder
-                .chat
+        ["!grep"] if can(Permission::ViewGlobalReputation) => {
+            return domain_holder
+                .user
                 .controller
-                .change_chat_settings(cx, domains::chat::ChatSettings {
-                    chat_id,
-                    is_rating_count: false,
-                    commands_for_admin_only: chat_settings.commands_for_admin_only,
-                })
+                .get_global_rating(cx, None)
                 .await
+                .map_err(anyhow::Error::new);
         }
-        ["!enable_commands_for_admin_only"] if is_admin => {
-            domain_holder
-                .chat
+        ["!grep", username] if can(Permission::ViewGlobalReputation) => {
+            return domain_holder
+                .user
                 .controller
-                .change_chat_settings(cx, domains::chat::ChatSettings{
-                    chat_id,
-                    is_rating_count: chat_settings.is_rating_count,
-                    commands_for_admin_only: true,
-                })
+                .get_global_rating(cx, Some(*username))
                 .await
+                .map_err(anyhow::Error::new);
         }
-        ["!disable_commands_for_admin_only"] if is_admin => {
-            domain_holder
-                .chat
+        ["!export", "ratings", args @ ..] if can(Permission::ImportExport) && args.len() <= 2 => {
+            return domain_holder
+                .user
+                .controller
+                .export_ratings(cx, args.first().copied(), args.get(1).copied())
+                .await
+                .map_err(anyhow::Error::new);
+        }
+        ["!export", "users", args @ ..] if can(Permission::ImportExport) && args.len() <= 1 => {
+            return domain_holder
+                .user
+                .controller
+                .export_users(cx, args.first().copied())
+                .await
+                .map_err(anyhow::Error::new);
+        }
+        ["!import"] if can(Permission::ImportExport) => {
+            return domain_holder
+                .user
+                .controller
+                .import_ratings(cx, chat_settings, false)
+                .await
+                .map_err(anyhow::Error::new);
+        }
+        ["!import", "commit"] if can(Permission::ImportExport) => {
+            return domain_holder
+                .user
                 .controller
-                .change_chat_settings(cx, domains::chat::ChatSettings {
-                    chat_id,
-                    is_rating_count: chat_settings.is_rating_count,
-                    commands_for_admin_only: false,
-                })
+                .import_ratings(cx, chat_settings, true)
                 .await
+                .map_err(anyhow::Error::new);
+        }
+        ["!add_rating_category", name, triggers @ ..] if can(Permission::ChangeSettings) && !triggers.is_empty() => {
+            return domain_holder
+                .user
+                .controller
+                .save_rating_category(cx, name, triggers)
+                .await
+                .map_err(anyhow::Error::new);
+        }
+        ["!remove_rating_category", name] if can(Permission::ChangeSettings) => {
+            return domain_holder
+                .user
+                .controller
+                .delete_rating_category(cx, name)
+                .await
+                .map_err(anyhow::Error::new);
         }
         _ => {Ok(())}
     }
         .map_err(|err| err.into())
 }
 
-pub async fn user_commands_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder,
-)  -> Result<(), lib::errors::UserError> {
+pub async fn user_commands_handler(ctx: &UpdateContext<'_>) -> Result<(), lib::errors::UserError> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
     let msg_text = cx.update.text().unwrap();
     let lowercased_vec = msg_text
         .split_whitespace()
@@ -145,32 +298,65 @@ pub async fn user_commands_handler(
         .collect_vec();
     let result = &lowercased_vec.iter().map(String::as_str).collect_vec()[..];
 
-    if let Ok(chat_settings) = domain_holder.chat.controller.get_chat_settings(cx).await {
-        let is_admin = lib::helpers::is_admin(&cx)
-            .await
-            .map_err(lib::errors::UserError::GetInfo)?;
+    let command = match result {
+        ["!rating_optout"] => Command::RatingOptout,
+        ["!rating_optin"] => Command::RatingOptin,
+        ["!top"] | ["!top", _] => Command::Top,
+        ["!rating_categories"] => Command::RatingCategories,
+        ["!me"] => Command::Me,
+        _ => return Ok(()),
+    };
+    let is_rating_command = !matches!(command, Command::RatingOptout | Command::RatingOptin);
+    if is_rating_command && !ctx.chat_settings().await.is_ok_and(|settings| settings.is_rating_count()) {
+        return Ok(());
+    }
+    if !allowed(ctx, command).await.map_err(lib::errors::UserError::GetInfo)? {
+        return Ok(());
+    }
 
-        if (!chat_settings.commands_for_admin_only && chat_settings.is_rating_count) ||
-            (chat_settings.commands_for_admin_only && chat_settings.is_rating_count && is_admin) {
-            match result {
-                ["!top"] => {
-                    domain_holder
-                        .user
-                        .controller
-                        .fetch_users_by_rating(cx)
-                        .await?;
-                }
-                ["!me"] => {
-                    domain_holder
-                        .user
-                        .controller
-                        .get_info(cx)
-                        .await?;
-                }
-                _ => { return Ok(()); }
-            }
+    match result {
+        ["!rating_optout"] => {
+            domain_holder
+                .user
+                .controller
+                .change_rating_optout(cx, true)
+                .await
         }
+        ["!rating_optin"] => {
+            domain_holder
+                .user
+                .controller
+                .change_rating_optout(cx, false)
+                .await
+        }
+        ["!top"] => {
+            domain_holder
+                .user
+                .controller
+                .fetch_users_by_rating(cx, None)
+                .await
+        }
+        ["!top", category_name] => {
+            domain_holder
+                .user
+                .controller
+                .fetch_users_by_rating(cx, Some(*category_name))
+                .await
+        }
+        ["!rating_categories"] => {
+            domain_holder
+                .user
+                .controller
+                .list_rating_categories(cx)
+                .await
+        }
+        ["!me"] => {
+            domain_holder
+                .user
+                .controller
+                .get_info(cx)
+                .await
+        }
+        _ => Ok(()),
     }
-
-    Ok(())
 }
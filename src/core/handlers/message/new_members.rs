@@ -1,15 +1,28 @@
-use crate::{injected, lib};
+use crate::{core::handlers::UpdateContext, domains::chat::registry, lib};
 
-pub async fn new_chat_member_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder,
-) -> Result<(), lib::errors::MessageControllerError> {
+pub async fn new_chat_member_handler(ctx: &UpdateContext<'_>) -> Result<(), lib::errors::MessageControllerError> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
+    let chat_settings = ctx
+        .chat_settings()
+        .await
+        .map_err(anyhow::Error::new)
+        .map_err(lib::errors::MessageControllerError::CheckNewMember)?;
+    let action = match chat_settings.moderation_action(&registry::CAS) {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+
+    let admins = ctx
+        .admins()
+        .await
+        .map_err(anyhow::Error::new)
+        .map_err(lib::errors::MessageControllerError::CheckNewMember)?;
     let new_members = cx.update.new_chat_members().unwrap();
     for new_member in new_members {
         domain_holder
             .message
             .controller
-            .check_new_member(cx, new_member)
+            .check_new_member(cx, admins, new_member, action)
             .await?;
     }
     Ok(())
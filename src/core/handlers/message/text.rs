@@ -1,24 +1,33 @@
-use crate::{injected, lib};
-use std::str::FromStr;
+use crate::{core::handlers::UpdateContext, domains::chat::registry, injected, lib};
 use anyhow;
 
-pub async fn user_init_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder,
-) -> Result<(), lib::errors::UserError> {
+pub async fn user_init_handler(ctx: &UpdateContext<'_>) -> Result<(), lib::errors::UserError> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
     let user_to_interact = lib::tg_helpers::get_user_to_interact(
         cx.update.from().unwrap().clone(),
         cx.update.sender_chat()
     );
     let chat_id = cx.update.chat_id();
-    let is_admin = lib::helpers::is_admin(cx)
+    let is_admin = ctx
+        .is_admin()
         .await
+        .map_err(anyhow::Error::new)
+        .map_err(lib::errors::UserError::Insert)?;
+    let chat_settings = ctx
+        .chat_settings()
+        .await
+        .map_err(anyhow::Error::new)
         .map_err(lib::errors::UserError::Insert)?;
 
     domain_holder
         .user
         .controller
-        .create_if_not_exists(&user_to_interact, chat_id, is_admin)
+        .create_if_not_exists(&user_to_interact, chat_id, is_admin, chat_settings)
+        .await?;
+    domain_holder
+        .user
+        .controller
+        .count_message(&user_to_interact, chat_id)
         .await
 }
 
@@ -29,65 +38,83 @@ pub async fn chat_init_handler(
     domain_holder.chat.controller.create_if_not_exists(cx).await
 }
 
-pub async fn clean_spam_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder
-) -> Result<(), lib::errors::MessageControllerError> {
-    domain_holder
-        .message
-        .controller
-        .check_link_in_message(cx)
-        .await?;
-    domain_holder
-        .message
-        .controller
-        .check_author(cx)
-        .await?;
-    domain_holder
-        .message
-        .controller
-        .check_insult_in_text(cx)
-        .await?;
-    domain_holder
-        .message
-        .controller
-        .check_politics_in_text(cx)
+/// Runs the checks the chat has switched on, each with the action the chat chose for it.
+pub async fn clean_spam_handler(ctx: &UpdateContext<'_>) -> Result<(), lib::errors::MessageControllerError> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
+    let chat_settings = ctx
+        .chat_settings()
+        .await
+        .map_err(anyhow::Error::new)
+        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+    let admins = ctx
+        .admins()
         .await
+        .map_err(anyhow::Error::new)
+        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+
+    if let Some(action) = chat_settings.moderation_action(&registry::LINKS) {
+        domain_holder
+            .message
+            .controller
+            .check_link_in_message(cx, admins, action)
+            .await?;
+    }
+    if let Some(action) = chat_settings.moderation_action(&registry::NAMES) {
+        domain_holder
+            .message
+            .controller
+            .check_author(cx, admins, action)
+            .await?;
+    }
+    if let Some(action) = chat_settings.moderation_action(&registry::INSULTS) {
+        domain_holder
+            .message
+            .controller
+            .check_insult_in_text(cx, action)
+            .await?;
+    }
+    if let Some(action) = chat_settings.moderation_action(&registry::POLITICS) {
+        domain_holder
+            .message
+            .controller
+            .check_politics_in_text(cx, action)
+            .await?;
+    }
+    Ok(())
 }
 
-pub async fn rating_trigger_handler(
-    cx: &lib::types::MessageContext,
-    domain_holder: &injected::DomainHolder,
-) -> Result<(), anyhow::Error> {
+pub async fn rating_trigger_handler(ctx: &UpdateContext<'_>) -> Result<(), anyhow::Error> {
+    let (cx, domain_holder) = (ctx.cx, ctx.domain_holder);
     let msg_text = cx.update.text().unwrap();
     let chat_id = cx.update.chat_id();
-    let is_admin = lib::helpers::is_admin(&cx).await?;
-
-    let chat_settings = domain_holder
-        .chat
-        .controller
-        .get_chat_settings(cx)
-        .await
-        .map_err(anyhow::Error::new)?;
+    let is_admin = ctx.is_admin().await?;
+    let chat_settings = ctx.chat_settings().await?;
 
-    if !chat_settings.is_rating_count || (chat_settings.commands_for_admin_only && !is_admin) {
+    if !chat_settings.is_rating_count() {
         return Ok(());
     }
 
-    if let Ok(rating_trigger) = lib::enums::RatingTriggers::from_str(msg_text) {
+    let rating_categories = domain_holder
+        .user
+        .controller
+        .get_rating_categories(chat_id)
+        .await?;
+    let parsed_trigger = lib::enums::RatingTriggers::from_str_with_categories(msg_text, &rating_categories);
+
+    if let Ok((rating_trigger, rating_category)) = parsed_trigger {
         if let Some(reply_msg) = cx.update.reply_to_message() {
             if let Some(reply_user) = reply_msg.from() {
                 if !reply_user.is_bot {
                     domain_holder
                         .user
                         .controller
-                        .create_if_not_exists(reply_user, chat_id, is_admin)
+                        .create_if_not_exists(reply_user, chat_id, is_admin, chat_settings)
                         .await?;
 
                     domain_holder
                         .user
                         .controller
-                        .create_rating_record(cx, rating_trigger)
+                        .create_rating_record(cx, rating_trigger, rating_category.cloned(), chat_settings)
                         .await?;
                 }
             }
@@ -96,13 +123,13 @@ pub async fn rating_trigger_handler(
                 domain_holder
                     .user
                     .controller
-                    .create_if_not_exists(&lib::tg_helpers::get_user_as_chat(sender_chat), chat_id, is_admin)
+                    .create_if_not_exists(&lib::tg_helpers::get_user_as_chat(sender_chat), chat_id, is_admin, chat_settings)
                     .await?;
 
                 domain_holder
                     .user
                     .controller
-                    .create_rating_record(cx, rating_trigger)
+                    .create_rating_record(cx, rating_trigger, rating_category.cloned(), chat_settings)
                     .await?;
             }
         }
@@ -1,24 +1,41 @@
 mod callback;
+mod chat_member;
+mod context;
 mod message;
+pub use context::UpdateContext;
 use crate::{injected, lib};
 use std::sync::Arc;
+use teloxide::types::AllowedUpdate;
+
+/// Telegram doesn't send `chat_member` updates unless they are asked for explicitly.
+pub fn allowed_updates() -> Vec<AllowedUpdate> {
+    vec![
+        AllowedUpdate::Message,
+        AllowedUpdate::CallbackQuery,
+        AllowedUpdate::MyChatMember,
+        AllowedUpdate::ChatMember,
+    ]
+}
 
 pub async fn message_handler(
     cx: &lib::types::MessageContext,
     domain_holder: Arc<injected::DomainHolder>,
 ) -> Result<(), anyhow::Error> {
     if cx.update.chat.is_group() || cx.update.chat.is_supergroup() {
+        let ctx = UpdateContext::new(cx, &domain_holder);
         if cx.update.text().is_some() {
             message::chat_init_handler(cx, &domain_holder).await?;
-            message::user_init_handler(cx, &domain_holder).await?;
-            message::admin_commands_handler(cx, &domain_holder).await?;
-            message::user_commands_handler(cx, &domain_holder).await?;
-            message::clean_spam_handler(cx, &domain_holder).await?;
-            message::rating_trigger_handler(cx, &domain_holder).await?;
+            message::user_init_handler(&ctx).await?;
+            message::admin_commands_handler(&ctx).await?;
+            message::user_commands_handler(&ctx).await?;
+            message::clean_spam_handler(&ctx).await?;
+            message::rating_trigger_handler(&ctx).await?;
         }
 
         if cx.update.new_chat_members().is_some() {
-            message::new_chat_member_handler(cx, &domain_holder).await?;
+            // The checks read the chat settings, and a join can be the first update of the chat.
+            message::chat_init_handler(cx, &domain_holder).await?;
+            message::new_chat_member_handler(&ctx).await?;
         }
 
         if cx.update.migrate_from_chat_id().is_some() && cx.update.migrate_to_chat_id().is_some() {
@@ -33,5 +50,496 @@ pub async fn callback_handler(
     domain_holder: Arc<injected::DomainHolder>,
 ) -> Result<(), anyhow::Error> {
     callback::cancel_rating_handler(cx, &domain_holder).await?;
+    callback::settings_panel_handler(cx, &domain_holder).await?;
+    Ok(())
+}
+
+pub async fn chat_member_handler(
+    cx: &lib::types::ChatMemberContext,
+    domain_holder: Arc<injected::DomainHolder>,
+) -> Result<(), anyhow::Error> {
+    if cx.update.chat.is_group() || cx.update.chat.is_supergroup() {
+        chat_member::member_status_handler(cx, &domain_holder).await?;
+    }
+    Ok(())
+}
+
+pub async fn my_chat_member_handler(
+    cx: &lib::types::ChatMemberContext,
+    domain_holder: Arc<injected::DomainHolder>,
+) -> Result<(), anyhow::Error> {
+    if cx.update.chat.is_group() || cx.update.chat.is_supergroup() {
+        chat_member::bot_status_handler(cx, &domain_holder).await?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::{MemoryStore, Storage};
+    use crate::lib::{bot_actions::{BotCall, RecordingBotActions}, test_helpers};
+    use serde_json::json;
+
+    fn reply_texts(calls: Vec<BotCall>) -> Vec<String> {
+        calls
+            .into_iter()
+            .filter_map(|call| match call {
+                BotCall::SendMessage { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rating_vote_runs_against_memory_storage_with_cooldown() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let bot = Arc::new(RecordingBotActions::new());
+        let voter = test_helpers::user(1, "Voter");
+        let helper = test_helpers::user(2, "Helper");
+
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &voter, "+", &helper));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].contains("изменил рейтинг <b>Helper</b>"));
+
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(12, &voter, "+", &helper));
+        message_handler(&cx, domain_holder).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].starts_with("Вы слишком часто инициируете изменение рейтинга"));
+    }
+
+    #[tokio::test]
+    async fn chat_administrators_are_asked_once_and_cached() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &admin, "!me"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        assert_eq!(bot.admin_lookups(), 1);
+        assert_eq!(reply_texts(bot.take_calls()).len(), 1);
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(11, &admin, "!settings"));
+        message_handler(&cx, domain_holder).await.unwrap();
+        assert_eq!(bot.admin_lookups(), 1);
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].starts_with("<b>Настройки чата:</b>"));
+    }
+
+    #[tokio::test]
+    async fn report_mentions_cached_human_admins() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let mut admin_bot = test_helpers::user(3, "Admin bot");
+        admin_bot.is_bot = true;
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![test_helpers::user(1, "Admin"), admin_bot]));
+        let reporter = test_helpers::user(2, "Reporter");
+        let offender = test_helpers::user(4, "Offender");
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &reporter, "hello"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(11, &reporter, "!report", &offender));
+        message_handler(&cx, domain_holder).await.unwrap();
+
+        assert_eq!(bot.admin_lookups(), 1);
+        let texts = reply_texts(bot.take_calls());
+        assert!(texts.last().unwrap().starts_with("Благодарим за репорт!"));
+        assert!(texts.last().unwrap().contains("tg://user?id=1"));
+        assert!(!texts.last().unwrap().contains("tg://user?id=3"));
+    }
+
+    #[tokio::test]
+    async fn leaving_and_rejoining_member_is_tracked() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let bot = Arc::new(RecordingBotActions::new());
+        let member = test_helpers::user(1, "Member");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &member, "hello"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+
+        let left = test_helpers::chat_member_updated(
+            &member,
+            json!({ "status": "member" }),
+            json!({ "status": "left" }),
+        );
+        chat_member_handler(&test_helpers::chat_member_context(&bot, left), domain_holder.clone()).await.unwrap();
+        assert!(store.tables().chat_user(1, test_helpers::CHAT_ID).unwrap().left_at.is_some());
+
+        let rejoined = test_helpers::chat_member_updated(
+            &member,
+            json!({ "status": "left" }),
+            json!({ "status": "member" }),
+        );
+        chat_member_handler(&test_helpers::chat_member_context(&bot, rejoined), domain_holder).await.unwrap();
+        let tables = store.tables();
+        let chat_user = tables.chat_user(1, test_helpers::CHAT_ID).unwrap();
+        assert!(chat_user.left_at.is_none());
+        assert!(chat_user.rejoined_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn promotion_forgets_cached_admins() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let bot = Arc::new(RecordingBotActions::new());
+        let member = test_helpers::user(1, "Member");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &member, "hello"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        assert_eq!(bot.admin_lookups(), 1);
+
+        let promoted = test_helpers::chat_member_updated(
+            &member,
+            json!({ "status": "member" }),
+            test_helpers::administrator(true, true),
+        );
+        chat_member_handler(&test_helpers::chat_member_context(&bot, promoted), domain_holder.clone()).await.unwrap();
+        let cx = test_helpers::message_context(&bot, test_helpers::message(11, &member, "hello"));
+        message_handler(&cx, domain_holder).await.unwrap();
+        assert_eq!(bot.admin_lookups(), 2);
+    }
+
+    #[tokio::test]
+    async fn bot_losing_rights_warns_and_removal_deactivates_chat() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let bot = Arc::new(RecordingBotActions::new());
+        let bot_user = test_helpers::user(99, "Bot");
+        let member = test_helpers::user(1, "Member");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &member, "hello"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+
+        let demoted = test_helpers::chat_member_updated(
+            &bot_user,
+            test_helpers::administrator(true, true),
+            test_helpers::administrator(false, true),
+        );
+        my_chat_member_handler(&test_helpers::chat_member_context(&bot, demoted), domain_holder.clone()).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts.len(), 1);
+        assert!(texts[0].contains("удаление сообщений"));
+        assert!(!texts[0].contains("блокировка участников"));
+
+        let removed = test_helpers::chat_member_updated(
+            &bot_user,
+            test_helpers::administrator(false, true),
+            json!({ "status": "left" }),
+        );
+        my_chat_member_handler(&test_helpers::chat_member_context(&bot, removed), domain_holder).await.unwrap();
+        assert!(bot.take_calls().is_empty());
+        assert!(!store.tables().chats[0].is_active);
+    }
+
+    #[tokio::test]
+    async fn settings_panel_is_changed_by_admins_in_place() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let member = test_helpers::user(2, "Member");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &admin, "!settings"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        assert!(matches!(&bot.take_calls()[..], [BotCall::SendMessage { reply_markup: Some(_), .. }]));
+        // The recording bot numbers sent messages from 1.
+        let panel = test_helpers::message(1, &test_helpers::user(99, "Bot"), "panel");
+        let press = |user: &teloxide::types::User, data: &str| {
+            test_helpers::callback_context(&bot, test_helpers::callback_query(user, &panel, data))
+        };
+
+        callback_handler(&press(&member, "settings:toggle:rating_count"), domain_holder.clone()).await.unwrap();
+        match &bot.take_calls()[..] {
+            [BotCall::AnswerCallbackQuery { show_alert: true, .. }] => {}
+            calls => panic!("Expected the member to be denied, got {:?}", calls),
+        }
+        assert!(store.tables().chat_setting.is_empty());
+
+        callback_handler(&press(&admin, "settings:toggle:rating_count"), domain_holder.clone()).await.unwrap();
+        match &bot.take_calls()[..] {
+            [BotCall::EditMessageText { message_id: 1, text, .. }, BotCall::AnswerCallbackQuery { .. }] =>
+                assert!(text.contains("Подсчёт рейтинга: <b>Отключён</b>")),
+            calls => panic!("Expected the panel to be edited, got {:?}", calls),
+        }
+        assert_eq!(store.tables().chat_setting[0].name, "rating_count");
+        assert_eq!(store.tables().chat_setting[0].value, "false");
+
+        callback_handler(&press(&admin, "settings:menu:voter_min_age"), domain_holder.clone()).await.unwrap();
+        match &bot.take_calls()[..] {
+            [BotCall::EditMessageText { text, .. }, BotCall::AnswerCallbackQuery { .. }] =>
+                assert!(text.starts_with("<b>Минимальный срок участия</b>")),
+            calls => panic!("Expected the sub-menu, got {:?}", calls),
+        }
+
+        callback_handler(&press(&admin, "settings:set:voter_min_age:86400"), domain_holder).await.unwrap();
+        assert_eq!(store.tables().chat_setting[1].name, "voter_min_age");
+        assert_eq!(store.tables().chat_setting[1].value, "86400");
+        assert_eq!(bot.admin_lookups(), 1);
+    }
+
+    #[tokio::test]
+    async fn registry_settings_are_set_and_read_by_key() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let send = |message_id: i32, user: &teloxide::types::User, text: &str| {
+            test_helpers::message_context(&bot, test_helpers::message(message_id, user, text))
+        };
+
+        message_handler(&send(10, &admin, "!set global_reputation_start on"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(11, &admin, "!set rating_count maybe"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(12, &admin, "!set unknown on"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(13, &admin, "!get global_reputation_start"), domain_holder).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts[0], "Настройки чата успешно изменены");
+        assert!(texts[1].starts_with("Некорректное значение <code>rating_count</code>"));
+        assert!(texts[2].starts_with("Неизвестная настройка <code>unknown</code>"));
+        assert!(texts[3].starts_with("<code>global_reputation_start</code>: <b>Включён</b>"));
+        assert_eq!(store.tables().chat_setting.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn out_of_range_voter_min_age_is_rejected() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &admin, "!set voter_min_age 30000d"));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        let cx = test_helpers::message_context(&bot, test_helpers::message(11, &admin, "!set voter_min_age 1d"));
+        message_handler(&cx, domain_holder).await.unwrap();
+        assert_eq!(
+            reply_texts(bot.take_calls()),
+            vec![
+                "Некорректное значение <code>voter_min_age</code>. Ожидается срок, пример, <code>1d</code>".to_string(),
+                "Настройки чата успешно изменены".to_string(),
+            ],
+        );
+        let tables = store.tables();
+        assert_eq!(tables.chat_setting.len(), 1);
+        assert_eq!(tables.chat_setting[0].value, "86400");
+    }
+
+    #[tokio::test]
+    async fn command_policies_limit_member_commands() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let member = test_helpers::user(2, "Member");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let send = |message_id: i32, user: &teloxide::types::User, text: &str| {
+            test_helpers::message_context(&bot, test_helpers::message(message_id, user, text))
+        };
+
+        message_handler(&send(10, &admin, "!command top admins"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(11, &admin, "!command report disabled"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(12, &admin, "!command rating_categories rating 100.01"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(13, &admin, "!command me cooldown 1m"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(14, &admin, "!command unknown everyone"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(15, &admin, "!command me anyone"), domain_holder.clone()).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts[0], "Доступ к команде изменён\n<code>!top</code>: <b>администраторы</b>");
+        assert!(texts[3].ends_with("<code>!me</code>: <b>все участники</b>, не чаще раза в <b>1m</b>"));
+        assert!(texts[4].starts_with("Неизвестная команда <code>unknown</code>"));
+        assert!(texts[5].starts_with("Некорректный доступ"));
+        assert_eq!(store.tables().chat_commands.len(), 4);
+
+        message_handler(&send(20, &member, "!top"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(21, &member, "!report"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(22, &member, "!rating_categories"), domain_holder.clone()).await.unwrap();
+        assert!(bot.take_calls().is_empty());
+
+        message_handler(&send(23, &member, "!me"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(24, &member, "!me"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(28, &member, "!me"), domain_holder.clone()).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert_eq!(texts.len(), 2);
+        assert!(texts[0].starts_with("Пользователь: <b>Member</b>"));
+        assert!(texts[1].starts_with("Вы слишком часто используете <code>!me</code>"));
+
+        message_handler(&send(25, &admin, "!me"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(26, &admin, "!me"), domain_holder.clone()).await.unwrap();
+        message_handler(&send(27, &admin, "!top"), domain_holder.clone()).await.unwrap();
+        assert_eq!(reply_texts(bot.take_calls()).len(), 3);
+
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(31, &admin, "+", &member));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+        message_handler(&send(32, &member, "!rating_categories"), domain_holder.clone()).await.unwrap();
+        assert_eq!(reply_texts(bot.take_calls()), vec!["В данном чате нет категорий рейтинга".to_string()]);
+
+        message_handler(&send(33, &member, "!help"), domain_holder).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert!(texts[0].contains("<code>!me</code>"));
+        assert!(texts[0].contains("<code>!rating_categories</code>"));
+        assert!(!texts[0].contains("<code>!top"));
+        assert!(!texts[0].contains("<code>!report</code>"));
+        assert!(!texts[0].contains("<code>!settings</code>"));
+    }
+
+    #[tokio::test]
+    async fn moderator_roles_grant_commands_by_permission() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let owner = test_helpers::user(2, "Owner");
+        let helper = test_helpers::user(3, "Helper");
+        let member = test_helpers::user(4, "Member");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let reply = |message_id: i32, user: &teloxide::types::User, text: &str, to: &teloxide::types::User| {
+            test_helpers::message_context(&bot, test_helpers::reply(message_id, user, text, to))
+        };
+
+        message_handler(&reply(10, &admin, "!promote owner", &owner), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(11, &owner, "!promote helper", &helper), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(12, &owner, "!promote owner", &member), domain_holder.clone()).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert!(texts[0].contains("выдана роль <b>Владелец</b>"));
+        assert!(texts[1].contains("выдана роль <b>Помощник</b>"));
+        assert_eq!(texts[2], "Назначать и снимать владельцев могут только администраторы чата");
+        assert_eq!(store.tables().chat_roles.len(), 2);
+
+        message_handler(&reply(13, &helper, "!ban", &member), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(14, &helper, "!promote helper", &member), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(18, &helper, "!grep", &member), domain_holder.clone()).await.unwrap();
+        assert!(bot.take_calls().is_empty());
+        message_handler(&reply(19, &owner, "!grep", &member), domain_holder.clone()).await.unwrap();
+        assert!(reply_texts(bot.take_calls())[0].contains("Глобальная репутация"));
+        message_handler(&reply(15, &helper, "!ro 1h", &member), domain_holder.clone()).await.unwrap();
+        assert!(matches!(&bot.take_calls()[0], BotCall::RestrictChatMember { user_id: 4, .. }));
+
+        message_handler(&reply(16, &owner, "!demote", &helper), domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+        message_handler(&reply(17, &helper, "!ro 1h", &member), domain_holder).await.unwrap();
+        assert!(bot.take_calls().is_empty());
+        assert_eq!(store.tables().chat_roles.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn moderators_cancel_rating_changes_of_others() {
+        let store = Arc::new(MemoryStore::default());
+        let storage = Storage::Memory(store.clone());
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let moderator = test_helpers::user(2, "Moderator");
+        let helper = test_helpers::user(3, "Helper");
+        let voter = test_helpers::user(4, "Voter");
+        let target = test_helpers::user(5, "Target");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let reply = |message_id: i32, user: &teloxide::types::User, text: &str, to: &teloxide::types::User| {
+            test_helpers::message_context(&bot, test_helpers::reply(message_id, user, text, to))
+        };
+        message_handler(&reply(10, &admin, "!promote moderator", &moderator), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(11, &admin, "!promote helper", &helper), domain_holder.clone()).await.unwrap();
+        message_handler(&reply(12, &voter, "+", &target), domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+        let vote_ids = || store.tables().ratings
+            .iter()
+            .filter(|rating| rating.by_chat_user_id.is_some())
+            .map(|rating| rating.rating_id)
+            .collect::<Vec<_>>();
+        let vote_id = vote_ids()[0];
+        let rating_message = test_helpers::message(50, &test_helpers::user(99, "Bot"), "rating");
+        let press = |user: &teloxide::types::User| {
+            let data = format!("{} {}", voter.id, vote_id);
+            test_helpers::callback_context(&bot, test_helpers::callback_query(user, &rating_message, &data))
+        };
+
+        callback_handler(&press(&helper), domain_holder.clone()).await.unwrap();
+        match &bot.take_calls()[..] {
+            [BotCall::AnswerCallbackQuery { show_alert: true, .. }] => {}
+            calls => panic!("Expected the helper to be denied, got {:?}", calls),
+        }
+        assert_eq!(vote_ids(), vec![vote_id]);
+
+        callback_handler(&press(&moderator), domain_holder).await.unwrap();
+        match &bot.take_calls()[..] {
+            [BotCall::AnswerCallbackQuery { .. }, BotCall::DeleteMessage { message_id: 50, .. }] => {}
+            calls => panic!("Expected the vote to be cancelled, got {:?}", calls),
+        }
+        assert!(vote_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn moderation_checks_follow_chat_settings() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let admin = test_helpers::user(1, "Admin");
+        let member = test_helpers::user(2, "Member");
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![admin.clone()]));
+        let send = |message_id: i32, user: &teloxide::types::User, text: &str| {
+            test_helpers::message_context(&bot, test_helpers::message(message_id, user, text))
+        };
+
+        message_handler(&send(10, &admin, "!set link_action delete"), domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+        message_handler(&send(11, &member, "see https://free-cash.io"), domain_holder.clone()).await.unwrap();
+        let calls = bot.take_calls();
+        assert_eq!(calls[0], BotCall::DeleteMessage { chat_id: test_helpers::CHAT_ID, message_id: 11 });
+        assert!(!calls.iter().any(|call| matches!(call, BotCall::KickChatMember { .. })));
+
+        message_handler(&send(12, &admin, "!set link_check off"), domain_holder.clone()).await.unwrap();
+        bot.take_calls();
+        message_handler(&send(13, &member, "see https://free-cash.io"), domain_holder).await.unwrap();
+        assert!(bot.take_calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn history_replay_uses_past_ratings_and_skips_imported_votes() {
+        let storage = Storage::Memory(Arc::new(MemoryStore::default()));
+        let domain_holder = Arc::new(injected::new_domain_holder(&storage, test_helpers::config()).await);
+        let bot = Arc::new(RecordingBotActions::new());
+        let author = test_helpers::user(1, "Author");
+        let helper = test_helpers::user(2, "Helper");
+        let voter = test_helpers::user(3, "Voter");
+
+        // a vote given after the history makes Helper 110, the replayed vote must not count it
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &voter, "+", &helper));
+        message_handler(&cx, domain_holder.clone()).await.unwrap();
+        let history = || {
+            let date = chrono::NaiveDate::from_ymd(2021, 1, 1).and_hms(10, 0, 0);
+            vec![
+                crate::domains::user::HistoryMessage {
+                    id: 1,
+                    user_tg_id: author.id,
+                    full_name: "Author".to_string(),
+                    date,
+                    reply_to_message_id: None,
+                    text: "hello".to_string(),
+                },
+                crate::domains::user::HistoryMessage {
+                    id: 2,
+                    user_tg_id: helper.id,
+                    full_name: "Helper".to_string(),
+                    date: date + chrono::Duration::minutes(1),
+                    reply_to_message_id: Some(1),
+                    text: "+".to_string(),
+                },
+            ]
+        };
+
+        let summary = domain_holder.user.controller.replay_history(test_helpers::CHAT_ID, history()).await.unwrap();
+        assert_eq!((summary.ratings, summary.skipped_as_imported), (1, 0));
+        let summary = domain_holder.user.controller.replay_history(test_helpers::CHAT_ID, history()).await.unwrap();
+        assert_eq!((summary.ratings, summary.skipped_as_imported), (0, 1));
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(11, &author, "!me"));
+        bot.take_calls();
+        message_handler(&cx, domain_holder).await.unwrap();
+        let texts = reply_texts(bot.take_calls());
+        assert!(texts[0].contains("110.00"), "{}", texts[0]);
+    }
+}
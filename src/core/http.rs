@@ -0,0 +1,50 @@
+use crate::{core::persistence::Storage, lib};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::reply::{with_status, WithStatus};
+use warp::Filter;
+
+/// `/metrics` in the Prometheus text format, `/healthz` for liveness and `/readyz` checking the storage backends.
+pub fn service_routes(storage: Arc<Storage>) -> BoxedFilter<(WithStatus<String>,)> {
+    let metrics = warp::path("metrics")
+        .and(warp::path::end())
+        .map(|| match lib::metrics::render() {
+            Ok(body) => with_status(body, StatusCode::OK),
+            Err(err) => with_status(format!("{:#}", err), StatusCode::INTERNAL_SERVER_ERROR),
+        });
+
+    let liveness_storage = storage.clone();
+    let healthz = warp::path("healthz")
+        .and(warp::path::end())
+        .map(move || match liveness_storage.is_closed() {
+            false => with_status("ok".to_string(), StatusCode::OK),
+            true => with_status("storage is closed".to_string(), StatusCode::SERVICE_UNAVAILABLE),
+        });
+
+    let readyz = warp::path("readyz")
+        .and(warp::path::end())
+        .and_then(move || {
+            let storage = storage.clone();
+            async move {
+                let reply = match storage.check().await {
+                    Ok(_) => with_status("ok".to_string(), StatusCode::OK),
+                    Err(err) => {
+                        log::warn!("Readiness check failed: {:#}", err);
+                        with_status(format!("{:#}", err), StatusCode::SERVICE_UNAVAILABLE)
+                    }
+                };
+                Ok::<_, std::convert::Infallible>(reply)
+            }
+        });
+
+    warp::get().and(metrics.or(healthz).unify().or(readyz).unify()).boxed()
+}
+
+/// Serves `routes` on its own when updates come from long polling.
+pub fn spawn_server(address: SocketAddr, routes: BoxedFilter<(WithStatus<String>,)>) {
+    let (address, server) = warp::serve(routes).bind_ephemeral(address);
+    tokio::spawn(server);
+    log::info!("Serving metrics and health checks on {}", address);
+}
@@ -0,0 +1,42 @@
+use crate::{injected, lib::bot_actions::BotActions};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Background task running due jobs from the job table.
+pub struct JobWorker {
+    stop: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+}
+
+impl JobWorker {
+    /// Lets the batch in progress finish and waits for the worker to exit.
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        if let Err(err) = self.handle.await {
+            log::error!("Job worker panicked: {}", err);
+        }
+    }
+}
+
+pub fn spawn_worker(domain_holder: Arc<injected::DomainHolder>, requester: Arc<dyn BotActions>) -> JobWorker {
+    let (stop, mut stopped) = watch::channel(false);
+    let handle = tokio::spawn(async move {
+        log::info!("Job worker started");
+        while !*stopped.borrow() {
+            match domain_holder.job.controller.run_due_jobs(&*requester).await {
+                // A full batch means more jobs may be due already
+                Ok(count) if count as i64 >= domain_holder.config.load().jobs.batch_size => continue,
+                Ok(_) => {}
+                Err(err) => log::error!("Job worker errored: {:#}", err),
+            }
+            let interval = std::time::Duration::from_secs(domain_holder.config.load().jobs.poll_interval_seconds);
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = stopped.changed() => break,
+            }
+        }
+        log::info!("Job worker stopped");
+    });
+    JobWorker { stop, handle }
+}
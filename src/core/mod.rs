@@ -1,2 +1,7 @@
+pub mod chat_queue;
 pub mod handlers;
+pub mod http;
+pub mod jobs;
 pub mod persistence;
+pub mod shutdown;
+pub mod webhook;
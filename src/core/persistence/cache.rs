@@ -1,6 +1,5 @@
 use redis;
 
-pub async fn new_redis_client() -> redis::Client {
-    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL env var needs to be set");
+pub async fn new_redis_client(redis_url: &str) -> redis::Client {
     redis::Client::open(redis_url).expect("Unable to connect to Redis")
 }
\ No newline at end of file
@@ -1,32 +1,33 @@
-use crate::lib::helpers::get_envvar;
+use crate::lib::{config::StorageConfig, errors::ConfigError};
+use std::str::FromStr;
 use {sqlx, std};
 
-pub async fn new_pg_pool() -> sqlx::PgPool {
-    let options = sqlx::postgres::PgConnectOptions::new()
-        .host(&*get_envvar("POSTGRES_HOST"))
-        .port((&*get_envvar("POSTGRES_PORT")).parse().unwrap_or_else(|err| {
-            panic!("{:?}", err);
-        }))
-        .username(&*get_envvar("POSTGRES_USER"))
-        .password(&*get_envvar("POSTGRES_PASSWORD"))
-        .database(&*get_envvar("POSTGRES_DB"))
-        .to_owned();
+/// Connects to `database_url` when it's set, otherwise to the `postgres` settings.
+pub async fn new_pg_pool(config: &StorageConfig) -> Result<sqlx::PgPool, ConfigError> {
+    let options = match config.database_url.as_str() {
+        "" => sqlx::postgres::PgConnectOptions::new()
+            .host(&config.postgres.host)
+            .port(config.postgres.port)
+            .username(&config.postgres.user)
+            .password(&config.postgres.password)
+            .database(&config.postgres.database),
+        database_url => sqlx::postgres::PgConnectOptions::from_str(database_url).map_err(|err| {
+            ConfigError::Invalid(vec![format!("storage.database_url (DATABASE_URL): invalid Postgres URL: {}", err)])
+        })?,
+    };
 
-    let pool = sqlx::postgres::PgPoolOptions::new()
+    Ok(sqlx::postgres::PgPoolOptions::new()
         .max_connections(10)
         .connect_timeout(std::time::Duration::from_secs(1))
         .idle_timeout(std::time::Duration::from_secs(10))
         .connect_with(options)
         .await
-        .expect("Unable to connect to DB");
-
-    return pool;
+        .expect("Unable to connect to DB"))
 }
 
-pub async fn run_pg_migrations() {
-    let pool = new_pg_pool().await;
+pub async fn run_pg_migrations(pool: &sqlx::PgPool) {
     sqlx::migrate!()
-        .run(&pool)
+        .run(pool)
         .await
         .expect("Unable to run migrations");
 }
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+pub struct ChatRow {
+    pub chat_id: i64,
+    pub title: String,
+    pub is_active: bool,
+}
+
+pub struct ChatSettingsRow {
+    pub chat_id: i64,
+}
+
+pub struct ChatSettingRow {
+    pub chat_id: i64,
+    pub name: String,
+    pub value: String,
+}
+
+pub struct ChatCommandRow {
+    pub chat_id: i64,
+    pub command: String,
+    pub access: String,
+    pub min_rating: Option<sqlx::types::BigDecimal>,
+    pub cooldown_seconds: i32,
+}
+
+pub struct ChatRoleRow {
+    pub chat_id: i64,
+    pub telegram_id: i64,
+    pub role: String,
+    pub granted_by: i64,
+}
+
+pub struct UserRow {
+    pub user_id: sqlx::types::Uuid,
+    pub telegram_id: i64,
+    pub username: Option<String>,
+    pub first_name: String,
+    pub last_name: Option<String>,
+}
+
+impl UserRow {
+    /// Same as `CONCAT(first_name, ' ', last_name)` in the Postgres queries.
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name.clone().unwrap_or_default())
+    }
+}
+
+pub struct ChatUserRow {
+    pub chat_user_id: sqlx::types::Uuid,
+    pub user_id: sqlx::types::Uuid,
+    pub chat_id: i64,
+    pub created_at: chrono::NaiveDateTime,
+    pub messages_count: i64,
+    pub is_rating_optout: bool,
+    pub left_at: Option<chrono::NaiveDateTime>,
+    pub rejoined_at: Option<chrono::NaiveDateTime>,
+}
+
+pub struct RatingRow {
+    pub rating_id: sqlx::types::Uuid,
+    pub chat_user_id: Option<sqlx::types::Uuid>,
+    pub by_chat_user_id: Option<sqlx::types::Uuid>,
+    pub amount: sqlx::types::BigDecimal,
+    pub comment: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub rating_category_id: Option<sqlx::types::Uuid>,
+    pub source_id: Option<String>,
+}
+
+pub struct RatingCategoryRow {
+    pub rating_category_id: sqlx::types::Uuid,
+    pub chat_id: i64,
+    pub name: String,
+    pub triggers: Vec<String>,
+}
+
+pub struct ChatAutoDeleteRow {
+    pub chat_id: i64,
+    pub category: String,
+    pub ttl_seconds: i32,
+    pub delete_trigger: bool,
+}
+
+pub struct JobRow {
+    pub job_id: sqlx::types::Uuid,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub run_at: chrono::NaiveDateTime,
+    pub attempts: i32,
+    pub locked_until: Option<chrono::NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub failed_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Tables of the Postgres schema, kept in process memory.
+#[derive(Default)]
+pub struct MemoryTables {
+    pub chats: Vec<ChatRow>,
+    pub chat_settings: Vec<ChatSettingsRow>,
+    pub chat_setting: Vec<ChatSettingRow>,
+    pub chat_roles: Vec<ChatRoleRow>,
+    pub chat_commands: Vec<ChatCommandRow>,
+    pub chat_auto_delete: Vec<ChatAutoDeleteRow>,
+    pub users: Vec<UserRow>,
+    pub chat_users: Vec<ChatUserRow>,
+    pub ratings: Vec<RatingRow>,
+    pub rating_categories: Vec<RatingCategoryRow>,
+    pub jobs: Vec<JobRow>,
+}
+
+impl MemoryTables {
+    pub fn chat_user(&self, telegram_id: i64, chat_id: i64) -> Option<&ChatUserRow> {
+        let user = self.users.iter().find(|user| user.telegram_id == telegram_id)?;
+        self.chat_users
+            .iter()
+            .find(|chat_user| chat_user.user_id == user.user_id && chat_user.chat_id == chat_id)
+    }
+
+    pub fn chat_user_mut(&mut self, telegram_id: i64, chat_id: i64) -> Option<&mut ChatUserRow> {
+        let user_id = self.users.iter().find(|user| user.telegram_id == telegram_id)?.user_id;
+        self.chat_users
+            .iter_mut()
+            .find(|chat_user| chat_user.user_id == user_id && chat_user.chat_id == chat_id)
+    }
+}
+
+/// Replaces both Postgres and Redis for single-process deployments and tests. Nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<MemoryTables>,
+    cache: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MemoryStore {
+    pub fn tables(&self) -> MutexGuard<'_, MemoryTables> {
+        self.tables.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn set_ex(&self, key: String, value: String, ttl: Duration) {
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        cache.retain(|_, (_, expires_at)| *expires_at > now);
+        cache.insert(key, (value, now + ttl));
+    }
+
+    /// Like Redis `SET NX EX`: stores the value only when the key doesn't exist. Returns whether it was stored.
+    pub fn set_nx_ex(&self, key: String, value: String, ttl: Duration) -> bool {
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        cache.retain(|_, (_, expires_at)| *expires_at > now);
+        if cache.contains_key(&key) {
+            return false;
+        }
+        cache.insert(key, (value, now + ttl));
+        true
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        cache
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(value, _)| value.clone())
+    }
+
+    pub fn del(&self, key: &str) {
+        self.cache.lock().unwrap_or_else(|err| err.into_inner()).remove(key);
+    }
+
+    /// Seconds left before the key expires, or -2 like Redis `TTL` when it doesn't exist.
+    pub fn ttl(&self, key: &str) -> i64 {
+        let cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        match cache.get(key) {
+            Some((_, expires_at)) => match expires_at.checked_duration_since(Instant::now()) {
+                Some(left) => left.as_secs_f64().ceil() as i64,
+                None => -2,
+            },
+            None => -2,
+        }
+    }
+}
@@ -1,4 +1,107 @@
 mod db;
 mod cache;
+mod memory;
+mod sqlite;
 pub use db::{new_pg_pool, run_pg_migrations};
+pub use sqlite::{new_sqlite_pool, run_sqlite_migrations};
 pub use cache::new_redis_client;
+pub use memory::{
+    ChatAutoDeleteRow, ChatCommandRow, ChatRoleRow, ChatRow, ChatSettingRow, ChatSettingsRow, ChatUserRow, JobRow,
+    MemoryStore, MemoryTables, RatingCategoryRow, RatingRow, UserRow,
+};
+use crate::lib::{config::{Database, StorageConfig}, errors::ConfigError};
+use anyhow::Context;
+use std::sync::Arc;
+
+/// Where the repositories keep their state, chosen by `storage.backend` and the `storage.database_url` scheme.
+pub enum Storage {
+    Postgres {
+        pool: Arc<sqlx::PgPool>,
+        redis_client: Arc<redis::Client>,
+    },
+    /// Rating cooldowns and cached administrators don't need to survive a restart, so they stay in memory.
+    Sqlite {
+        pool: Arc<sqlx::SqlitePool>,
+        cache: Arc<MemoryStore>,
+    },
+    Memory(Arc<MemoryStore>),
+}
+
+impl Storage {
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Storage::Postgres { pool, .. } => pool.is_closed(),
+            Storage::Sqlite { pool, .. } => pool.is_closed(),
+            Storage::Memory(_) => false,
+        }
+    }
+
+    /// Waits for the pool connections to be returned and closes them.
+    /// The Redis client opens a connection per command, so there is nothing to close there.
+    pub async fn close(&self) {
+        match self {
+            Storage::Postgres { pool, .. } => pool.close().await,
+            Storage::Sqlite { pool, .. } => pool.close().await,
+            Storage::Memory(_) => {}
+        }
+    }
+
+    /// Makes a round trip to every backing store.
+    pub async fn check(&self) -> Result<(), anyhow::Error> {
+        match self {
+            Storage::Postgres { pool, redis_client } => {
+                sqlx::query("SELECT 1")
+                    .execute(&**pool)
+                    .await
+                    .context("Postgres is unavailable")?;
+                let mut conn = redis_client
+                    .get_async_connection()
+                    .await
+                    .context("Redis is unavailable")?;
+                redis::cmd("PING")
+                    .query_async::<_, String>(&mut conn)
+                    .await
+                    .context("Redis is unavailable")?;
+            }
+            Storage::Sqlite { pool, .. } => {
+                sqlx::query("SELECT 1")
+                    .execute(&**pool)
+                    .await
+                    .context("SQLite is unavailable")?;
+            }
+            Storage::Memory(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Fails on a `storage.backend` other than [`STORAGE_BACKENDS`](crate::lib::config::STORAGE_BACKENDS)
+/// and on a `storage.database_url` that isn't a Postgres or SQLite URL.
+pub async fn new_storage(config: &StorageConfig) -> Result<Storage, ConfigError> {
+    let storage = match config.backend.as_str() {
+        "database" => match config.database().map_err(|err| ConfigError::Invalid(vec![err]))? {
+            Database::Postgres => {
+                let pool = new_pg_pool(config).await?;
+                run_pg_migrations(&pool).await;
+                Storage::Postgres {
+                    pool: Arc::new(pool),
+                    redis_client: Arc::new(new_redis_client(&config.redis_url).await),
+                }
+            }
+            Database::Sqlite => {
+                let pool = new_sqlite_pool(&config.database_url).await?;
+                run_sqlite_migrations(&pool).await;
+                Storage::Sqlite {
+                    pool: Arc::new(pool),
+                    cache: Arc::new(MemoryStore::default()),
+                }
+            }
+        },
+        "memory" => {
+            log::warn!("Using in-memory storage, all data will be lost on restart");
+            Storage::Memory(Arc::new(MemoryStore::default()))
+        }
+        backend => return Err(ConfigError::Invalid(vec![crate::lib::config::unknown_backend_error(backend)])),
+    };
+    Ok(storage)
+}
@@ -0,0 +1,46 @@
+use crate::lib::errors::ConfigError;
+use std::str::FromStr;
+
+pub async fn new_sqlite_pool(database_url: &str) -> Result<sqlx::SqlitePool, ConfigError> {
+    let options = sqlx::sqlite::SqliteConnectOptions::from_str(database_url)
+        .map_err(|err| {
+            ConfigError::Invalid(vec![format!("storage.database_url (DATABASE_URL): invalid SQLite URL: {}", err)])
+        })?
+        .create_if_missing(true)
+        .foreign_keys(true);
+
+    Ok(sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(4)
+        .connect_with(options)
+        .await
+        .expect("Unable to open SQLite database"))
+}
+
+pub async fn run_sqlite_migrations(pool: &sqlx::SqlitePool) {
+    sqlx::migrate!("./migrations_sqlite")
+        .run(pool)
+        .await
+        .expect("Unable to run SQLite migrations");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn migrations_run_on_a_new_database() {
+        let pool = new_sqlite_pool("sqlite::memory:").await.unwrap();
+        run_sqlite_migrations(&pool).await;
+        let (tables,): (i64,) = sqlx::query_as("SELECT count(*) FROM sqlite_master WHERE name = 'chat_command'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(tables, 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_url_is_a_config_error() {
+        let err = new_sqlite_pool("sqlite:comparty.db?mode=sideways").await.unwrap_err();
+        assert!(err.to_string().contains("storage.database_url (DATABASE_URL): invalid SQLite URL"));
+    }
+}
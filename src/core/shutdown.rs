@@ -0,0 +1,70 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use teloxide::dispatching::ShutdownToken;
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static HANDLED: AtomicU64 = AtomicU64::new(0);
+
+/// Marks an update handler as running until dropped.
+pub struct HandlerGuard;
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+        HANDLED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn track_handler() -> HandlerGuard {
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    HandlerGuard
+}
+
+pub fn in_flight() -> usize {
+    IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+pub fn handled() -> u64 {
+    HANDLED.load(Ordering::Relaxed)
+}
+
+/// Resolves on SIGTERM (sent by `docker stop`) or SIGINT.
+async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Unable to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => "SIGTERM",
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.ok();
+        "Ctrl-C"
+    }
+}
+
+/// Drives `dispatching` until a termination signal, then stops fetching updates through `token` and gives
+/// the running handlers `deadline` to finish. Returns whether they all finished in time.
+pub async fn dispatch_until_signal(dispatching: impl Future<Output = ()>, token: ShutdownToken, deadline: Duration)
+    -> bool {
+    tokio::pin!(dispatching);
+    tokio::select! {
+        _ = &mut dispatching => return true,
+        name = signal() => log::info!("Received {}, no longer accepting updates", name),
+    }
+
+    if let Err(err) = token.shutdown() {
+        log::warn!("Unable to stop the dispatcher: {:?}", err);
+    }
+    match tokio::time::timeout(deadline, &mut dispatching).await {
+        Ok(_) => true,
+        Err(_) => {
+            log::warn!("{} handlers didn't finish in {}s", in_flight(), deadline.as_secs());
+            false
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{core::handlers, lib::config::WebhookConfig};
+use anyhow::Context;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use subtle::ConstantTimeEq;
+use teloxide::dispatching::stop_token::AsyncStopToken;
+use teloxide::dispatching::update_listeners::{StatefulListener, UpdateListener};
+use teloxide::types::Update;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::reply::WithStatus;
+use warp::Filter;
+
+const SECRET_TOKEN_HEADER: &str = "x-telegram-bot-api-secret-token";
+
+/// `setWebhook` goes through the HTTP API directly since teloxide doesn't know about `secret_token` yet.
+async fn call_bot_api(token: &str, method: &str, body: serde_json::Value) -> Result<(), anyhow::Error> {
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(format!("https://api.telegram.org/bot{}/{}", token, method))
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context(format!("Unable to call {}", method))?
+        .json()
+        .await
+        .context(format!("Unable to parse {} response", method))?;
+
+    match response["ok"].as_bool() {
+        Some(true) => Ok(()),
+        _ => Err(anyhow::anyhow!("{} failed: {}", method, response["description"])),
+    }
+}
+
+pub async fn set_webhook(token: &str, config: &WebhookConfig) -> Result<(), anyhow::Error> {
+    let url = config.public_url().context("webhook.url is not set")?;
+    let mut body = serde_json::json!({ "url": url, "allowed_updates": handlers::allowed_updates() });
+    if let Some(token) = &config.secret_token {
+        body["secret_token"] = token.clone().into();
+    }
+    call_bot_api(token, "setWebhook", body).await?;
+    log::info!("Webhook registered at {}", url);
+    Ok(())
+}
+
+pub async fn delete_webhook(token: &str) -> Result<(), anyhow::Error> {
+    call_bot_api(token, "deleteWebhook", serde_json::json!({})).await?;
+    log::info!("Webhook removed");
+    Ok(())
+}
+
+/// Compares in constant time, so response timing doesn't reveal how much of the token was guessed.
+fn is_secret_token_valid(expected: Option<&str>, received: Option<&str>) -> bool {
+    match (expected, received) {
+        (None, _) => true,
+        (Some(expected), Some(received)) => expected.as_bytes().ct_eq(received.as_bytes()).into(),
+        (Some(_), None) => false,
+    }
+}
+
+/// Starts the HTTP server with the webhook route next to `routes` and returns a listener
+/// the dispatcher reads updates from instead of `getUpdates`.
+pub async fn webhook_listener(
+    config: &WebhookConfig,
+    address: SocketAddr,
+    routes: BoxedFilter<(WithStatus<String>,)>,
+) -> impl UpdateListener<Infallible> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let path = config.path.clone();
+    let secret_token = config.secret_token.clone();
+
+    let route = warp::post()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>(SECRET_TOKEN_HEADER))
+        .and(warp::body::json())
+        .map(move |full_path: warp::path::FullPath, token: Option<String>, json: serde_json::Value| {
+            if full_path.as_str() != path {
+                return StatusCode::NOT_FOUND;
+            }
+            if !is_secret_token_valid(secret_token.as_deref(), token.as_deref()) {
+                log::warn!("Rejected webhook request with a wrong secret token");
+                return StatusCode::UNAUTHORIZED;
+            }
+            // Telegram retries anything but 200, so updates we can't parse are dropped here.
+            match Update::try_parse(&json) {
+                Ok(update) => {
+                    if tx.send(Ok(update)).is_err() {
+                        log::error!("Dispatcher is gone, dropping incoming update");
+                    }
+                }
+                Err(err) => log::warn!("Unable to parse incoming update: {}", err),
+            }
+            StatusCode::OK
+        });
+
+    let (stop_token, stop_flag) = AsyncStopToken::new_pair();
+    let (address, server) = warp::serve(route.or(routes)).bind_with_graceful_shutdown(address, stop_flag);
+    tokio::spawn(server);
+    log::info!("Listening for webhook updates on {}{}", address, config.path);
+
+    fn stream<S, T>(state: &mut (S, T)) -> &mut S {
+        &mut state.0
+    }
+    StatefulListener::new(
+        (UnboundedReceiverStream::new(rx), stop_token),
+        stream,
+        |state: &mut (_, AsyncStopToken)| state.1.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_token_must_match_when_set() {
+        assert!(is_secret_token_valid(None, None));
+        assert!(is_secret_token_valid(Some("s3cret"), Some("s3cret")));
+        assert!(!is_secret_token_valid(Some("s3cret"), Some("s3cre")));
+        assert!(!is_secret_token_valid(Some("s3cret"), None));
+    }
+}
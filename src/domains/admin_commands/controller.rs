@@ -1,47 +1,45 @@
-use crate::{domains::{admin_commands::service::AdminCommandsService, chat}, lib, Requester};
+use crate::{domains::{admin_commands::service::AdminCommandsService, job}, lib};
 use async_trait::async_trait;
-use teloxide::payloads::RestrictChatMemberSetters;
-use crate::lib::errors::AdminCommandsControllerError;
-use crate::lib::types::MessageContext;
+use std::sync::Arc;
+use crate::lib::{auto_delete::AutoDelete, enums::ReplyCategory};
 
 #[async_trait]
 pub trait AdminCommandsController: Send + Sync {
-    async fn report(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Mentions `admins` under the reported message.
+    async fn report(&self, cx: &lib::types::MessageContext, admins: &[i64])
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
     async fn mute_user(&self, cx: &lib::types::MessageContext, time: &str) -> Result<(), lib::errors::AdminCommandsControllerError>;
     async fn ban_user(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError>;
-    async fn get_settings(
-        &self,
-        cx: &lib::types::MessageContext,
-        settings: chat::ChatSettings
-    ) -> Result<(), lib::errors::AdminCommandsControllerError>;
+    async fn warn_user(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError>;
 }
 
 struct AdminCommandsControllerImpl {
     service: Box<dyn AdminCommandsService>,
+    auto_delete: Arc<dyn AutoDelete>,
+    jobs: Arc<dyn job::JobService>,
 }
 
 #[async_trait]
 impl AdminCommandsController for AdminCommandsControllerImpl {
-    async fn report(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError> {
-        let msg_text = match cx.update.reply_to_message() {
+    async fn report(&self, cx: &lib::types::MessageContext, admins: &[i64])
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let (category, msg_text) = match cx.update.reply_to_message() {
             Some(_) => {
-                let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(cx)
-                    .await
-                    .map_err(|err| err.into())
-                    .map_err(lib::errors::AdminCommandsControllerError::Report)?;
-                "Благодарим за репорт! Администрация разберётся в ситуации за кратчайшие сроки."
+                let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
+                let msg_text = "Благодарим за репорт! Администрация разберётся в ситуации за кратчайшие сроки."
                     .to_owned()
-                    + &admin_mentions.join("")
+                    + &admin_mentions.join("");
+                (ReplyCategory::Moderation, msg_text)
             }
-            None => "Используйте эту команду в ответ на сообщение!".to_string(),
+            None => (ReplyCategory::Error, "Используйте эту команду в ответ на сообщение!".to_string()),
         };
-        lib::tg_helpers::reply_to(cx, msg_text)
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, category, msg_text)
             .await
             .map_err(lib::errors::AdminCommandsControllerError::Report)
     }
 
     async fn mute_user(&self, cx: &lib::types::MessageContext, time: &str) -> Result<(), lib::errors::AdminCommandsControllerError> {
-        let msg_text = match cx.update.reply_to_message() {
+        let (category, msg_text) = match cx.update.reply_to_message() {
             Some(msg) => {
                 let sender = lib::tg_helpers::get_user_to_interact(
                     msg.from().unwrap().clone(),
@@ -54,22 +52,29 @@ impl AdminCommandsController for AdminCommandsControllerImpl {
                     .map_err(|err| err.into())
                     .map_err(lib::errors::AdminCommandsControllerError::MuteUser)?;
 
+                let chat_id = cx.update.chat_id();
+                let expire_date = restrict_time.to_expire_date(cx.update.date as i64);
                 let result = cx
                     .requester
-                    .restrict_chat_member(
-                        cx.update.chat_id(),
-                        sender.id,
-                        teloxide::types::ChatPermissions::default(),
-                    )
-                    .until_date(restrict_time.to_expire_date(cx.update.date as i64))
+                    .restrict_chat_member(chat_id, sender.id, expire_date)
                     .await;
 
                 match result {
-                    Ok(_) => format!(
-                        "Пользователь {} может только читать сообщения на протяжении <b>{}</b>.",
-                        teloxide::utils::html::user_mention_or_link(&sender),
-                        restrict_time.to_string(),
-                    ),
+                    Ok(_) => {
+                        lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["mute", "manual"]).inc();
+                        // Not relying on `until_date` alone, Telegram treats mutes under 30 seconds
+                        // or over 366 days as permanent
+                        let action = job::JobAction::LiftRestrictions { chat_id, user_id: sender.id };
+                        if let Err(err) = self.jobs.schedule(action, expire_date).await {
+                            log::error!("Unable to schedule unmuting user {} in chat {}: {:#}", sender.id, chat_id, err);
+                        }
+                        let msg_text = format!(
+                            "Пользователь {} может только читать сообщения на протяжении <b>{}</b>.",
+                            teloxide::utils::html::user_mention_or_link(&sender),
+                            restrict_time,
+                        );
+                        (ReplyCategory::Moderation, msg_text)
+                    }
                     Err(error) => {
                         log::error!(
                             "Oops, error occurred restricting the user (full name: {}, id: {}) : {:#?}",
@@ -77,21 +82,23 @@ impl AdminCommandsController for AdminCommandsControllerImpl {
                             sender.id,
                             error,
                         );
-                        "Невозможно ограничить права пользователя. \
-                        Пожалуйста, убедитесь, что бот имеет соответствующие права и повторите попытку позже.".to_string()
+                        let msg_text = "Невозможно ограничить права пользователя. \
+                        Пожалуйста, убедитесь, что бот имеет соответствующие права и повторите попытку позже."
+                            .to_string();
+                        (ReplyCategory::Error, msg_text)
                     }
                 }
             }
-            None => "Используйте эту команду в ответ на сообщение!".to_string(),
+            None => (ReplyCategory::Error, "Используйте эту команду в ответ на сообщение!".to_string()),
         };
 
-        lib::tg_helpers::reply_to(cx, msg_text)
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, category, msg_text)
             .await
             .map_err(lib::errors::AdminCommandsControllerError::MuteUser)
     }
 
     async fn ban_user(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError> {
-        let msg_text = match cx.update.reply_to_message() {
+        let (category, msg_text) = match cx.update.reply_to_message() {
             Some(msg) => {
                 let sender = lib::tg_helpers::get_user_to_interact(
                     msg.from().unwrap().clone(),
@@ -103,10 +110,14 @@ impl AdminCommandsController for AdminCommandsControllerImpl {
                     .await;
 
                 match result {
-                    Ok(_) => format!(
-                        "Пользователь {} выгнан из чата.",
-                        teloxide::utils::html::user_mention_or_link(&sender)
-                    ),
+                    Ok(_) => {
+                        lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["ban", "manual"]).inc();
+                        let msg_text = format!(
+                            "Пользователь {} выгнан из чата.",
+                            teloxide::utils::html::user_mention_or_link(&sender)
+                        );
+                        (ReplyCategory::Moderation, msg_text)
+                    }
                     Err(error) => {
                         log::error!(
                             "Oops, error occurred deleting the user (full name: {}, id: {}) : {:#?}",
@@ -114,37 +125,210 @@ impl AdminCommandsController for AdminCommandsControllerImpl {
                             sender.id,
                             error,
                         );
-                        "Невозможно выгнать пользователя. \
+                        let msg_text = "Невозможно выгнать пользователя. \
                         Пожалуйста, убедитесь, что бот имеет соответствующие права и повторите попытку позже."
-                            .to_string()
+                            .to_string();
+                        (ReplyCategory::Error, msg_text)
                     }
                 }
             }
-            None => "Используйте эту команду в ответ на сообщение!".to_string(),
+            None => (ReplyCategory::Error, "Используйте эту команду в ответ на сообщение!".to_string()),
         };
-        lib::tg_helpers::reply_to(cx, msg_text)
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, category, msg_text)
             .await
             .map_err(lib::errors::AdminCommandsControllerError::BanUser)
     }
 
-    async fn get_settings(&self, cx: &MessageContext, settings: chat::ChatSettings) -> Result<(), AdminCommandsControllerError> {
-        let text = format!(
-            "\
-<b>Настройки чата:</b>
-Подсчёт рейтинга: <b>{}</b>
-Команды доступны исключительно администраторам: <b>{}</b>
-",
-            lib::helpers::bool_to_string_switch(settings.is_rating_count),
-            lib::helpers::bool_to_string_switch(settings.commands_for_admin_only),
+    async fn warn_user(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let msg = match cx.update.reply_to_message() {
+            Some(msg) => msg,
+            None => {
+                return lib::tg_helpers::reply_to(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Error,
+                    "Используйте эту команду в ответ на сообщение!".to_string(),
+                )
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::WarnUser);
+            }
+        };
+        let sender = lib::tg_helpers::get_user_to_interact(msg.from().unwrap().clone(), msg.sender_chat());
+        lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["warn", "manual"]).inc();
+        let msg_text = format!(
+            "Пользователь {} получил предупреждение от модерации. \
+            Повторные нарушения могут привести к ограничению или бану.",
+            teloxide::utils::html::user_mention_or_link(&sender),
         );
-        lib::tg_helpers::reply_to(cx, text)
+        lib::tg_helpers::send_reply(cx, &*self.auto_delete, ReplyCategory::Moderation, msg_text, Some(msg.id), None)
             .await
-            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)
+            .map(|_| ())
+            .map_err(lib::errors::AdminCommandsControllerError::WarnUser)
     }
 }
 
 pub fn new_admin_commands_controller(
     service: Box<dyn AdminCommandsService>,
+    auto_delete: Arc<dyn AutoDelete>,
+    jobs: Arc<dyn job::JobService>,
 ) -> Box<dyn AdminCommandsController> {
-    Box::new(AdminCommandsControllerImpl { service })
+    Box::new(AdminCommandsControllerImpl { service, auto_delete, jobs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::{MemoryStore, Storage};
+    use crate::domains::admin_commands::service::new_admin_commands_service;
+    use crate::lib::{bot_actions::{BotCall, RecordingBotActions}, test_helpers};
+
+    async fn controller_with_store(store: &Arc<MemoryStore>) -> Box<dyn AdminCommandsController> {
+        let jobs = job::new_job_domain(&Storage::Memory(store.clone()), test_helpers::config()).await.service;
+        new_admin_commands_controller(new_admin_commands_service(), test_helpers::auto_delete(store).await, jobs)
+    }
+
+    async fn controller() -> Box<dyn AdminCommandsController> {
+        controller_with_store(&Arc::new(MemoryStore::default())).await
+    }
+
+    fn last_reply_text(bot: &RecordingBotActions) -> String {
+        match bot.calls().last() {
+            Some(BotCall::SendMessage { text, .. }) => text.clone(),
+            call => panic!("Expected a reply, got {:?}", call),
+        }
+    }
+
+    #[tokio::test]
+    async fn ban_user_kicks_replied_user() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let admin = test_helpers::user(1, "Admin");
+        let spammer = test_helpers::user(2, "Spammer");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ban", &spammer));
+
+        controller().await.ban_user(&cx).await.unwrap();
+
+        let calls = bot.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], BotCall::KickChatMember { chat_id: test_helpers::CHAT_ID, user_id: 2 });
+        assert!(matches!(&calls[1], BotCall::SendMessage { reply_to_message_id: Some(10), .. }));
+        assert!(last_reply_text(&bot).contains("выгнан из чата"));
+    }
+
+    #[tokio::test]
+    async fn ban_user_reports_failed_kick() {
+        let bot = Arc::new(RecordingBotActions::new().with_failing("kickChatMember"));
+        let admin = test_helpers::user(1, "Admin");
+        let spammer = test_helpers::user(2, "Spammer");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ban", &spammer));
+
+        controller().await.ban_user(&cx).await.unwrap();
+
+        assert!(last_reply_text(&bot).starts_with("Невозможно выгнать пользователя"));
+    }
+
+    #[tokio::test]
+    async fn ban_user_requires_reply() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let admin = test_helpers::user(1, "Admin");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &admin, "!ban"));
+
+        controller().await.ban_user(&cx).await.unwrap();
+
+        assert_eq!(bot.calls().len(), 1);
+        assert_eq!(last_reply_text(&bot), "Используйте эту команду в ответ на сообщение!");
+    }
+
+    #[tokio::test]
+    async fn warn_user_replies_to_warned_message() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let moderator = test_helpers::user(1, "Moderator");
+        let flooder = test_helpers::user(2, "Flooder");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &moderator, "!warn", &flooder));
+
+        controller().await.warn_user(&cx).await.unwrap();
+
+        assert!(matches!(&bot.calls()[0], BotCall::SendMessage { reply_to_message_id: Some(9), .. }));
+        assert!(last_reply_text(&bot).contains("получил предупреждение"));
+    }
+
+    #[tokio::test]
+    async fn mute_user_restricts_until_requested_time() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let admin = test_helpers::user(1, "Admin");
+        let flooder = test_helpers::user(2, "Flooder");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ro 2h", &flooder));
+
+        controller().await.mute_user(&cx, "2h").await.unwrap();
+
+        let calls = bot.calls();
+        assert_eq!(
+            calls[0],
+            BotCall::RestrictChatMember {
+                chat_id: test_helpers::CHAT_ID,
+                user_id: 2,
+                until_date: test_helpers::MESSAGE_DATE + 2 * 60 * 60,
+            }
+        );
+        assert!(last_reply_text(&bot).contains("может только читать сообщения"));
+    }
+
+    #[tokio::test]
+    async fn mute_user_schedules_unmute() {
+        let store = Arc::new(MemoryStore::default());
+        let bot = Arc::new(RecordingBotActions::new());
+        let admin = test_helpers::user(1, "Admin");
+        let flooder = test_helpers::user(2, "Flooder");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ro 2h", &flooder));
+
+        controller_with_store(&store).await.mute_user(&cx, "2h").await.unwrap();
+
+        let tables = store.tables();
+        let unmute = tables.jobs.iter().find(|job| job.kind == "lift_restrictions").unwrap();
+        assert_eq!(unmute.payload, serde_json::json!({ "chat_id": test_helpers::CHAT_ID, "user_id": 2 }));
+        assert_eq!(unmute.run_at.timestamp(), test_helpers::MESSAGE_DATE + 2 * 60 * 60);
+    }
+
+    #[tokio::test]
+    async fn failed_mute_schedules_nothing() {
+        let store = Arc::new(MemoryStore::default());
+        let bot = Arc::new(RecordingBotActions::new().with_failing("restrictChatMember"));
+        let admin = test_helpers::user(1, "Admin");
+        let flooder = test_helpers::user(2, "Flooder");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ro 2h", &flooder));
+
+        controller_with_store(&store).await.mute_user(&cx, "2h").await.unwrap();
+
+        assert!(store.tables().jobs.iter().all(|job| job.kind != "lift_restrictions"));
+    }
+
+    #[tokio::test]
+    async fn mute_user_rejects_invalid_time() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let admin = test_helpers::user(1, "Admin");
+        let flooder = test_helpers::user(2, "Flooder");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &admin, "!ro forever", &flooder));
+
+        let result = controller().await
+            .mute_user(&cx, "forever")
+            .await;
+
+        assert!(result.is_err());
+        assert!(bot.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn report_mentions_given_admins() {
+        let bot = Arc::new(RecordingBotActions::new().with_admins(vec![test_helpers::user(3, "Admin")]));
+        let reporter = test_helpers::user(2, "Reporter");
+        let offender = test_helpers::user(4, "Offender");
+        let cx = test_helpers::message_context(&bot, test_helpers::reply(10, &reporter, "!report", &offender));
+
+        controller().await.report(&cx, &[1]).await.unwrap();
+
+        let text = last_reply_text(&bot);
+        assert!(text.starts_with("Благодарим за репорт!"));
+        assert!(text.contains("tg://user?id=1"));
+        assert!(!text.contains("tg://user?id=3"));
+        assert_eq!(bot.admin_lookups(), 0);
+    }
 }
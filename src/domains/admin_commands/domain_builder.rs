@@ -1,11 +1,12 @@
-use crate::domains::admin_commands::{controller, service};
+use crate::{domains::{admin_commands::{controller, service}, job::JobService}, lib::auto_delete::AutoDelete};
+use std::sync::Arc;
 
 pub struct AdminCommandsDomain {
     pub controller: Box<dyn controller::AdminCommandsController>,
 }
 
-pub async fn new_admin_commands_domain() -> AdminCommandsDomain {
+pub async fn new_admin_commands_domain(auto_delete: Arc<dyn AutoDelete>, jobs: Arc<dyn JobService>) -> AdminCommandsDomain {
     let service = service::new_admin_commands_service();
-    let controller = controller::new_admin_commands_controller(service);
+    let controller = controller::new_admin_commands_controller(service, auto_delete, jobs);
     AdminCommandsDomain { controller }
 }
@@ -13,7 +13,7 @@ struct AdminCommandsServiceImpl;
 impl AdminCommandsService for AdminCommandsServiceImpl {
     async fn get_restrict_time(&self, time: &str) -> Result<lib::enums::TimeUnits, lib::errors::AdminCommandsError> {
         lib::enums::TimeUnits::from_str(time)
-            .map_err(|err| lib::errors::AdminCommandsError::GetRestrictMentions(err))
+            .map_err(lib::errors::AdminCommandsError::GetRestrictMentions)
     }
 }
 
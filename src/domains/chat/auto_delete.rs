@@ -0,0 +1,106 @@
+use crate::{
+    domains::{chat::service::ChatService, job},
+    lib::{self, enums::ReplyCategory},
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use teloxide::types::Chat;
+
+struct AutoDeleteImpl {
+    service: Arc<dyn ChatService>,
+    jobs: Arc<dyn job::JobService>,
+}
+
+#[async_trait]
+impl lib::auto_delete::AutoDelete for AutoDeleteImpl {
+    async fn reply_sent(&self, chat: &Chat, trigger_message_id: Option<i32>, category: ReplyCategory, message_id: i32) {
+        if chat.is_private() {
+            return;
+        }
+        let chat_id = chat.id;
+        let rule = match self.service.get_auto_delete_rules(chat_id).await {
+            Ok(rules) => rules.into_iter().find(|rule| rule.category == category.name()),
+            Err(err) => {
+                log::error!("Unable to get auto-delete rules (chat id: {}): {:#}", chat_id, err);
+                return;
+            }
+        };
+        let rule = match rule {
+            Some(rule) if rule.ttl_seconds > 0 => rule,
+            _ => return,
+        };
+
+        let delete_at = chrono::Utc::now() + chrono::Duration::seconds(rule.ttl_seconds as i64);
+        let mut message_ids = vec![message_id];
+        if rule.delete_trigger {
+            message_ids.extend(trigger_message_id);
+        }
+        for message_id in message_ids {
+            let action = job::JobAction::DeleteMessage { chat_id, message_id };
+            if let Err(err) = self.jobs.schedule(action, delete_at).await {
+                log::error!("Unable to schedule deleting message {} in chat {}: {:#}", message_id, chat_id, err);
+            }
+        }
+    }
+}
+
+pub fn new_auto_delete(service: Arc<dyn ChatService>, jobs: Arc<dyn job::JobService>)
+    -> Arc<dyn lib::auto_delete::AutoDelete> {
+    Arc::new(AutoDeleteImpl { service, jobs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::{MemoryStore, Storage};
+    use crate::domains::chat::{model, repositories, service::new_chat_service};
+    use crate::lib::test_helpers;
+
+    async fn reply_sent(store: &Arc<MemoryStore>, category: ReplyCategory) {
+        let service: Arc<dyn ChatService> = Arc::from(new_chat_service(
+            repositories::new_memory_chat_db_repository(store.clone()),
+            repositories::new_memory_chat_cache_repository(store.clone()),
+            test_helpers::config(),
+        ));
+        let jobs = job::new_job_domain(&Storage::Memory(store.clone()), test_helpers::config()).await.service;
+        let message = test_helpers::message(10, &test_helpers::user(1, "Member"), "!me");
+        new_auto_delete(service, jobs).reply_sent(&message.chat, Some(message.id), category, 11).await;
+    }
+
+    fn scheduled_message_ids(store: &MemoryStore) -> Vec<i64> {
+        store.tables().jobs.iter().map(|job| job.payload["message_id"].as_i64().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn rating_confirmation_is_deleted_with_the_vote_by_default() {
+        let store = Arc::new(MemoryStore::default());
+
+        reply_sent(&store, ReplyCategory::Rating).await;
+        reply_sent(&store, ReplyCategory::Info).await;
+
+        assert_eq!(scheduled_message_ids(&store), vec![11, 10]);
+    }
+
+    #[tokio::test]
+    async fn chat_rule_replaces_the_default() {
+        let store = Arc::new(MemoryStore::default());
+        let repo = repositories::new_memory_chat_db_repository(store.clone());
+        for (category, ttl_seconds) in [("info", 300), ("rating", 0)] {
+            repo.set_auto_delete_rule(&model::AutoDeleteRule {
+                chat_id: test_helpers::CHAT_ID,
+                category: category.to_string(),
+                ttl_seconds,
+                delete_trigger: false,
+            })
+                .await
+                .unwrap();
+        }
+
+        reply_sent(&store, ReplyCategory::Rating).await;
+        reply_sent(&store, ReplyCategory::Info).await;
+
+        assert_eq!(scheduled_message_ids(&store), vec![11]);
+        let run_at = store.tables().jobs[0].run_at;
+        assert!(run_at > (chrono::Utc::now() + chrono::Duration::seconds(290)).naive_utc());
+    }
+}
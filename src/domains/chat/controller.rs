@@ -1,37 +1,250 @@
-use crate::{domains::chat::{model, service::ChatService}, lib};
+use crate::{
+    domains::chat::{model, registry, service::ChatService, settings_panel::{self, PanelAction}},
+    lib::{
+        self,
+        auto_delete::AutoDelete,
+        bot_actions::BotActions,
+        enums::{Command, ModeratorRole, Permission, ReplyCategory},
+    },
+};
 use async_trait::async_trait;
+use std::{str::FromStr, sync::Arc};
+use teloxide::types::ChatMemberKind;
+
+type HasRight = fn(&ChatMemberKind) -> bool;
+
+/// Rights the always-on moderation can't work without, with the features that stop working.
+const REQUIRED_RIGHTS: [(&str, HasRight, &str); 2] = [
+    (
+        "удаление сообщений",
+        ChatMemberKind::can_delete_messages,
+        "удаление спама и автоудаление ответов бота",
+    ),
+    (
+        "блокировка участников",
+        ChatMemberKind::can_restrict_members,
+        "команды <code>!ro</code> и <code>!ban</code>, блокировка спамеров",
+    ),
+];
 
 #[async_trait]
 pub trait ChatController: Send + Sync {
     async fn create_if_not_exists(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::ChatError>;
+    async fn create_by_id(&self, chat_id: i64, title: &str) -> Result<(), lib::errors::ChatError>;
     async fn migrate_chat(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::ChatError>;
+    /// Forgets the cached administrators when the member became or stopped being one.
+    async fn member_changed(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::ChatError>;
+    /// Tracks whether the bot is still in the chat and warns the chat when the bot lost rights it needs.
+    async fn bot_member_changed(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::ChatError>;
     async fn get_chat_settings(&self, cx: &lib::types::MessageContext) -> Result<model::ChatSettings, lib::errors::ChatError>;
-    async fn change_chat_settings(
+    /// Telegram ids of the chat administrators who aren't bots, asked from Telegram only when the cached list
+    /// expired.
+    async fn get_admins(&self, requester: &dyn BotActions, chat_id: i64) -> Result<Vec<i64>, lib::errors::ChatError>;
+    /// Bot-level role of the member, `None` when the member has none.
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<ModeratorRole>, lib::errors::ChatError>;
+    /// Whether the member is a chat administrator or has a role with the permission.
+    async fn has_permission(
+        &self,
+        requester: &dyn BotActions,
+        chat_id: i64,
+        telegram_id: i64,
+        permission: Permission,
+    ) -> Result<bool, lib::errors::ChatError>;
+    /// Grants the role to the author of the replied message. Only administrators grant and take away owners.
+    async fn promote(&self, cx: &lib::types::MessageContext, role: &str, is_admin: bool)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Takes away the role of the author of the replied message.
+    async fn demote(&self, cx: &lib::types::MessageContext, is_admin: bool)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Replies with the value of a registry setting, or with every registry setting when `key` is `None`.
+    async fn get_setting(&self, cx: &lib::types::MessageContext, key: Option<&str>)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Sets a registry setting from the text typed by the user.
+    async fn set_setting(&self, cx: &lib::types::MessageContext, key: &str, value: &str)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Replies with the settings panel, a message whose buttons change the settings.
+    async fn show_settings_panel(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Handles a press on a settings panel button: only administrators and members whose role may change
+    /// the settings may press them, and the panel is edited in place to show the result.
+    async fn handle_settings_callback(&self, cx: &lib::types::CallbackContext, data: &str)
+        -> Result<(), lib::errors::ChatError>;
+    async fn get_auto_delete(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    async fn change_auto_delete(
+        &self,
+        cx: &lib::types::MessageContext,
+        category: ReplyCategory,
+        ttl_seconds: i32,
+        delete_trigger: bool,
+    ) -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Replies with the policy of every member command.
+    async fn get_command_policies(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Changes who may use the command, `min_rating` is required by the `rating` access only.
+    async fn set_command_access(
         &self,
         cx: &lib::types::MessageContext,
-        body: model::ChatSettings
+        command: &str,
+        access: &str,
+        min_rating: Option<&str>,
     ) -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Changes the per member cooldown of the command, `off` removes it.
+    async fn set_command_cooldown(&self, cx: &lib::types::MessageContext, command: &str, time: &str)
+        -> Result<(), lib::errors::AdminCommandsControllerError>;
+    /// Seconds left of the member's running cooldown of the command, `None` when the command may be used.
+    /// A failing cache doesn't block the command.
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        cooldown_seconds: i32,
+    ) -> Option<i64>;
+    /// Whether to tell the member about the running cooldown, only the first attempt in the window is answered.
+    async fn start_cooldown_notice(&self, chat_id: i64, telegram_id: i64, command: Command, seconds_left: i64) -> bool;
 }
 
 struct ChatControllerImpl {
-    service: Box<dyn ChatService>,
+    service: Arc<dyn ChatService>,
+    auto_delete: Arc<dyn AutoDelete>,
+}
+
+impl ChatControllerImpl {
+    /// Changes one part of the command policy, the rest of the policy stays as it is.
+    async fn change_command_policy(
+        &self,
+        cx: &lib::types::MessageContext,
+        command: Command,
+        change: impl FnOnce(&mut model::CommandPolicy) + Send,
+    ) -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let chat_id = cx.update.chat_id();
+        let result = match self.service.get_chat_settings(chat_id).await {
+            Ok(settings) => {
+                let mut policy = settings.command_policy(command);
+                change(&mut policy);
+                self.service.set_command_policy(chat_id, command, &policy).await.map(|_| policy)
+            }
+            Err(err) => Err(err),
+        };
+        let msg_text = match result {
+            Ok(policy) => format!("Доступ к команде изменён\n{}", describe_command_policy(command, &policy)),
+            Err(_) => "Невозможно изменить доступ к команде".to_string(),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings)
+    }
+}
+
+fn describe_auto_delete(rule: &model::AutoDeleteRule) -> String {
+    match (rule.ttl_seconds, rule.delete_trigger) {
+        (0, _) => format!("<code>{}</code>: <b>выключено</b>", rule.category),
+        (ttl_seconds, false) => format!(
+            "<code>{}</code>: через <b>{}</b>",
+            rule.category,
+            lib::enums::TimeUnits::from_seconds(ttl_seconds),
+        ),
+        (ttl_seconds, true) => format!(
+            "<code>{}</code>: через <b>{}</b>, вместе с командой",
+            rule.category,
+            lib::enums::TimeUnits::from_seconds(ttl_seconds),
+        ),
+    }
+}
+
+const ROLE_TARGET_REQUIRED: &str = "Используйте эту команду в ответ на сообщение участника!";
+const OWNERS_BY_ADMINS_ONLY: &str = "Назначать и снимать владельцев могут только администраторы чата";
+
+/// The author of the replied message, bots and channels can't have roles.
+fn role_target(cx: &lib::types::MessageContext) -> Option<&teloxide::types::User> {
+    cx.update
+        .reply_to_message()
+        .filter(|message| message.sender_chat().is_none())
+        .and_then(|message| message.from())
+        .filter(|user| !user.is_bot)
+}
+
+fn unknown_role(role: &str) -> String {
+    format!(
+        "Неизвестная роль <code>{}</code>. Доступные роли: {}",
+        role,
+        ModeratorRole::ALL.iter().map(|role| format!("<code>{}</code>", role.name())).collect::<Vec<_>>().join(", "),
+    )
+}
+
+fn describe_command_policy(command: Command, policy: &model::CommandPolicy) -> String {
+    match policy.cooldown_seconds {
+        0 => format!("<code>!{}</code>: <b>{}</b>", command.name(), policy.access.describe()),
+        cooldown_seconds => format!(
+            "<code>!{}</code>: <b>{}</b>, не чаще раза в <b>{}</b>",
+            command.name(),
+            policy.access.describe(),
+            lib::enums::TimeUnits::from_seconds(cooldown_seconds),
+        ),
+    }
+}
+
+fn unknown_command(command: &str) -> String {
+    format!(
+        "Неизвестная команда <code>{}</code>. Доступные команды: {}",
+        teloxide::utils::html::escape(command),
+        Command::ALL.iter().map(|command| format!("<code>!{}</code>", command.name())).collect::<Vec<_>>().join(", "),
+    )
+}
+
+fn describe_setting(setting: &registry::Setting, settings: &model::ChatSettings) -> String {
+    format!(
+        "<code>{}</code>: <b>{}</b>\n{} (по умолчанию: <code>{}</code>)",
+        setting.key,
+        settings.values.get(setting).describe(),
+        setting.description,
+        setting.default,
+    )
+}
+
+fn unknown_setting(key: &str) -> String {
+    format!(
+        "Неизвестная настройка <code>{}</code>. Доступные настройки: {}",
+        teloxide::utils::html::escape(key),
+        registry::SETTINGS.iter().map(|setting| format!("<code>{}</code>", setting.key)).collect::<Vec<_>>().join(", "),
+    )
+}
+
+fn describe_lost_rights(old: &ChatMemberKind, new: &ChatMemberKind) -> Option<String> {
+    let lost_rights = REQUIRED_RIGHTS
+        .iter()
+        .filter(|(_, has_right, _)| has_right(old) && !has_right(new))
+        .map(|(right, _, features)| format!("<b>{}</b> – {}", right, features))
+        .collect::<Vec<_>>();
+    match lost_rights.is_empty() {
+        true => None,
+        false => Some(format!(
+            "Бот лишился прав, без которых не работают:\n{}\nВерните боту эти права в настройках администраторов чата",
+            lost_rights.join("\n"),
+        )),
+    }
 }
 
 #[async_trait]
 impl ChatController for ChatControllerImpl {
     async fn create_if_not_exists(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::ChatError> {
         let chat = &cx.update.chat;
+        self.create_by_id(chat.id, chat.title().unwrap()).await
+    }
+
+    async fn create_by_id(&self, chat_id: i64, title: &str) -> Result<(), lib::errors::ChatError> {
         self.service
             .create(model::Chat {
-                chat_id: chat.id,
-                title: chat.title().unwrap().to_string(),
+                chat_id,
+                title: title.to_string(),
             })
             .await?;
         self.service
             .create_chat_settings(&model::ChatSettings {
-                chat_id: chat.id,
-                is_rating_count: true,
-                commands_for_admin_only: false,
+                chat_id,
+                values: registry::SettingValues::default(),
+                commands: Default::default(),
             })
             .await
     }
@@ -44,36 +257,467 @@ impl ChatController for ChatControllerImpl {
             .await
     }
 
+    async fn member_changed(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::ChatError> {
+        let update = &cx.update;
+        if update.old_chat_member.kind.is_privileged() || update.new_chat_member.kind.is_privileged() {
+            self.service.forget_admins(update.chat.id).await;
+        }
+        Ok(())
+    }
+
+    async fn bot_member_changed(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::ChatError> {
+        let update = &cx.update;
+        let chat_id = update.chat.id;
+        self.service.forget_admins(chat_id).await;
+
+        if !update.new_chat_member.kind.is_present() {
+            return self.service.change_active(chat_id, false).await;
+        }
+        if !update.old_chat_member.kind.is_present() {
+            self.create_by_id(chat_id, update.chat.title().unwrap_or_default()).await?;
+            self.service.change_active(chat_id, true).await?;
+        }
+
+        match describe_lost_rights(&update.old_chat_member.kind, &update.new_chat_member.kind) {
+            Some(msg_text) => {
+                lib::tg_helpers::send_to_chat(
+                    &*cx.requester,
+                    &*self.auto_delete,
+                    &update.chat,
+                    ReplyCategory::Moderation,
+                    msg_text,
+                )
+                    .await
+                    .map_err(lib::errors::ChatError::WarnMissingRights)
+            }
+            None => Ok(()),
+        }
+    }
+
     async fn get_chat_settings(&self, cx: &lib::types::MessageContext) -> Result<model::ChatSettings, lib::errors::ChatError> {
         self.service
             .get_chat_settings(cx.update.chat_id())
             .await
     }
 
-    async fn change_chat_settings(
+    async fn get_admins(&self, requester: &dyn BotActions, chat_id: i64) -> Result<Vec<i64>, lib::errors::ChatError> {
+        if let Some(admins) = self.service.get_cached_admins(chat_id).await {
+            return Ok(admins);
+        }
+        let admins = requester
+            .get_chat_administrators(chat_id)
+            .await
+            .map_err(lib::errors::ChatError::GetAdmins)?
+            .iter()
+            .filter(|admin| !admin.is_bot)
+            .map(|admin| admin.id)
+            .collect::<Vec<_>>();
+        self.service.cache_admins(chat_id, &admins).await;
+        Ok(admins)
+    }
+
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<ModeratorRole>, lib::errors::ChatError> {
+        self.service.get_role(chat_id, telegram_id).await
+    }
+
+    async fn has_permission(
         &self,
-        cx: &lib::types::MessageContext,
-        body: model::ChatSettings
-    ) -> Result<(), lib::errors::AdminCommandsControllerError> {
-        let result = self
-            .service
-            .change_chat_settings(&model::ChatSettings {
-                chat_id: body.chat_id,
-                is_rating_count: body.is_rating_count,
-                commands_for_admin_only: body.commands_for_admin_only,
-            })
-            .await;
+        requester: &dyn BotActions,
+        chat_id: i64,
+        telegram_id: i64,
+        permission: Permission,
+    ) -> Result<bool, lib::errors::ChatError> {
+        if self.get_admins(requester, chat_id).await?.contains(&telegram_id) {
+            return Ok(true);
+        }
+        Ok(self.service
+            .get_role(chat_id, telegram_id)
+            .await?
+            .is_some_and(|role| role.permits(permission)))
+    }
+
+    async fn promote(&self, cx: &lib::types::MessageContext, role: &str, is_admin: bool)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let chat_id = cx.update.chat_id();
+        let (category, msg_text) = match (role.parse::<ModeratorRole>(), role_target(cx)) {
+            (Err(_), _) => (ReplyCategory::Error, unknown_role(role)),
+            (_, None) => (ReplyCategory::Error, ROLE_TARGET_REQUIRED.to_string()),
+            (Ok(role), Some(member)) => {
+                let current = self.service
+                    .get_role(chat_id, member.id)
+                    .await
+                    .map_err(|err| err.into())
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeRole)?;
+                if !is_admin && (role == ModeratorRole::Owner || current == Some(ModeratorRole::Owner)) {
+                    (ReplyCategory::Error, OWNERS_BY_ADMINS_ONLY.to_string())
+                } else {
+                    match self.service.set_role(chat_id, member.id, role, cx.update.from().unwrap().id).await {
+                        Ok(_) => (ReplyCategory::Moderation, format!(
+                            "Пользователю {} выдана роль <b>{}</b>",
+                            teloxide::utils::html::user_mention_or_link(member),
+                            role.title(),
+                        )),
+                        Err(_) => (ReplyCategory::Error, "Невозможно изменить роль пользователя".to_string()),
+                    }
+                }
+            }
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, category, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::ChangeRole)
+    }
+
+    async fn demote(&self, cx: &lib::types::MessageContext, is_admin: bool)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let chat_id = cx.update.chat_id();
+        let member = match role_target(cx) {
+            Some(member) => member,
+            None => {
+                let msg_text = ROLE_TARGET_REQUIRED.to_string();
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, msg_text)
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeRole);
+            }
+        };
+        let current = self.service
+            .get_role(chat_id, member.id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::AdminCommandsControllerError::ChangeRole)?;
+        let (category, msg_text) = match current {
+            None => (ReplyCategory::Error, "У пользователя нет роли".to_string()),
+            Some(ModeratorRole::Owner) if !is_admin => (ReplyCategory::Error, OWNERS_BY_ADMINS_ONLY.to_string()),
+            Some(role) => match self.service.delete_role(chat_id, member.id).await {
+                Ok(_) => (ReplyCategory::Moderation, format!(
+                    "Пользователь {} лишён роли <b>{}</b>",
+                    teloxide::utils::html::user_mention_or_link(member),
+                    role.title(),
+                )),
+                Err(_) => (ReplyCategory::Error, "Невозможно изменить роль пользователя".to_string()),
+            },
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, category, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::ChangeRole)
+    }
+
+    async fn get_setting(&self, cx: &lib::types::MessageContext, key: Option<&str>)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let settings = self.service
+            .get_chat_settings(cx.update.chat_id())
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)?;
+        let msg_text = match key.map(|key| (key, registry::find(key))) {
+            Some((_, Some(setting))) => describe_setting(setting, &settings),
+            Some((key, None)) => unknown_setting(key),
+            None => std::iter::once("<b>Настройки чата:</b>".to_string())
+                .chain(registry::SETTINGS.iter().map(|setting| describe_setting(setting, &settings)))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)
+    }
+
+    async fn set_setting(&self, cx: &lib::types::MessageContext, key: &str, value: &str)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let setting = match registry::find(key) {
+            Some(setting) => setting,
+            None => {
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, unknown_setting(key))
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings);
+            }
+        };
+        let value = match setting.parse(value) {
+            Ok(value) => value,
+            Err(hint) => {
+                return lib::tg_helpers::reply_to(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Error,
+                    format!("Некорректное значение <code>{}</code>. {}", setting.key, hint),
+                )
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings);
+            }
+        };
+        let result = match self.service.get_chat_settings(cx.update.chat_id()).await {
+            Ok(mut settings) => {
+                settings.values.set(setting.key, value);
+                self.service.change_chat_settings(&settings).await
+            }
+            Err(err) => Err(err),
+        };
         let msg_text = match result {
             Ok(_) => "Настройки чата успешно изменены",
             Err(_) => "Невозможно изменить настройки чата"
         }.to_string();
 
-        lib::tg_helpers::reply_to(cx, msg_text)
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
             .await
             .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings)
     }
+
+    async fn show_settings_panel(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let settings = self.service
+            .get_chat_settings(cx.update.chat_id())
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)?;
+        lib::tg_helpers::send_reply(
+            cx,
+            &*self.auto_delete,
+            ReplyCategory::Settings,
+            settings_panel::main_text(&settings),
+            Some(cx.update.id),
+            Some(settings_panel::main_keyboard(&settings)),
+        )
+            .await
+            .map(|_| ())
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)
+    }
+
+    async fn handle_settings_callback(&self, cx: &lib::types::CallbackContext, data: &str)
+        -> Result<(), lib::errors::ChatError> {
+        let message = match &cx.update.message {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let chat_id = message.chat_id();
+        let is_allowed = self
+            .has_permission(&*cx.requester, chat_id, cx.update.from.id, Permission::ChangeSettings)
+            .await?;
+        if !is_allowed {
+            return cx.requester
+                .answer_callback_query(
+                    &cx.update.id,
+                    "Изменять настройки чата могут только администраторы и владельцы".to_string(),
+                    true,
+                )
+                .await
+                .map_err(lib::errors::ChatError::SettingsPanel);
+        }
+
+        let action = match PanelAction::parse(data) {
+            Some(action) => action,
+            None => {
+                return cx.requester
+                    .answer_callback_query(&cx.update.id, String::new(), false)
+                    .await
+                    .map_err(lib::errors::ChatError::SettingsPanel);
+            }
+        };
+        if action == PanelAction::Close {
+            cx.requester
+                .answer_callback_query(&cx.update.id, String::new(), false)
+                .await
+                .map_err(lib::errors::ChatError::SettingsPanel)?;
+            return cx.requester
+                .delete_message(chat_id, message.id)
+                .await
+                .map_err(lib::errors::ChatError::SettingsPanel);
+        }
+
+        let mut settings = self.service.get_chat_settings(chat_id).await?;
+        let change = match &action {
+            PanelAction::Toggle(setting) => Some(settings_panel::toggle(setting, &settings)),
+            PanelAction::Set(setting, value) => Some((*setting, value.clone())),
+            _ => None,
+        };
+        let mut answer = String::new();
+        if let Some((setting, value)) = change {
+            let mut changed = settings.clone();
+            changed.values.set(setting.key, value);
+            answer = match self.service.change_chat_settings(&changed).await {
+                Ok(_) => {
+                    settings = changed;
+                    "Настройки чата успешно изменены"
+                }
+                Err(_) => "Невозможно изменить настройки чата",
+            }.to_string();
+        }
+        let (text, keyboard) = match action {
+            PanelAction::Menu(setting) => (
+                settings_panel::menu_text(setting, &settings),
+                settings_panel::menu_keyboard(setting, &settings),
+            ),
+            _ => (settings_panel::main_text(&settings), settings_panel::main_keyboard(&settings)),
+        };
+
+        let result = cx.requester
+            .edit_message_text(chat_id, message.id, text, Some(keyboard))
+            .await;
+        if let Err(err) = result {
+            // Pressing a button of an outdated panel can render the same text again.
+            if !format!("{:#}", err).contains("message is not modified") {
+                return Err(lib::errors::ChatError::SettingsPanel(err));
+            }
+        }
+        cx.requester
+            .answer_callback_query(&cx.update.id, answer, false)
+            .await
+            .map_err(lib::errors::ChatError::SettingsPanel)
+    }
+
+    async fn get_auto_delete(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let rules = self.service
+            .get_auto_delete_rules(cx.update.chat_id())
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)?;
+        let mut text = "<b>Автоудаление ответов бота:</b>".to_string();
+        for rule in rules.iter() {
+            text.push_str(&format!("\n{}", describe_auto_delete(rule)));
+        }
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)
+    }
+
+    async fn change_auto_delete(
+        &self,
+        cx: &lib::types::MessageContext,
+        category: ReplyCategory,
+        ttl_seconds: i32,
+        delete_trigger: bool,
+    ) -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let rule = model::AutoDeleteRule {
+            chat_id: cx.update.chat_id(),
+            category: category.name().to_string(),
+            ttl_seconds,
+            delete_trigger,
+        };
+        let msg_text = match self.service.set_auto_delete_rule(&rule).await {
+            Ok(_) => format!("Автоудаление ответов изменено\n{}", describe_auto_delete(&rule)),
+            Err(_) => "Невозможно изменить автоудаление ответов".to_string(),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings)
+    }
+
+    async fn get_command_policies(&self, cx: &lib::types::MessageContext)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let settings = self.service
+            .get_chat_settings(cx.update.chat_id())
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)?;
+        let msg_text = std::iter::once("<b>Доступ к командам:</b>".to_string())
+            .chain(Command::ALL.iter().map(|command| {
+                describe_command_policy(*command, &settings.command_policy(*command))
+            }))
+            .collect::<Vec<_>>()
+            .join("\n");
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::AdminCommandsControllerError::GetSettings)
+    }
+
+    async fn set_command_access(
+        &self,
+        cx: &lib::types::MessageContext,
+        command: &str,
+        access: &str,
+        min_rating: Option<&str>,
+    ) -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let command = match Command::from_str(command) {
+            Ok(command) => command,
+            Err(_) => {
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, unknown_command(command))
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings);
+            }
+        };
+        let access = match min_rating.map(sqlx::types::BigDecimal::from_str).transpose() {
+            Ok(min_rating) => model::CommandAccess::from_parts(access, min_rating),
+            Err(_) => None,
+        };
+        match access {
+            Some(access) => self.change_command_policy(cx, command, |policy| policy.access = access).await,
+            None => lib::tg_helpers::reply_to(
+                cx,
+                &*self.auto_delete,
+                ReplyCategory::Error,
+                format!(
+                    "Некорректный доступ. Доступные значения: {}, пример, <code>!command me rating 10</code>",
+                    model::CommandAccess::NAMES
+                        .iter()
+                        .map(|name| format!("<code>{}</code>", name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            )
+                .await
+                .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings),
+        }
+    }
+
+    async fn set_command_cooldown(&self, cx: &lib::types::MessageContext, command: &str, time: &str)
+        -> Result<(), lib::errors::AdminCommandsControllerError> {
+        let command = match Command::from_str(command) {
+            Ok(command) => command,
+            Err(_) => {
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, unknown_command(command))
+                    .await
+                    .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings);
+            }
+        };
+        let cooldown_seconds = match time {
+            "off" => Some(0),
+            time => match lib::enums::TimeUnits::from_str(time).map(|time| time.to_duration().num_seconds()) {
+                Ok(seconds) if seconds > 0 && seconds <= i32::MAX as i64 => Some(seconds as i32),
+                _ => None,
+            },
+        };
+        match cooldown_seconds {
+            Some(cooldown_seconds) => {
+                self.change_command_policy(cx, command, |policy| policy.cooldown_seconds = cooldown_seconds).await
+            }
+            None => lib::tg_helpers::reply_to(
+                cx,
+                &*self.auto_delete,
+                ReplyCategory::Error,
+                "Некорректный срок, пример, <code>1m</code>".to_string(),
+            )
+                .await
+                .map_err(lib::errors::AdminCommandsControllerError::ChangeSettings),
+        }
+    }
+
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        cooldown_seconds: i32,
+    ) -> Option<i64> {
+        self.service
+            .start_command_cooldown(chat_id, telegram_id, command, cooldown_seconds)
+            .await
+            .unwrap_or_else(|err| {
+                log::error!("{:?}", err);
+                None
+            })
+    }
+
+    async fn start_cooldown_notice(&self, chat_id: i64, telegram_id: i64, command: Command, seconds_left: i64) -> bool {
+        self.service
+            .start_cooldown_notice(chat_id, telegram_id, command, seconds_left)
+            .await
+            .unwrap_or_else(|err| {
+                log::error!("{:?}", err);
+                false
+            })
+    }
 }
 
-pub fn new_chat_controller(service: Box<dyn ChatService>) -> Box<dyn ChatController> {
-    Box::new(ChatControllerImpl { service })
+pub fn new_chat_controller(service: Arc<dyn ChatService>, auto_delete: Arc<dyn AutoDelete>)
+    -> Box<dyn ChatController> {
+    Box::new(ChatControllerImpl { service, auto_delete })
 }
@@ -1,14 +1,35 @@
-use crate::domains::chat::{controller, repositories, service};
-use sqlx::PgPool;
+use crate::core::persistence::Storage;
+use crate::domains::{chat::{auto_delete, controller, repositories, service}, job::JobService};
+use crate::lib;
 use std::sync::Arc;
 
 pub struct ChatDomain {
     pub controller: Box<dyn controller::ChatController>,
+    /// Shared with the domains that reply to messages.
+    pub auto_delete: Arc<dyn lib::auto_delete::AutoDelete>,
 }
 
-pub async fn new_chat_domain(pool: Arc<PgPool>) -> ChatDomain {
-    let repo = repositories::new_chat_db_repository(pool);
-    let service = service::new_chat_service(repo);
-    let controller = controller::new_chat_controller(service);
-    ChatDomain { controller }
+pub async fn new_chat_domain(
+    storage: &Storage,
+    jobs: Arc<dyn JobService>,
+    config: lib::config::SharedConfig,
+) -> ChatDomain {
+    let (db_repo, cache_repo) = match storage {
+        Storage::Postgres { pool, redis_client } => (
+            repositories::new_chat_db_repository(pool.clone()),
+            repositories::new_chat_cache_repository(redis_client.clone()),
+        ),
+        Storage::Sqlite { pool, cache } => (
+            repositories::new_sqlite_chat_db_repository(pool.clone()),
+            repositories::new_memory_chat_cache_repository(cache.clone()),
+        ),
+        Storage::Memory(store) => (
+            repositories::new_memory_chat_db_repository(store.clone()),
+            repositories::new_memory_chat_cache_repository(store.clone()),
+        ),
+    };
+    let service: Arc<dyn service::ChatService> = Arc::from(service::new_chat_service(db_repo, cache_repo, config));
+    let auto_delete = auto_delete::new_auto_delete(service.clone(), jobs);
+    let controller = controller::new_chat_controller(service, auto_delete.clone());
+    ChatDomain { controller, auto_delete }
 }
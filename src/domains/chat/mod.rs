@@ -1,6 +1,10 @@
+mod auto_delete;
 mod controller;
 mod domain_builder;
 mod model;
+pub mod registry;
 mod repositories;
 mod service;
-pub use {domain_builder::{new_chat_domain, ChatDomain}, model::ChatSettings};
+mod settings_panel;
+pub use {domain_builder::{new_chat_domain, ChatDomain}, model::{ChatSettings, CommandAccess}};
+pub use settings_panel::CALLBACK_PREFIX as SETTINGS_CALLBACK_PREFIX;
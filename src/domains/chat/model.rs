@@ -1,9 +1,152 @@
+use crate::{domains::chat::registry, lib::enums::Command};
+use sqlx::types::BigDecimal;
+use std::collections::HashMap;
+
 pub struct Chat {
     pub chat_id: i64,
     pub title: String,
 }
+#[derive(Clone)]
 pub struct ChatSettings {
     pub chat_id: i64,
-    pub is_rating_count: bool,
-    pub commands_for_admin_only: bool,
+    /// Settings of the registry, stored in the `chat_setting` table.
+    pub values: registry::SettingValues,
+    /// Policies the chat set explicitly, stored in the `chat_command` table.
+    pub commands: HashMap<Command, CommandPolicy>,
+}
+
+impl ChatSettings {
+    pub fn is_rating_count(&self) -> bool {
+        self.values.bool(&registry::RATING_COUNT)
+    }
+
+    /// Commands without an explicit policy are open to everyone without a cooldown.
+    pub fn command_policy(&self, command: Command) -> CommandPolicy {
+        self.commands.get(&command).cloned().unwrap_or_default()
+    }
+
+    pub fn is_global_reputation_start(&self) -> bool {
+        self.values.bool(&registry::GLOBAL_REPUTATION_START)
+    }
+
+    /// Seconds a member has to be in the chat before changing someone else's rating.
+    pub fn voter_min_age(&self) -> i32 {
+        self.values.duration(&registry::VOTER_MIN_AGE)
+    }
+
+    pub fn voter_min_messages(&self) -> i64 {
+        self.values.integer(&registry::VOTER_MIN_MESSAGES)
+    }
+
+    pub fn voter_min_rating(&self) -> BigDecimal {
+        self.values.decimal(&registry::VOTER_MIN_RATING)
+    }
+
+    /// What the moderation check does in the chat, `None` when it's switched off.
+    pub fn moderation_action(&self, check: &registry::ModerationCheck) -> Option<crate::lib::enums::ModerationAction> {
+        self.values.moderation_action(check)
+    }
+}
+
+/// A value of a registry setting a chat set explicitly, in the stored format.
+pub struct ChatSettingValue {
+    pub chat_id: i64,
+    pub name: String,
+    pub value: String,
+}
+
+/// Who may use a command. Administrators and owners pass every level but `Disabled`, moderators and helpers
+/// every level below `Admins`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandAccess {
+    Disabled,
+    Everyone,
+    /// Members with at least this rating in the chat.
+    MinRating(BigDecimal),
+    /// Members with any bot role.
+    Moderators,
+    Admins,
+}
+
+impl CommandAccess {
+    pub const NAMES: [&'static str; 5] = ["disabled", "everyone", "rating", "moderators", "admins"];
+
+    /// `rating` needs the minimal rating, the other levels take none.
+    pub fn from_parts(name: &str, min_rating: Option<BigDecimal>) -> Option<CommandAccess> {
+        match (name, min_rating) {
+            ("disabled", None) => Some(CommandAccess::Disabled),
+            ("everyone", None) => Some(CommandAccess::Everyone),
+            ("rating", Some(min_rating)) => Some(CommandAccess::MinRating(min_rating)),
+            ("moderators", None) => Some(CommandAccess::Moderators),
+            ("admins", None) => Some(CommandAccess::Admins),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandAccess::Disabled => "disabled",
+            CommandAccess::Everyone => "everyone",
+            CommandAccess::MinRating(_) => "rating",
+            CommandAccess::Moderators => "moderators",
+            CommandAccess::Admins => "admins",
+        }
+    }
+
+    pub fn min_rating(&self) -> Option<BigDecimal> {
+        match self {
+            CommandAccess::MinRating(min_rating) => Some(min_rating.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            CommandAccess::Disabled => "отключена".to_string(),
+            CommandAccess::Everyone => "все участники".to_string(),
+            CommandAccess::MinRating(min_rating) => format!("участники с рейтингом от {:.2}", min_rating),
+            CommandAccess::Moderators => "модераторы".to_string(),
+            CommandAccess::Admins => "администраторы".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandPolicy {
+    pub access: CommandAccess,
+    /// Per member, 0 for none. Administrators and owners have no cooldown.
+    pub cooldown_seconds: i32,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        CommandPolicy { access: CommandAccess::Everyone, cooldown_seconds: 0 }
+    }
+}
+
+/// A command policy in the stored format, `access` is a [`CommandAccess`] name.
+pub struct ChatCommandPolicy {
+    pub chat_id: i64,
+    pub command: String,
+    pub access: String,
+    pub min_rating: Option<BigDecimal>,
+    pub cooldown_seconds: i32,
+}
+
+/// A bot-level role of a chat member, `role` is a [`crate::lib::enums::ModeratorRole`] name.
+pub struct ChatRole {
+    pub chat_id: i64,
+    pub telegram_id: i64,
+    pub role: String,
+    pub granted_by: i64,
+}
+
+/// Auto-delete setting of one reply category in a chat, replacing the category's default.
+pub struct AutoDeleteRule {
+    pub chat_id: i64,
+    pub category: String,
+    /// 0 keeps the replies.
+    pub ttl_seconds: i32,
+    /// Also deletes the message the bot replied to.
+    pub delete_trigger: bool,
 }
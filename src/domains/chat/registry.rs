@@ -0,0 +1,461 @@
+use crate::lib;
+use itertools::Itertools;
+use sqlx::types::BigDecimal;
+use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+
+/// The type of a setting value, with the values it accepts. The numeric types validate the parsed value,
+/// the error is the hint shown to the user.
+#[derive(Clone, Copy, Debug)]
+pub enum SettingType {
+    Bool,
+    /// One of the options.
+    Choice(&'static [&'static str]),
+    Integer(fn(i64) -> Result<(), &'static str>),
+    Decimal(fn(&BigDecimal) -> Result<(), &'static str>),
+    /// Seconds, typed like `1d` or `30m`.
+    Duration(fn(i32) -> Result<(), &'static str>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Choice(&'static str),
+    Integer(i64),
+    Decimal(BigDecimal),
+    /// Seconds.
+    Duration(i32),
+}
+
+impl SettingValue {
+    /// The format values are kept in the `chat_setting` table, read back by [`Setting::parse`].
+    pub fn to_stored(&self) -> String {
+        match self {
+            SettingValue::Bool(value) => value.to_string(),
+            SettingValue::Choice(option) => option.to_string(),
+            SettingValue::Integer(value) => value.to_string(),
+            SettingValue::Decimal(value) => value.to_string(),
+            SettingValue::Duration(seconds) => seconds.to_string(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            SettingValue::Bool(value) => lib::helpers::bool_to_string_switch(*value).to_string(),
+            SettingValue::Choice(option) => option.to_string(),
+            SettingValue::Integer(value) => value.to_string(),
+            SettingValue::Decimal(value) => format!("{:.2}", value),
+            SettingValue::Duration(seconds) => lib::enums::TimeUnits::from_seconds(*seconds).to_string(),
+        }
+    }
+}
+
+/// A chat setting kept in the `chat_setting` key/value table. A new setting needs only its static here
+/// and an entry in [`SETTINGS`]: `!set`, `!get` and `!settings` pick it up.
+#[derive(Debug)]
+pub struct Setting {
+    pub key: &'static str,
+    pub setting_type: SettingType,
+    /// In the stored format.
+    pub default: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Values offered in the settings panel sub-menu, in the stored format. Settings without presets
+    /// are switched by a press on their button.
+    pub presets: &'static [&'static str],
+}
+
+/// Settings are told apart by their keys.
+impl PartialEq for Setting {
+    fn eq(&self, other: &Setting) -> bool {
+        self.key == other.key
+    }
+}
+
+pub static RATING_COUNT: Setting = Setting {
+    key: "rating_count",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Подсчёт рейтинга",
+    description: "Подсчёт рейтинга в чате. При отключении данные не стираются",
+    presets: &[],
+};
+
+pub static GLOBAL_REPUTATION_START: Setting = Setting {
+    key: "global_reputation_start",
+    setting_type: SettingType::Bool,
+    default: "false",
+    title: "Начальный рейтинг по репутации",
+    description: "Начальный рейтинг новых участников зависит от их глобальной репутации",
+    presets: &[],
+};
+
+pub static CAS_CHECK: Setting = Setting {
+    key: "cas_check",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Проверка по CAS",
+    description: "Проверка новых участников по <a href='https://cas.chat'>CAS</a>",
+    presets: &[],
+};
+
+pub static CAS_ACTION: Setting = Setting {
+    key: "cas_action",
+    setting_type: SettingType::Choice(&["ban", "report"]),
+    default: "ban",
+    title: "CAS",
+    description: "Действие с участником из CAS: <code>ban</code> – бан, \
+        <code>report</code> – уведомить администраторов",
+    presets: &[],
+};
+
+pub static LINK_CHECK: Setting = Setting {
+    key: "link_check",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Проверка ссылок",
+    description: "Проверка ссылок в сообщениях в соответствии с общими ограничениями",
+    presets: &[],
+};
+
+pub static LINK_ACTION: Setting = Setting {
+    key: "link_action",
+    setting_type: SettingType::Choice(&["ban", "delete", "report"]),
+    default: "ban",
+    title: "Ссылки",
+    description: "Действие с запрещённой ссылкой: <code>ban</code> – удалить сообщение и забанить автора, \
+        <code>delete</code> – только удалить сообщение, <code>report</code> – уведомить администраторов",
+    presets: &[],
+};
+
+pub static NAME_CHECK: Setting = Setting {
+    key: "name_check",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Проверка имён",
+    description: "Проверка имён авторов сообщений в соответствии с общими ограничениями",
+    presets: &[],
+};
+
+pub static NAME_ACTION: Setting = Setting {
+    key: "name_action",
+    setting_type: SettingType::Choice(&["ban", "report"]),
+    default: "ban",
+    title: "Имена",
+    description: "Действие с автором с запрещённым именем: <code>ban</code> – бан, \
+        <code>report</code> – уведомить администраторов",
+    presets: &[],
+};
+
+pub static INSULT_CHECK: Setting = Setting {
+    key: "insult_check",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Замечания за оскорбления",
+    description: "Замечание в ответ на оскорбления в сообщении",
+    presets: &[],
+};
+
+pub static INSULT_ACTION: Setting = Setting {
+    key: "insult_action",
+    setting_type: SettingType::Choice(&["warn", "delete"]),
+    default: "warn",
+    title: "Оскорбления",
+    description: "Действие с оскорблением: <code>warn</code> – замечание, \
+        <code>delete</code> – удалить сообщение и сделать замечание",
+    presets: &[],
+};
+
+pub static POLITICS_CHECK: Setting = Setting {
+    key: "politics_check",
+    setting_type: SettingType::Bool,
+    default: "true",
+    title: "Замечания за политику",
+    description: "Замечание в ответ на политические темы в сообщении",
+    presets: &[],
+};
+
+pub static POLITICS_ACTION: Setting = Setting {
+    key: "politics_action",
+    setting_type: SettingType::Choice(&["warn", "delete"]),
+    default: "warn",
+    title: "Политика",
+    description: "Действие с политической темой: <code>warn</code> – замечание, \
+        <code>delete</code> – удалить сообщение и сделать замечание",
+    presets: &[],
+};
+
+pub static VOTER_MIN_AGE: Setting = Setting {
+    key: "voter_min_age",
+    setting_type: SettingType::Duration(|seconds| match seconds >= 0 {
+        true => Ok(()),
+        false => Err("Срок не может быть отрицательным"),
+    }),
+    default: "0",
+    title: "Минимальный срок участия",
+    description: "Минимальный срок участия в чате для изменения чужого рейтинга. <code>0s</code> отключает ограничение",
+    presets: &["0", "3600", "86400", "604800", "2592000"],
+};
+
+pub static VOTER_MIN_MESSAGES: Setting = Setting {
+    key: "voter_min_messages",
+    setting_type: SettingType::Integer(|amount| match amount >= 0 {
+        true => Ok(()),
+        false => Err("Количество сообщений не может быть отрицательным"),
+    }),
+    default: "0",
+    title: "Минимум сообщений",
+    description: "Минимальное количество сообщений в чате для изменения чужого рейтинга",
+    presets: &["0", "10", "50", "100", "500"],
+};
+
+pub static VOTER_MIN_RATING: Setting = Setting {
+    key: "voter_min_rating",
+    setting_type: SettingType::Decimal(|_| Ok(())),
+    default: "0",
+    title: "Минимальный рейтинг",
+    description: "Минимальный собственный рейтинг для изменения чужого рейтинга",
+    presets: &["-10", "0", "1", "5", "10"],
+};
+
+pub static GLOBAL_REPUTATION_WEIGHT: Setting = Setting {
+    key: "global_reputation_weight",
+    setting_type: SettingType::Decimal(|weight| match *weight >= BigDecimal::from(0) {
+        true => Ok(()),
+        false => Err("Вес чата не может быть отрицательным"),
+    }),
+    default: "1",
+    title: "Вес чата",
+    description: "Вес рейтинга данного чата в глобальной репутации. <code>0</code> исключает чат из глобальной репутации",
+    presets: &["0", "0.5", "1", "2"],
+};
+
+pub static SETTINGS: [&Setting; 16] = [
+    &RATING_COUNT,
+    &GLOBAL_REPUTATION_START,
+    &CAS_CHECK,
+    &CAS_ACTION,
+    &LINK_CHECK,
+    &LINK_ACTION,
+    &NAME_CHECK,
+    &NAME_ACTION,
+    &INSULT_CHECK,
+    &INSULT_ACTION,
+    &POLITICS_CHECK,
+    &POLITICS_ACTION,
+    &VOTER_MIN_AGE,
+    &VOTER_MIN_MESSAGES,
+    &VOTER_MIN_RATING,
+    &GLOBAL_REPUTATION_WEIGHT,
+];
+
+/// A moderation check that can be switched off, with what it does to the messages or members it catches.
+pub struct ModerationCheck {
+    pub enabled: &'static Setting,
+    /// A choice of [`lib::enums::ModerationAction`] names.
+    pub action: &'static Setting,
+}
+
+pub static CAS: ModerationCheck = ModerationCheck { enabled: &CAS_CHECK, action: &CAS_ACTION };
+pub static LINKS: ModerationCheck = ModerationCheck { enabled: &LINK_CHECK, action: &LINK_ACTION };
+pub static NAMES: ModerationCheck = ModerationCheck { enabled: &NAME_CHECK, action: &NAME_ACTION };
+pub static INSULTS: ModerationCheck = ModerationCheck { enabled: &INSULT_CHECK, action: &INSULT_ACTION };
+pub static POLITICS: ModerationCheck = ModerationCheck { enabled: &POLITICS_CHECK, action: &POLITICS_ACTION };
+
+pub fn find(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().copied().find(|setting| setting.key == key)
+}
+
+impl Setting {
+    /// Parses a value typed by a user or read from the table, the error is the hint shown to the user.
+    pub fn parse(&self, input: &str) -> Result<SettingValue, String> {
+        match self.setting_type {
+            SettingType::Bool => match input.to_lowercase().as_str() {
+                "true" | "on" | "1" | "вкл" => Ok(SettingValue::Bool(true)),
+                "false" | "off" | "0" | "выкл" => Ok(SettingValue::Bool(false)),
+                _ => Err("Ожидается <code>on</code> или <code>off</code>".to_string()),
+            },
+            SettingType::Choice(options) => options
+                .iter()
+                .find(|option| **option == input.to_lowercase())
+                .map(|option| SettingValue::Choice(option))
+                .ok_or_else(|| format!(
+                    "Ожидается одно из значений: {}",
+                    options.iter().map(|option| format!("<code>{}</code>", option)).join(" | "),
+                )),
+            SettingType::Integer(validate) => {
+                let value = input.parse::<i64>().map_err(|_| "Ожидается целое число".to_string())?;
+                validate(value).map(|_| SettingValue::Integer(value)).map_err(str::to_string)
+            }
+            SettingType::Decimal(validate) => {
+                let value = BigDecimal::from_str(input).map_err(|_| "Ожидается число, пример, <code>0.5</code>".to_string())?;
+                validate(&value).map(|_| SettingValue::Decimal(value)).map_err(str::to_string)
+            }
+            SettingType::Duration(validate) => {
+                // Stored as seconds, typed with units
+                let seconds = match input.parse::<i32>() {
+                    Ok(seconds) => Some(seconds),
+                    Err(_) => lib::enums::TimeUnits::from_str(input)
+                        .ok()
+                        .and_then(|time| i32::try_from(time.to_duration().num_seconds()).ok()),
+                };
+                let seconds = seconds.ok_or_else(|| "Ожидается срок, пример, <code>1d</code>".to_string())?;
+                validate(seconds).map(|_| SettingValue::Duration(seconds)).map_err(str::to_string)
+            }
+        }
+    }
+
+    /// The value after `value` when going through them in the settings panel: the opposite one for on/off,
+    /// the next option for a choice, the same one for the numeric types.
+    pub fn next_value(&self, value: &SettingValue) -> SettingValue {
+        match (self.setting_type, value) {
+            (SettingType::Choice(options), SettingValue::Choice(option)) => {
+                let position = options.iter().position(|other| other == option).unwrap_or_default();
+                SettingValue::Choice(options[(position + 1) % options.len()])
+            }
+            (_, SettingValue::Bool(value)) => SettingValue::Bool(!value),
+            (_, value) => value.clone(),
+        }
+    }
+
+    pub fn default_value(&self) -> SettingValue {
+        self.parse(self.default).unwrap_or_else(|err| panic!("Invalid default of {}: {}", self.key, err))
+    }
+}
+
+/// Values a chat set explicitly, the other settings have their defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SettingValues(HashMap<&'static str, SettingValue>);
+
+impl SettingValues {
+    /// Skips the values of unknown (removed) settings and values that no longer parse.
+    pub fn from_stored(chat_id: i64, rows: Vec<(String, String)>) -> SettingValues {
+        let mut values = HashMap::new();
+        for (key, value) in rows {
+            match find(&key).map(|setting| (setting, setting.parse(&value))) {
+                Some((setting, Ok(value))) => {
+                    values.insert(setting.key, value);
+                }
+                Some((_, Err(err))) =>
+                    log::warn!("Invalid value of chat setting {} (chat_id: {}): {:?}, {}", key, chat_id, value, err),
+                None => log::debug!("Unknown chat setting {} (chat_id: {}) skipped", key, chat_id),
+            }
+        }
+        SettingValues(values)
+    }
+
+    pub fn get(&self, setting: &Setting) -> SettingValue {
+        self.0.get(setting.key).cloned().unwrap_or_else(|| setting.default_value())
+    }
+
+    pub fn bool(&self, setting: &Setting) -> bool {
+        matches!(self.get(setting), SettingValue::Bool(true))
+    }
+
+    pub fn integer(&self, setting: &Setting) -> i64 {
+        match self.get(setting) {
+            SettingValue::Integer(value) => value,
+            _ => 0,
+        }
+    }
+
+    pub fn decimal(&self, setting: &Setting) -> BigDecimal {
+        match self.get(setting) {
+            SettingValue::Decimal(value) => value,
+            _ => BigDecimal::from(0),
+        }
+    }
+
+    /// Seconds.
+    pub fn duration(&self, setting: &Setting) -> i32 {
+        match self.get(setting) {
+            SettingValue::Duration(seconds) => seconds,
+            _ => 0,
+        }
+    }
+
+    /// What the check does, `None` when it's switched off.
+    pub fn moderation_action(&self, check: &ModerationCheck) -> Option<lib::enums::ModerationAction> {
+        if !self.bool(check.enabled) {
+            return None;
+        }
+        match self.get(check.action) {
+            SettingValue::Choice(option) => option.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, key: &'static str, value: SettingValue) {
+        self.0.insert(key, value);
+    }
+
+    /// The explicitly set values in the stored format, ordered by key.
+    pub fn to_stored(&self) -> Vec<(&'static str, String)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (*key, value.to_stored()))
+            .sorted()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_parse_and_stored_values_round_trip() {
+        for setting in SETTINGS.iter() {
+            let value = setting.default_value();
+            assert_eq!(setting.parse(&value.to_stored()), Ok(value));
+        }
+        assert_eq!(RATING_COUNT.parse("OFF"), Ok(SettingValue::Bool(false)));
+        assert!(RATING_COUNT.parse("maybe").is_err());
+        assert!(LINK_ACTION.parse("warn").is_err());
+    }
+
+    #[test]
+    fn numeric_values_are_validated() {
+        assert_eq!(VOTER_MIN_AGE.parse("1d"), Ok(SettingValue::Duration(86400)));
+        assert_eq!(VOTER_MIN_AGE.parse("3600"), Ok(SettingValue::Duration(3600)));
+        assert!(VOTER_MIN_AGE.parse("-5").is_err());
+        assert!(VOTER_MIN_AGE.parse("soon").is_err());
+        assert_eq!(VOTER_MIN_MESSAGES.parse("50"), Ok(SettingValue::Integer(50)));
+        assert!(VOTER_MIN_MESSAGES.parse("-1").is_err());
+        assert!(VOTER_MIN_MESSAGES.parse("1.5").is_err());
+        assert_eq!(VOTER_MIN_RATING.parse("-2.5"), Ok(SettingValue::Decimal(BigDecimal::from_str("-2.5").unwrap())));
+        assert!(GLOBAL_REPUTATION_WEIGHT.parse("-1").is_err());
+        for setting in SETTINGS.iter() {
+            for preset in setting.presets {
+                assert!(setting.parse(preset).is_ok(), "{} of {}", preset, setting.key);
+            }
+        }
+    }
+
+    #[test]
+    fn every_action_option_is_a_moderation_action_and_options_cycle() {
+        for check in [&CAS, &LINKS, &NAMES, &INSULTS, &POLITICS] {
+            if let SettingType::Choice(options) = check.action.setting_type {
+                for option in options.iter() {
+                    assert!(option.parse::<lib::enums::ModerationAction>().is_ok(), "{}", option);
+                }
+            }
+        }
+        let value = LINK_ACTION.default_value();
+        let after_three = (0..3).fold(value.clone(), |value, _| LINK_ACTION.next_value(&value));
+        assert_eq!(LINK_ACTION.next_value(&value), SettingValue::Choice("delete"));
+        assert_eq!(after_three, value);
+    }
+
+    #[test]
+    fn unknown_and_invalid_stored_values_fall_back_to_defaults() {
+        let values = SettingValues::from_stored(1, vec![
+            ("rating_count".to_string(), "false".to_string()),
+            ("global_reputation_start".to_string(), "maybe".to_string()),
+            ("removed_setting".to_string(), "true".to_string()),
+        ]);
+
+        assert!(!values.bool(&RATING_COUNT));
+        assert!(!values.bool(&GLOBAL_REPUTATION_START));
+        assert_eq!(values.to_stored(), vec![("rating_count", "false".to_string())]);
+    }
+}
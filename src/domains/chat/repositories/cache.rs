@@ -0,0 +1,237 @@
+use crate::core::persistence::MemoryStore;
+use redis::{Client, AsyncCommands};
+use crate::lib::{self, errors::CacheError};
+use async_trait::async_trait;
+use std::sync::Arc;
+use anyhow::Context;
+
+#[async_trait]
+pub trait ChatCacheRepository: Send + Sync {
+    /// Telegram ids of the chat administrators, `None` when they aren't cached or the cache expired.
+    async fn get_admins(&self, chat_id: i64) -> Result<Option<Vec<i64>>, CacheError>;
+    async fn save_admins(&self, chat_id: i64, admins: &[i64], seconds: u64) -> Result<(), CacheError>;
+    async fn delete_admins(&self, chat_id: i64) -> Result<(), CacheError>;
+    /// `false` when the member's cooldown of the command is already running.
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError>;
+    async fn get_command_cooldown(&self, chat_id: i64, telegram_id: i64, command: &str) -> Result<i64, CacheError>;
+    /// `false` when the member was already told about the running cooldown of the command.
+    async fn start_cooldown_notice(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError>;
+}
+
+fn admins_key(chat_id: i64) -> String {
+    format!("chat-admins-{}", chat_id)
+}
+
+fn command_cooldown_key(chat_id: i64, telegram_id: i64, command: &str) -> String {
+    format!("command-cooldown-{}-{}-{}", chat_id, telegram_id, command)
+}
+
+fn cooldown_notice_key(chat_id: i64, telegram_id: i64, command: &str) -> String {
+    format!("command-cooldown-notice-{}-{}-{}", chat_id, telegram_id, command)
+}
+
+struct RedisChatCacheRepositoryImpl {
+    client: Arc<Client>,
+}
+
+#[async_trait]
+impl ChatCacheRepository for RedisChatCacheRepositoryImpl {
+    async fn get_admins(&self, chat_id: i64) -> Result<Option<Vec<i64>>, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["get"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        let admins: Option<String> = conn
+            .get(admins_key(chat_id))
+            .await
+            .context("Unable get document using Redis")
+            .map_err(CacheError::Execute)?;
+        admins
+            .map(|admins| serde_json::from_str(&admins))
+            .transpose()
+            .context("Unable parse cached chat administrators")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn save_admins(&self, chat_id: i64, admins: &[i64], seconds: u64) -> Result<(), CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["set_ex"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        conn
+            .set_ex(
+                admins_key(chat_id),
+                serde_json::to_string(admins).context("Unable serialize chat administrators")?,
+                seconds as usize,
+            )
+            .await
+            .context("Unable save document using Redis")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn delete_admins(&self, chat_id: i64) -> Result<(), CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["del"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        conn
+            .del(admins_key(chat_id))
+            .await
+            .context("Unable delete document using Redis")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["set_nx_ex"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        redis::cmd("SET")
+            .arg(command_cooldown_key(chat_id, telegram_id, command))
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async::<_, Option<String>>(&mut conn)
+            .await
+            .map(|reply| reply.is_some())
+            .context("Unable save document using Redis")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn get_command_cooldown(&self, chat_id: i64, telegram_id: i64, command: &str) -> Result<i64, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["ttl"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        conn
+            .ttl(command_cooldown_key(chat_id, telegram_id, command))
+            .await
+            .context("Unable get document TTL using Redis")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn start_cooldown_notice(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["set_nx_ex"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        redis::cmd("SET")
+            .arg(cooldown_notice_key(chat_id, telegram_id, command))
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async::<_, Option<String>>(&mut conn)
+            .await
+            .map(|reply| reply.is_some())
+            .context("Unable save document using Redis")
+            .map_err(CacheError::Execute)
+    }
+}
+
+pub fn new_chat_cache_repository(client: Arc<Client>) -> Box<dyn ChatCacheRepository> {
+    Box::new(RedisChatCacheRepositoryImpl { client })
+}
+
+struct MemoryChatCacheRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+#[async_trait]
+impl ChatCacheRepository for MemoryChatCacheRepositoryImpl {
+    async fn get_admins(&self, chat_id: i64) -> Result<Option<Vec<i64>>, CacheError> {
+        self.store
+            .get(&admins_key(chat_id))
+            .map(|admins| serde_json::from_str(&admins))
+            .transpose()
+            .context("Unable parse cached chat administrators")
+            .map_err(CacheError::Execute)
+    }
+
+    async fn save_admins(&self, chat_id: i64, admins: &[i64], seconds: u64) -> Result<(), CacheError> {
+        self.store.set_ex(
+            admins_key(chat_id),
+            serde_json::to_string(admins).context("Unable serialize chat administrators")?,
+            std::time::Duration::from_secs(seconds),
+        );
+        Ok(())
+    }
+
+    async fn delete_admins(&self, chat_id: i64) -> Result<(), CacheError> {
+        self.store.del(&admins_key(chat_id));
+        Ok(())
+    }
+
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError> {
+        Ok(self.store.set_nx_ex(
+            command_cooldown_key(chat_id, telegram_id, command),
+            "1".to_string(),
+            std::time::Duration::from_secs(seconds),
+        ))
+    }
+
+    async fn get_command_cooldown(&self, chat_id: i64, telegram_id: i64, command: &str) -> Result<i64, CacheError> {
+        Ok(self.store.ttl(&command_cooldown_key(chat_id, telegram_id, command)))
+    }
+
+    async fn start_cooldown_notice(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: &str,
+        seconds: u64,
+    ) -> Result<bool, CacheError> {
+        Ok(self.store.set_nx_ex(
+            cooldown_notice_key(chat_id, telegram_id, command),
+            "1".to_string(),
+            std::time::Duration::from_secs(seconds),
+        ))
+    }
+}
+
+pub fn new_memory_chat_cache_repository(store: Arc<MemoryStore>) -> Box<dyn ChatCacheRepository> {
+    Box::new(MemoryChatCacheRepositoryImpl { store })
+}
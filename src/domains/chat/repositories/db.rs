@@ -1,5 +1,8 @@
+use crate::core::persistence::{
+    ChatAutoDeleteRow, ChatCommandRow, ChatRoleRow, ChatRow, ChatSettingRow, ChatSettingsRow, MemoryStore,
+};
 use crate::domains::chat::model;
-use crate::lib::errors::DBError;
+use crate::lib::{self, errors::DBError};
 use async_trait::async_trait;
 use anyhow::Context;
 use std::sync::Arc;
@@ -9,8 +12,20 @@ pub trait ChatDBRepository: Send + Sync {
     async fn create(&self, body: &model::Chat) -> Result<bool, DBError>;
     async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, DBError>;
     async fn create_chat_settings(&self, body: &model::ChatSettings) -> Result<bool, DBError>;
-    async fn change_chat_settings(&self, body: &model::ChatSettings) -> Result<(), DBError>;
+    /// Registry settings the chat set explicitly.
+    async fn get_setting_values(&self, chat_id: i64) -> Result<Vec<model::ChatSettingValue>, DBError>;
+    async fn set_setting_value(&self, body: &model::ChatSettingValue) -> Result<(), DBError>;
+    /// Command policies the chat set explicitly.
+    async fn get_command_policies(&self, chat_id: i64) -> Result<Vec<model::ChatCommandPolicy>, DBError>;
+    async fn set_command_policy(&self, body: &model::ChatCommandPolicy) -> Result<(), DBError>;
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<model::ChatRole>, DBError>;
+    async fn set_role(&self, body: &model::ChatRole) -> Result<(), DBError>;
+    /// `false` when the member had no role.
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, DBError>;
     async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), DBError>;
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), DBError>;
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, DBError>;
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), DBError>;
 }
 
 struct PgChatDBRepositoryImpl {
@@ -20,6 +35,7 @@ struct PgChatDBRepositoryImpl {
 #[async_trait]
 impl ChatDBRepository for PgChatDBRepositoryImpl {
     async fn create(&self, body: &model::Chat) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.create"]).start_timer();
         sqlx::query_file!(
             "src/domains/chat/repositories/queries/chat/create.sql",
             body.chat_id,
@@ -34,23 +50,28 @@ impl ChatDBRepository for PgChatDBRepositoryImpl {
     }
 
     async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, DBError> {
-        sqlx::query_file_as!(
-            model::ChatSettings,
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.get_chat_settings"]).start_timer();
+        sqlx::query_file!(
             "src/domains/chat/repositories/queries/chat_settings/fetch.sql",
             chat_id
         )
             .fetch_one(&*self.pool)
             .await
+            .map(|row| model::ChatSettings {
+                chat_id: row.chat_id,
+                values: Default::default(),
+                commands: Default::default(),
+            })
             .map_err(anyhow::Error::new)
             .context("Failed to fetch chat settings in Postgres")
             .map_err(DBError::Execute)
     }
 
     async fn create_chat_settings(&self, body: &model::ChatSettings) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.create_chat_settings"]).start_timer();
         sqlx::query_file!(
             "src/domains/chat/repositories/queries/chat_settings/create.sql",
-            body.chat_id,
-            body.is_rating_count
+            body.chat_id
         )
             .execute(&*self.pool)
             .await
@@ -60,22 +81,117 @@ impl ChatDBRepository for PgChatDBRepositoryImpl {
             .map_err(DBError::Execute)
     }
 
-    async fn change_chat_settings(&self, body: &model::ChatSettings) -> Result<(), DBError> {
+    async fn get_setting_values(&self, chat_id: i64) -> Result<Vec<model::ChatSettingValue>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.get_setting_values"]).start_timer();
+        sqlx::query_file_as!(
+            model::ChatSettingValue,
+            "src/domains/chat/repositories/queries/chat_setting/fetch.sql",
+            chat_id
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat setting values in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_setting_value(&self, body: &model::ChatSettingValue) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.set_setting_value"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/chat/repositories/queries/chat_setting/upsert.sql",
+            body.chat_id,
+            body.name,
+            body.value,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat setting value in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_command_policies(&self, chat_id: i64) -> Result<Vec<model::ChatCommandPolicy>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.get_command_policies"]).start_timer();
+        sqlx::query_file_as!(
+            model::ChatCommandPolicy,
+            "src/domains/chat/repositories/queries/chat_command/fetch.sql",
+            chat_id
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat command policies in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_command_policy(&self, body: &model::ChatCommandPolicy) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.set_command_policy"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/chat/repositories/queries/chat_command/upsert.sql",
+            body.chat_id,
+            body.command,
+            body.access,
+            body.min_rating,
+            body.cooldown_seconds,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat command policy in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<model::ChatRole>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.get_role"]).start_timer();
+        sqlx::query_file_as!(
+            model::ChatRole,
+            "src/domains/chat/repositories/queries/chat_role/fetch.sql",
+            chat_id,
+            telegram_id
+        )
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat role in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_role(&self, body: &model::ChatRole) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.set_role"]).start_timer();
         sqlx::query_file!(
-            "src/domains/chat/repositories/queries/chat_settings/change.sql",
+            "src/domains/chat/repositories/queries/chat_role/upsert.sql",
             body.chat_id,
-            body.is_rating_count,
-            body.commands_for_admin_only,
+            body.telegram_id,
+            body.role,
+            body.granted_by,
         )
             .execute(&*self.pool)
             .await
             .map(|_| ())
             .map_err(anyhow::Error::new)
-            .context("Failed to change chat settings in Postgres")
+            .context("Failed to save chat role in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.delete_role"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/chat/repositories/queries/chat_role/delete.sql",
+            chat_id,
+            telegram_id
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to delete chat role in Postgres")
             .map_err(DBError::Execute)
     }
 
     async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.migrate_chat"]).start_timer();
         sqlx::query_file!(
             "src/domains/chat/repositories/queries/chat/migrate_chat_id.sql",
             from,
@@ -88,8 +204,502 @@ impl ChatDBRepository for PgChatDBRepositoryImpl {
             .context("Failed to migrate chat ids in Postgres")
             .map_err(DBError::Execute)
     }
+
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.change_active"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/chat/repositories/queries/chat/change_active.sql",
+            chat_id,
+            is_active
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat activity in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.get_auto_delete_rules"]).start_timer();
+        sqlx::query_file_as!(
+            model::AutoDeleteRule,
+            "src/domains/chat/repositories/queries/chat_auto_delete/fetch.sql",
+            chat_id
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat auto-delete rules in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["chat.set_auto_delete_rule"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/chat/repositories/queries/chat_auto_delete/upsert.sql",
+            body.chat_id,
+            body.category,
+            body.ttl_seconds,
+            body.delete_trigger,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat auto-delete rule in Postgres")
+            .map_err(DBError::Execute)
+    }
 }
 
 pub fn new_chat_db_repository(pool: Arc<sqlx::PgPool>) -> Box<dyn ChatDBRepository> {
     Box::new(PgChatDBRepositoryImpl { pool })
 }
+
+struct MemoryChatDBRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+#[async_trait]
+impl ChatDBRepository for MemoryChatDBRepositoryImpl {
+    async fn create(&self, body: &model::Chat) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        match tables.chats.iter_mut().find(|chat| chat.chat_id == body.chat_id) {
+            Some(chat) if chat.title == body.title => Ok(false),
+            Some(chat) => {
+                chat.title = body.title.clone();
+                Ok(true)
+            }
+            None => {
+                tables.chats.push(ChatRow { chat_id: body.chat_id, title: body.title.clone(), is_active: true });
+                Ok(true)
+            }
+        }
+    }
+
+    async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, DBError> {
+        self.store
+            .tables()
+            .chat_settings
+            .iter()
+            .find(|settings| settings.chat_id == chat_id)
+            .map(|settings| model::ChatSettings {
+                chat_id: settings.chat_id,
+                values: Default::default(),
+                commands: Default::default(),
+            })
+            .context(format!("Chat settings (chat_id: {}) not found in memory", chat_id))
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_chat_settings(&self, body: &model::ChatSettings) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        if tables.chat_settings.iter().any(|settings| settings.chat_id == body.chat_id) {
+            return Ok(false);
+        }
+        tables.chat_settings.push(ChatSettingsRow { chat_id: body.chat_id });
+        Ok(true)
+    }
+
+    async fn get_setting_values(&self, chat_id: i64) -> Result<Vec<model::ChatSettingValue>, DBError> {
+        Ok(self.store
+            .tables()
+            .chat_setting
+            .iter()
+            .filter(|setting| setting.chat_id == chat_id)
+            .map(|setting| model::ChatSettingValue {
+                chat_id: setting.chat_id,
+                name: setting.name.clone(),
+                value: setting.value.clone(),
+            })
+            .collect())
+    }
+
+    async fn set_setting_value(&self, body: &model::ChatSettingValue) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        let existing = tables.chat_setting
+            .iter_mut()
+            .find(|setting| setting.chat_id == body.chat_id && setting.name == body.name);
+        match existing {
+            Some(setting) => setting.value = body.value.clone(),
+            None => tables.chat_setting.push(ChatSettingRow {
+                chat_id: body.chat_id,
+                name: body.name.clone(),
+                value: body.value.clone(),
+            }),
+        }
+        Ok(())
+    }
+
+    async fn get_command_policies(&self, chat_id: i64) -> Result<Vec<model::ChatCommandPolicy>, DBError> {
+        Ok(self.store
+            .tables()
+            .chat_commands
+            .iter()
+            .filter(|policy| policy.chat_id == chat_id)
+            .map(|policy| model::ChatCommandPolicy {
+                chat_id: policy.chat_id,
+                command: policy.command.clone(),
+                access: policy.access.clone(),
+                min_rating: policy.min_rating.clone(),
+                cooldown_seconds: policy.cooldown_seconds,
+            })
+            .collect())
+    }
+
+    async fn set_command_policy(&self, body: &model::ChatCommandPolicy) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        let existing = tables.chat_commands
+            .iter_mut()
+            .find(|policy| policy.chat_id == body.chat_id && policy.command == body.command);
+        match existing {
+            Some(policy) => {
+                policy.access = body.access.clone();
+                policy.min_rating = body.min_rating.clone();
+                policy.cooldown_seconds = body.cooldown_seconds;
+            }
+            None => tables.chat_commands.push(ChatCommandRow {
+                chat_id: body.chat_id,
+                command: body.command.clone(),
+                access: body.access.clone(),
+                min_rating: body.min_rating.clone(),
+                cooldown_seconds: body.cooldown_seconds,
+            }),
+        }
+        Ok(())
+    }
+
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<model::ChatRole>, DBError> {
+        Ok(self.store
+            .tables()
+            .chat_roles
+            .iter()
+            .find(|role| role.chat_id == chat_id && role.telegram_id == telegram_id)
+            .map(|role| model::ChatRole {
+                chat_id: role.chat_id,
+                telegram_id: role.telegram_id,
+                role: role.role.clone(),
+                granted_by: role.granted_by,
+            }))
+    }
+
+    async fn set_role(&self, body: &model::ChatRole) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        let existing = tables.chat_roles
+            .iter_mut()
+            .find(|role| role.chat_id == body.chat_id && role.telegram_id == body.telegram_id);
+        match existing {
+            Some(role) => {
+                role.role = body.role.clone();
+                role.granted_by = body.granted_by;
+            }
+            None => tables.chat_roles.push(ChatRoleRow {
+                chat_id: body.chat_id,
+                telegram_id: body.telegram_id,
+                role: body.role.clone(),
+                granted_by: body.granted_by,
+            }),
+        }
+        Ok(())
+    }
+
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        let count = tables.chat_roles.len();
+        tables.chat_roles.retain(|role| role.chat_id != chat_id || role.telegram_id != telegram_id);
+        Ok(tables.chat_roles.len() < count)
+    }
+
+    async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        tables.chats.iter_mut().filter(|chat| chat.chat_id == from).for_each(|chat| chat.chat_id = to);
+        tables.chat_settings
+            .iter_mut()
+            .filter(|settings| settings.chat_id == from)
+            .for_each(|settings| settings.chat_id = to);
+        tables.chat_users
+            .iter_mut()
+            .filter(|chat_user| chat_user.chat_id == from)
+            .for_each(|chat_user| chat_user.chat_id = to);
+        tables.rating_categories
+            .iter_mut()
+            .filter(|category| category.chat_id == from)
+            .for_each(|category| category.chat_id = to);
+        tables.chat_auto_delete
+            .iter_mut()
+            .filter(|rule| rule.chat_id == from)
+            .for_each(|rule| rule.chat_id = to);
+        tables.chat_setting
+            .iter_mut()
+            .filter(|setting| setting.chat_id == from)
+            .for_each(|setting| setting.chat_id = to);
+        tables.chat_roles
+            .iter_mut()
+            .filter(|role| role.chat_id == from)
+            .for_each(|role| role.chat_id = to);
+        tables.chat_commands
+            .iter_mut()
+            .filter(|policy| policy.chat_id == from)
+            .for_each(|policy| policy.chat_id = to);
+        Ok(())
+    }
+
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), DBError> {
+        if let Some(chat) = self.store.tables().chats.iter_mut().find(|chat| chat.chat_id == chat_id) {
+            chat.is_active = is_active;
+        }
+        Ok(())
+    }
+
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, DBError> {
+        Ok(self.store
+            .tables()
+            .chat_auto_delete
+            .iter()
+            .filter(|rule| rule.chat_id == chat_id)
+            .map(|rule| model::AutoDeleteRule {
+                chat_id: rule.chat_id,
+                category: rule.category.clone(),
+                ttl_seconds: rule.ttl_seconds,
+                delete_trigger: rule.delete_trigger,
+            })
+            .collect())
+    }
+
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        let existing = tables.chat_auto_delete
+            .iter_mut()
+            .find(|rule| rule.chat_id == body.chat_id && rule.category == body.category);
+        match existing {
+            Some(rule) => {
+                rule.ttl_seconds = body.ttl_seconds;
+                rule.delete_trigger = body.delete_trigger;
+            }
+            None => tables.chat_auto_delete.push(ChatAutoDeleteRow {
+                chat_id: body.chat_id,
+                category: body.category.clone(),
+                ttl_seconds: body.ttl_seconds,
+                delete_trigger: body.delete_trigger,
+            }),
+        }
+        Ok(())
+    }
+}
+
+pub fn new_memory_chat_db_repository(store: Arc<MemoryStore>) -> Box<dyn ChatDBRepository> {
+    Box::new(MemoryChatDBRepositoryImpl { store })
+}
+
+struct SqliteChatDBRepositoryImpl {
+    pool: Arc<sqlx::SqlitePool>,
+}
+
+#[async_trait]
+impl ChatDBRepository for SqliteChatDBRepositoryImpl {
+    async fn create(&self, body: &model::Chat) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat/create.sql"))
+            .bind(body.chat_id)
+            .bind(&body.title)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to create chat in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, DBError> {
+        sqlx::query_as::<_, (i64,)>(include_str!("queries/sqlite/chat_settings/fetch.sql"))
+            .bind(chat_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map(|row| model::ChatSettings {
+                chat_id: row.0,
+                values: Default::default(),
+                commands: Default::default(),
+            })
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat settings in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_chat_settings(&self, body: &model::ChatSettings) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_settings/create.sql"))
+            .bind(body.chat_id)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to create chat settings in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_setting_values(&self, chat_id: i64) -> Result<Vec<model::ChatSettingValue>, DBError> {
+        sqlx::query_as::<_, (i64, String, String)>(include_str!("queries/sqlite/chat_setting/fetch.sql"))
+            .bind(chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|(chat_id, name, value)| model::ChatSettingValue { chat_id, name, value })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat setting values in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_setting_value(&self, body: &model::ChatSettingValue) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_setting/upsert.sql"))
+            .bind(body.chat_id)
+            .bind(&body.name)
+            .bind(&body.value)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat setting value in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_command_policies(&self, chat_id: i64) -> Result<Vec<model::ChatCommandPolicy>, DBError> {
+        sqlx::query_as::<_, (i64, String, String, Option<f64>, i32)>(
+            include_str!("queries/sqlite/chat_command/fetch.sql"),
+        )
+            .bind(chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|(chat_id, command, access, min_rating, cooldown_seconds)| model::ChatCommandPolicy {
+                    chat_id,
+                    command,
+                    access,
+                    min_rating: min_rating.map(lib::helpers::decimal_from_f64),
+                    cooldown_seconds,
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat command policies in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_command_policy(&self, body: &model::ChatCommandPolicy) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_command/upsert.sql"))
+            .bind(body.chat_id)
+            .bind(&body.command)
+            .bind(&body.access)
+            .bind(body.min_rating.as_ref().map(lib::helpers::f64_from_decimal))
+            .bind(body.cooldown_seconds)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat command policy in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<model::ChatRole>, DBError> {
+        sqlx::query_as::<_, (i64, i64, String, i64)>(include_str!("queries/sqlite/chat_role/fetch.sql"))
+            .bind(chat_id)
+            .bind(telegram_id)
+            .fetch_optional(&*self.pool)
+            .await
+            .map(|row| row.map(|(chat_id, telegram_id, role, granted_by)| model::ChatRole {
+                chat_id,
+                telegram_id,
+                role,
+                granted_by,
+            }))
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat role in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_role(&self, body: &model::ChatRole) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_role/upsert.sql"))
+            .bind(body.chat_id)
+            .bind(body.telegram_id)
+            .bind(&body.role)
+            .bind(body.granted_by)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat role in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_role/delete.sql"))
+            .bind(chat_id)
+            .bind(telegram_id)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to delete chat role in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat/migrate_chat_id.sql"))
+            .bind(from)
+            .bind(to)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to migrate chat ids in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat/change_active.sql"))
+            .bind(chat_id)
+            .bind(is_active)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat activity in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, DBError> {
+        sqlx::query_as::<_, (i64, String, i32, bool)>(include_str!("queries/sqlite/chat_auto_delete/fetch.sql"))
+            .bind(chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|(chat_id, category, ttl_seconds, delete_trigger)| model::AutoDeleteRule {
+                    chat_id,
+                    category,
+                    ttl_seconds,
+                    delete_trigger,
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat auto-delete rules in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_auto_delete/upsert.sql"))
+            .bind(body.chat_id)
+            .bind(&body.category)
+            .bind(body.ttl_seconds)
+            .bind(body.delete_trigger)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to save chat auto-delete rule in SQLite")
+            .map_err(DBError::Execute)
+    }
+}
+
+pub fn new_sqlite_chat_db_repository(pool: Arc<sqlx::SqlitePool>) -> Box<dyn ChatDBRepository> {
+    Box::new(SqliteChatDBRepositoryImpl { pool })
+}
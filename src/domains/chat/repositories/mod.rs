@@ -1,2 +1,4 @@
 mod db;
-pub use db::{ChatDBRepository, new_chat_db_repository};
\ No newline at end of file
+mod cache;
+pub use db::{ChatDBRepository, new_chat_db_repository, new_memory_chat_db_repository, new_sqlite_chat_db_repository};
+pub use cache::{ChatCacheRepository, new_chat_cache_repository, new_memory_chat_cache_repository};
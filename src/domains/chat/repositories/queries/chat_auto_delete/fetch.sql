@@ -0,0 +1,2 @@
+SELECT cad.chat_id, cad.category, cad.ttl_seconds, cad.delete_trigger FROM chat_auto_delete cad
+WHERE cad.chat_id = $1;
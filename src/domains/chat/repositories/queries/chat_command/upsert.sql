@@ -0,0 +1,2 @@
+INSERT INTO chat_command (chat_id, command, access, min_rating, cooldown_seconds) VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (chat_id, command) DO UPDATE SET access = $3, min_rating = $4, cooldown_seconds = $5;
\ No newline at end of file
@@ -0,0 +1,2 @@
+INSERT INTO chat_role (chat_id, telegram_id, role, granted_by) VALUES ($1, $2, $3, $4)
+ON CONFLICT (chat_id, telegram_id) DO UPDATE SET role = $3, granted_by = $4;
\ No newline at end of file
@@ -0,0 +1 @@
+SELECT cs.chat_id, cs.name, cs.value FROM chat_setting cs WHERE cs.chat_id = $1;
\ No newline at end of file
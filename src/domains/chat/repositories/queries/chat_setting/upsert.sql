@@ -0,0 +1,2 @@
+INSERT INTO chat_setting (chat_id, name, value) VALUES ($1, $2, $3)
+ON CONFLICT (chat_id, name) DO UPDATE SET value = $3;
\ No newline at end of file
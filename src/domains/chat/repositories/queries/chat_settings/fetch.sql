@@ -1 +1 @@
-SELECT * FROM chat_settings WHERE chat_settings.chat_id = $1;
\ No newline at end of file
+SELECT chat_id FROM chat_settings WHERE chat_settings.chat_id = $1;
\ No newline at end of file
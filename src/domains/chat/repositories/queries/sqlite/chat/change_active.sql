@@ -0,0 +1 @@
+UPDATE chat SET is_active = ?2 WHERE chat.chat_id = ?1;
@@ -0,0 +1,2 @@
+INSERT INTO chat_auto_delete (chat_id, category, ttl_seconds, delete_trigger) VALUES (?1, ?2, ?3, ?4)
+ON CONFLICT (chat_id, category) DO UPDATE SET ttl_seconds = ?3, delete_trigger = ?4;
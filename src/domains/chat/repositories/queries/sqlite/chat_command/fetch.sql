@@ -0,0 +1 @@
+SELECT cc.chat_id, cc.command, cc.access, cc.min_rating, cc.cooldown_seconds FROM chat_command cc WHERE cc.chat_id = ?1;
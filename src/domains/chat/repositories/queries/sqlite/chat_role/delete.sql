@@ -0,0 +1 @@
+DELETE FROM chat_role WHERE chat_id = ?1 AND telegram_id = ?2;
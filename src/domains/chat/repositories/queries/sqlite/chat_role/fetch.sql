@@ -0,0 +1 @@
+SELECT cr.chat_id, cr.telegram_id, cr.role, cr.granted_by FROM chat_role cr WHERE cr.chat_id = ?1 AND cr.telegram_id = ?2;
@@ -0,0 +1 @@
+INSERT INTO chat_settings(chat_id) VALUES (?1) ON CONFLICT DO NOTHING;
@@ -0,0 +1 @@
+SELECT chat_id FROM chat_settings WHERE chat_settings.chat_id = ?1;
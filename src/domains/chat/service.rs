@@ -1,5 +1,9 @@
-use crate::{lib, domains::chat::{model, repositories::ChatDBRepository}};
+use crate::{
+    lib::{self, enums::{Command, ModeratorRole, ReplyCategory}},
+    domains::chat::{model, registry, repositories::{ChatCacheRepository, ChatDBRepository}},
+};
 use async_trait::async_trait;
+use std::str::FromStr;
 
 #[async_trait]
 pub trait ChatService: Send + Sync {
@@ -8,10 +12,54 @@ pub trait ChatService: Send + Sync {
     async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, lib::errors::ChatError>;
     async fn change_chat_settings(&self, body: &model::ChatSettings) -> Result<(), lib::errors::ChatError>;
     async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), lib::errors::ChatError>;
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), lib::errors::ChatError>;
+    async fn set_command_policy(
+        &self,
+        chat_id: i64,
+        command: Command,
+        policy: &model::CommandPolicy,
+    ) -> Result<(), lib::errors::ChatError>;
+    /// Starts the member's cooldown of the command unless it's already running, in one step.
+    /// Returns the seconds left of the running cooldown, `None` when it was started.
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        cooldown_seconds: i32,
+    ) -> Result<Option<i64>, lib::errors::ChatError>;
+    /// `true` only for the first attempt while the cooldown is running, the member is told about it once.
+    async fn start_cooldown_notice(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        seconds_left: i64,
+    ) -> Result<bool, lib::errors::ChatError>;
+    /// `None` when the member has no role or the stored role is no longer known.
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<ModeratorRole>, lib::errors::ChatError>;
+    async fn set_role(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        role: ModeratorRole,
+        granted_by: i64,
+    ) -> Result<(), lib::errors::ChatError>;
+    /// `false` when the member had no role.
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, lib::errors::ChatError>;
+    /// Every reply category with the chat's setting, or the category default when the chat didn't change it.
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, lib::errors::ChatError>;
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), lib::errors::ChatError>;
+    /// Cached administrators of the chat. A cache error is logged and treated as a miss.
+    async fn get_cached_admins(&self, chat_id: i64) -> Option<Vec<i64>>;
+    async fn cache_admins(&self, chat_id: i64, admins: &[i64]);
+    async fn forget_admins(&self, chat_id: i64);
 }
 
 struct ChatServiceImpl {
     repo: Box<dyn ChatDBRepository>,
+    cache_repo: Box<dyn ChatCacheRepository>,
+    config: lib::config::SharedConfig,
 }
 
 #[async_trait]
@@ -47,42 +95,274 @@ impl ChatService for ChatServiceImpl {
     }
 
     async fn get_chat_settings(&self, chat_id: i64) -> Result<model::ChatSettings, lib::errors::ChatError> {
-        self.repo
+        let mut settings = self.repo
             .get_chat_settings(chat_id)
             .await
             .map_err(|err| err.into())
-            .map_err(lib::errors::ChatError::GetSettings)
+            .map_err(lib::errors::ChatError::GetSettings)?;
+        let values = self.repo
+            .get_setting_values(chat_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::GetSettings)?;
+        settings.values = registry::SettingValues::from_stored(
+            chat_id,
+            values.into_iter().map(|value| (value.name, value.value)).collect(),
+        );
+        let policies = self.repo
+            .get_command_policies(chat_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::GetSettings)?;
+        for policy in policies {
+            let command = Command::from_str(&policy.command).ok();
+            let access = model::CommandAccess::from_parts(&policy.access, policy.min_rating);
+            match (command, access) {
+                (Some(command), Some(access)) => {
+                    settings.commands.insert(
+                        command,
+                        model::CommandPolicy { access, cooldown_seconds: policy.cooldown_seconds },
+                    );
+                }
+                _ => log::warn!(
+                    "Skipped command policy {} (chat_id: {}): {}",
+                    policy.command,
+                    chat_id,
+                    policy.access,
+                ),
+            }
+        }
+        Ok(settings)
     }
 
     async fn change_chat_settings(&self, body: &model::ChatSettings) -> Result<(), lib::errors::ChatError> {
+        let values = body.values.to_stored();
+        for (name, value) in values.iter() {
+            self.repo
+                .set_setting_value(&model::ChatSettingValue {
+                    chat_id: body.chat_id,
+                    name: name.to_string(),
+                    value: value.clone(),
+                })
+                .await
+                .map_err(|err| err.into())
+                .map_err(lib::errors::ChatError::ChangeSettings)?;
+        }
+        log::info!("Successfully changed chat settings (id: {}, values: {:?})", body.chat_id, values);
+        Ok(())
+    }
+
+    async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), lib::errors::ChatError> {
+        self.repo
+            .migrate_chat(from, to)
+            .await
+            .map(|_| {
+                log::info!("Successfully migrated chat (from id: {}, to id: {})", from, to)
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::MigrateChat)
+    }
+
+    async fn change_active(&self, chat_id: i64, is_active: bool) -> Result<(), lib::errors::ChatError> {
+        self.repo
+            .change_active(chat_id, is_active)
+            .await
+            .map(|_| {
+                log::info!("Successfully changed chat activity (id: {}, is_active: {})", chat_id, is_active)
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::ChangeActive)
+    }
+
+    async fn set_command_policy(
+        &self,
+        chat_id: i64,
+        command: Command,
+        policy: &model::CommandPolicy,
+    ) -> Result<(), lib::errors::ChatError> {
         self.repo
-            .change_chat_settings(body)
+            .set_command_policy(&model::ChatCommandPolicy {
+                chat_id,
+                command: command.name().to_string(),
+                access: policy.access.name().to_string(),
+                min_rating: policy.access.min_rating(),
+                cooldown_seconds: policy.cooldown_seconds,
+            })
             .await
+            .map(|_| {
+                log::info!(
+                    "Successfully changed command policy (chat_id: {}, command: {}, policy: {:?})",
+                    chat_id,
+                    command.name(),
+                    policy,
+                )
+            })
             .map_err(|err| err.into())
             .map_err(lib::errors::ChatError::ChangeSettings)
+    }
+
+    async fn start_command_cooldown(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        cooldown_seconds: i32,
+    ) -> Result<Option<i64>, lib::errors::ChatError> {
+        let started = self.cache_repo
+            .start_command_cooldown(chat_id, telegram_id, command.name(), cooldown_seconds as u64)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::CommandCooldown)?;
+        if started {
+            return Ok(None);
+        }
+        self.cache_repo
+            .get_command_cooldown(chat_id, telegram_id, command.name())
+            .await
+            .map(|seconds_left| Some(seconds_left.max(1)))
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::CommandCooldown)
+    }
+
+    async fn start_cooldown_notice(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        command: Command,
+        seconds_left: i64,
+    ) -> Result<bool, lib::errors::ChatError> {
+        self.cache_repo
+            .start_cooldown_notice(chat_id, telegram_id, command.name(), seconds_left.max(1) as u64)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::CommandCooldown)
+    }
+
+    async fn get_role(&self, chat_id: i64, telegram_id: i64) -> Result<Option<ModeratorRole>, lib::errors::ChatError> {
+        let role = self.repo
+            .get_role(chat_id, telegram_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::GetRole)?;
+        Ok(role.and_then(|role| match ModeratorRole::from_str(&role.role) {
+            Ok(parsed) => Some(parsed),
+            Err(err) => {
+                log::warn!("Skipped chat role (chat_id: {}, telegram_id: {}): {}", chat_id, telegram_id, err);
+                None
+            }
+        }))
+    }
+
+    async fn set_role(
+        &self,
+        chat_id: i64,
+        telegram_id: i64,
+        role: ModeratorRole,
+        granted_by: i64,
+    ) -> Result<(), lib::errors::ChatError> {
+        self.repo
+            .set_role(&model::ChatRole { chat_id, telegram_id, role: role.name().to_string(), granted_by })
+            .await
             .map(|_| {
                 log::info!(
-                    "Successfully changed chat settings \
-                    (id: {}, is_rating_count: {}, commands_for_admin_only: {})",
-                    body.chat_id,
-                    body.is_rating_count,
-                    body.commands_for_admin_only,
+                    "Successfully granted chat role (chat_id: {}, telegram_id: {}, role: {}, granted_by: {})",
+                    chat_id,
+                    telegram_id,
+                    role.name(),
+                    granted_by,
                 )
             })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::ChangeRole)
     }
 
-    async fn migrate_chat(&self, from: i64, to: i64) -> Result<(), lib::errors::ChatError> {
+    async fn delete_role(&self, chat_id: i64, telegram_id: i64) -> Result<bool, lib::errors::ChatError> {
         self.repo
-            .migrate_chat(from, to)
+            .delete_role(chat_id, telegram_id)
+            .await
+            .inspect(|&deleted| {
+                if deleted {
+                    log::info!("Successfully removed chat role (chat_id: {}, telegram_id: {})", chat_id, telegram_id);
+                }
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::ChangeRole)
+    }
+
+    async fn get_auto_delete_rules(&self, chat_id: i64) -> Result<Vec<model::AutoDeleteRule>, lib::errors::ChatError> {
+        let rules = self.repo
+            .get_auto_delete_rules(chat_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::ChatError::GetAutoDelete)?;
+        Ok(ReplyCategory::ALL
+            .iter()
+            .map(|category| match rules.iter().find(|rule| rule.category == category.name()) {
+                Some(rule) => model::AutoDeleteRule {
+                    chat_id,
+                    category: category.name().to_string(),
+                    ttl_seconds: rule.ttl_seconds,
+                    delete_trigger: rule.delete_trigger,
+                },
+                None => {
+                    let (ttl_seconds, delete_trigger) = category.default_auto_delete();
+                    model::AutoDeleteRule {
+                        chat_id,
+                        category: category.name().to_string(),
+                        ttl_seconds,
+                        delete_trigger,
+                    }
+                }
+            })
+            .collect())
+    }
+
+    async fn set_auto_delete_rule(&self, body: &model::AutoDeleteRule) -> Result<(), lib::errors::ChatError> {
+        self.repo
+            .set_auto_delete_rule(body)
             .await
             .map(|_| {
-                log::info!("Successfully migrated chat (from id: {}, to id: {})", from, to)
+                log::info!(
+                    "Successfully changed auto-delete (id: {}, category: {}, ttl_seconds: {}, delete_trigger: {})",
+                    body.chat_id,
+                    body.category,
+                    body.ttl_seconds,
+                    body.delete_trigger,
+                )
             })
             .map_err(|err| err.into())
-            .map_err(lib::errors::ChatError::MigrateChat)
+            .map_err(lib::errors::ChatError::SetAutoDelete)
+    }
+
+    async fn get_cached_admins(&self, chat_id: i64) -> Option<Vec<i64>> {
+        self.cache_repo
+            .get_admins(chat_id)
+            .await
+            .unwrap_or_else(|err| {
+                log::error!("{:?}", err);
+                None
+            })
+    }
+
+    async fn cache_admins(&self, chat_id: i64, admins: &[i64]) {
+        let _ = self.cache_repo
+            .save_admins(chat_id, admins, self.config.load().cache.admins_ttl_seconds)
+            .await
+            .map_err(|err| { log::error!("{:?}", err); });
+    }
+
+    async fn forget_admins(&self, chat_id: i64) {
+        let _ = self.cache_repo
+            .delete_admins(chat_id)
+            .await
+            .map_err(|err| { log::error!("{:?}", err); });
     }
 }
 
-pub fn new_chat_service(repo: Box<dyn ChatDBRepository>) -> Box<dyn ChatService> {
-    Box::new(ChatServiceImpl { repo })
+pub fn new_chat_service(
+    repo: Box<dyn ChatDBRepository>,
+    cache_repo: Box<dyn ChatCacheRepository>,
+    config: lib::config::SharedConfig,
+) -> Box<dyn ChatService> {
+    Box::new(ChatServiceImpl { repo, cache_repo, config })
 }
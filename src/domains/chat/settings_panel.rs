@@ -0,0 +1,168 @@
+use crate::domains::chat::{model::ChatSettings, registry};
+use itertools::Itertools;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data of the settings panel buttons starts with it. It has no spaces, so it never looks like
+/// the rating cancel button data.
+pub const CALLBACK_PREFIX: &str = "settings:";
+
+/// What a press on a settings panel button asks for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PanelAction {
+    Main,
+    Close,
+    /// The sub-menu with the presets of a setting.
+    Menu(&'static registry::Setting),
+    /// Flips an on/off setting or moves a choice to its next option.
+    Toggle(&'static registry::Setting),
+    Set(&'static registry::Setting, registry::SettingValue),
+}
+
+/// Settings with presets are chosen in a sub-menu, the others are switched by a press on their button.
+fn has_menu(setting: &registry::Setting) -> bool {
+    !setting.presets.is_empty()
+}
+
+impl PanelAction {
+    /// `None` for data of other buttons and for outdated or forged data.
+    pub fn parse(data: &str) -> Option<PanelAction> {
+        let data = data.strip_prefix(CALLBACK_PREFIX)?;
+        match data.split(':').collect_vec()[..] {
+            ["main"] => Some(PanelAction::Main),
+            ["close"] => Some(PanelAction::Close),
+            ["toggle", key] => registry::find(key).filter(|setting| !has_menu(setting)).map(PanelAction::Toggle),
+            ["menu", key] => registry::find(key).filter(|setting| has_menu(setting)).map(PanelAction::Menu),
+            ["set", key, value] => {
+                let setting = registry::find(key).filter(|setting| has_menu(setting))?;
+                setting.parse(value).ok().map(|value| PanelAction::Set(setting, value))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn button(text: String, data: String) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, format!("{}{}", CALLBACK_PREFIX, data))
+}
+
+/// The new value of an on/off setting or a choice after a press on its button.
+pub fn toggle(setting: &'static registry::Setting, settings: &ChatSettings)
+    -> (&'static registry::Setting, registry::SettingValue) {
+    (setting, setting.next_value(&settings.values.get(setting)))
+}
+
+pub fn main_text(settings: &ChatSettings) -> String {
+    std::iter::once("<b>Настройки чата:</b>".to_string())
+        .chain(registry::SETTINGS
+            .iter()
+            .map(|setting| format!("{}: <b>{}</b>", setting.title, settings.values.get(setting).describe())))
+        .join("\n")
+}
+
+pub fn main_keyboard(settings: &ChatSettings) -> InlineKeyboardMarkup {
+    // Two on/off settings and choices per row, so a check and its action stay next to each other.
+    let toggles = registry::SETTINGS
+        .iter()
+        .filter(|setting| !has_menu(setting))
+        .map(|setting| button(
+            format!("{}: {}", setting.title, settings.values.get(setting).describe()),
+            format!("toggle:{}", setting.key),
+        ))
+        .chunks(2)
+        .into_iter()
+        .map(|row| row.collect_vec())
+        .collect_vec();
+    let menus = registry::SETTINGS.iter().filter(|setting| has_menu(setting)).map(|setting| vec![button(
+        format!("{}: {}", setting.title, settings.values.get(setting).describe()),
+        format!("menu:{}", setting.key),
+    )]);
+    InlineKeyboardMarkup::new(
+        toggles
+            .into_iter()
+            .chain(menus)
+            .chain(std::iter::once(vec![button("Закрыть".to_string(), "close".to_string())]))
+            .collect_vec(),
+    )
+}
+
+pub fn menu_text(setting: &registry::Setting, settings: &ChatSettings) -> String {
+    format!(
+        "<b>{}</b>\n{}\n\nТекущее значение: <b>{}</b>",
+        setting.title,
+        setting.description,
+        settings.values.get(setting).describe(),
+    )
+}
+
+/// Presets of the setting, the current one is marked, and a button back to the main panel.
+pub fn menu_keyboard(setting: &registry::Setting, settings: &ChatSettings) -> InlineKeyboardMarkup {
+    let current = settings.values.get(setting);
+    let presets = setting
+        .presets
+        .iter()
+        .filter_map(|preset| setting.parse(preset).ok().map(|value| (*preset, value)))
+        .map(|(preset, value)| {
+            let label = value.describe();
+            button(
+                if value == current { format!("• {}", label) } else { label },
+                format!("set:{}:{}", setting.key, preset),
+            )
+        })
+        .collect_vec();
+    InlineKeyboardMarkup::new(vec![presets, vec![button("« Назад".to_string(), "main".to_string())]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use teloxide::types::InlineKeyboardButtonKind;
+
+    fn settings() -> ChatSettings {
+        let mut values = registry::SettingValues::default();
+        values.set(registry::VOTER_MIN_AGE.key, registry::SettingValue::Duration(86400));
+        ChatSettings { chat_id: 1, values, commands: Default::default() }
+    }
+
+    fn menus() -> Vec<&'static registry::Setting> {
+        registry::SETTINGS.iter().copied().filter(|setting| has_menu(setting)).collect_vec()
+    }
+
+    #[test]
+    fn every_button_data_is_parsed_back_and_fits_telegram_limit() {
+        let settings = settings();
+        let keyboards = std::iter::once(main_keyboard(&settings))
+            .chain(menus().into_iter().map(|setting| menu_keyboard(setting, &settings)))
+            .collect_vec();
+        let buttons = keyboards.iter().flat_map(|keyboard| keyboard.inline_keyboard.iter().flatten()).collect_vec();
+
+        let presets_count: usize = menus().iter().map(|setting| setting.presets.len()).sum();
+        assert_eq!(buttons.len(), registry::SETTINGS.len() + 1 + presets_count + menus().len());
+        for button in buttons {
+            match &button.kind {
+                InlineKeyboardButtonKind::CallbackData(data) => {
+                    assert!(data.len() <= 64, "{} is too long", data);
+                    assert!(PanelAction::parse(data).is_some(), "{} isn't parsed", data);
+                }
+                kind => panic!("Unexpected button {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn current_preset_is_marked_and_invalid_values_are_rejected() {
+        let keyboard = menu_keyboard(&registry::VOTER_MIN_AGE, &settings());
+        let labels = keyboard.inline_keyboard[0].iter().map(|button| button.text.as_str()).collect_vec();
+        assert_eq!(labels, vec!["0s", "1h", "• 1d", "7d", "30d"]);
+
+        assert_eq!(PanelAction::parse("settings:set:voter_min_messages:-5"), None);
+        assert_eq!(PanelAction::parse("settings:set:global_reputation_weight:-1"), None);
+        assert_eq!(PanelAction::parse("123 456"), None);
+        assert_eq!(PanelAction::parse("settings:toggle:removed_setting"), None);
+        assert_eq!(PanelAction::parse("settings:toggle:voter_min_age"), None);
+        assert_eq!(PanelAction::parse("settings:set:rating_count:false"), None);
+        assert_eq!(
+            PanelAction::parse("settings:set:voter_min_age:3600"),
+            Some(PanelAction::Set(&registry::VOTER_MIN_AGE, registry::SettingValue::Duration(3600))),
+        );
+    }
+}
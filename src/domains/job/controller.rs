@@ -0,0 +1,203 @@
+use crate::{domains::job::{model, service::JobService}, lib::{self, bot_actions::BotActions}};
+use async_trait::async_trait;
+use std::sync::Arc;
+use teloxide::{ApiError, RequestError};
+
+#[async_trait]
+pub trait JobController: Send + Sync {
+    /// Runs the jobs that are due now and returns how many were claimed.
+    async fn run_due_jobs(&self, requester: &dyn BotActions) -> Result<usize, lib::errors::JobError>;
+    async fn count_pending(&self) -> Result<i64, lib::errors::JobError>;
+}
+
+struct JobControllerImpl {
+    service: Arc<dyn JobService>,
+}
+
+/// The message, member or chat is already gone, or the bot was removed from it, so retrying won't help.
+fn is_target_gone(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<RequestError>(),
+        Some(RequestError::ApiError {
+            kind: ApiError::MessageToDeleteNotFound
+                | ApiError::MessageCantBeDeleted
+                | ApiError::MessageIdInvalid
+                | ApiError::ChatNotFound
+                | ApiError::UserNotFound
+                | ApiError::GroupDeactivated
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup,
+            ..
+        })
+    )
+}
+
+async fn run_action(requester: &dyn BotActions, action: model::JobAction) -> Result<(), anyhow::Error> {
+    match action {
+        model::JobAction::DeleteMessage { chat_id, message_id } => requester.delete_message(chat_id, message_id).await,
+        model::JobAction::LiftRestrictions { chat_id, user_id } => requester.lift_restrictions(chat_id, user_id).await,
+        model::JobAction::SendMessage { chat_id, text } => requester
+            .send_message(chat_id, text, None, None)
+            .await
+            .map(|_| ()),
+        model::JobAction::UnpinMessage { chat_id, message_id } =>
+            requester.unpin_chat_message(chat_id, message_id).await,
+    }
+}
+
+#[async_trait]
+impl JobController for JobControllerImpl {
+    async fn run_due_jobs(&self, requester: &dyn BotActions) -> Result<usize, lib::errors::JobError> {
+        let jobs = self.service.claim_due(chrono::Utc::now()).await?;
+        let count = jobs.len();
+        for job in jobs {
+            let action = match model::JobAction::from_parts(&job.kind, job.payload.clone()) {
+                Ok(action) => action,
+                Err(err) => {
+                    self.service.retry_or_fail(&job, format!("Invalid payload: {}", err), true).await?;
+                    continue;
+                }
+            };
+            match run_action(requester, action).await {
+                Ok(_) => self.service.complete(job.job_id).await?,
+                Err(err) if is_target_gone(&err) => {
+                    log::info!("Dropping job {} ({}): {:#}", job.job_id, job.kind, err);
+                    self.service.complete(job.job_id).await?
+                }
+                Err(err) => self.service.retry_or_fail(&job, format!("{:#}", err), false).await?,
+            }
+        }
+        Ok(count)
+    }
+
+    async fn count_pending(&self) -> Result<i64, lib::errors::JobError> {
+        self.service.count_pending().await
+    }
+}
+
+pub fn new_job_controller(service: Arc<dyn JobService>) -> Box<dyn JobController> {
+    Box::new(JobControllerImpl { service })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::MemoryStore;
+    use crate::domains::job::{repositories, service::new_job_service};
+    use crate::lib::{bot_actions::{BotCall, RecordingBotActions}, test_helpers};
+
+    fn memory_job_service(store: &Arc<MemoryStore>) -> Arc<dyn JobService> {
+        Arc::from(new_job_service(repositories::new_memory_job_db_repository(store.clone()), test_helpers::config()))
+    }
+
+    #[tokio::test]
+    async fn due_jobs_run_once() {
+        let store = Arc::new(MemoryStore::default());
+        let service = memory_job_service(&store);
+        let now = chrono::Utc::now();
+        service
+            .schedule(model::JobAction::DeleteMessage { chat_id: 1, message_id: 10 }, now)
+            .await
+            .unwrap();
+        service
+            .schedule(model::JobAction::UnpinMessage { chat_id: 1, message_id: 11 }, now + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        let bot = RecordingBotActions::new();
+        let controller = new_job_controller(service);
+
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 1);
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 0);
+
+        assert_eq!(bot.calls(), vec![BotCall::DeleteMessage { chat_id: 1, message_id: 10 }]);
+        assert_eq!(controller.count_pending().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_job_is_retried_later() {
+        let store = Arc::new(MemoryStore::default());
+        let service = memory_job_service(&store);
+        service
+            .schedule(model::JobAction::LiftRestrictions { chat_id: 1, user_id: 2 }, chrono::Utc::now())
+            .await
+            .unwrap();
+        let bot = RecordingBotActions::new().with_failing("restrictChatMember");
+        let controller = new_job_controller(service);
+
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 1);
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 0);
+
+        let tables = store.tables();
+        assert_eq!(tables.jobs.len(), 1);
+        assert_eq!(tables.jobs[0].attempts, 1);
+        assert!(tables.jobs[0].failed_at.is_none());
+        assert!(tables.jobs[0].run_at > chrono::Utc::now().naive_utc());
+        assert!(tables.jobs[0].last_error.as_ref().unwrap().contains("set to fail"));
+    }
+
+    #[tokio::test]
+    async fn job_for_a_deleted_message_is_dropped() {
+        let store = Arc::new(MemoryStore::default());
+        let service = memory_job_service(&store);
+        service
+            .schedule(model::JobAction::DeleteMessage { chat_id: 1, message_id: 10 }, chrono::Utc::now())
+            .await
+            .unwrap();
+        let bot = RecordingBotActions::new().with_api_error("deleteMessage", ApiError::MessageToDeleteNotFound);
+        let controller = new_job_controller(service);
+
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 1);
+
+        assert!(store.tables().jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn every_job_kind_makes_its_telegram_call() {
+        let store = Arc::new(MemoryStore::default());
+        let service = memory_job_service(&store);
+        let now = chrono::Utc::now();
+        for action in [
+            model::JobAction::LiftRestrictions { chat_id: 1, user_id: 2 },
+            model::JobAction::SendMessage { chat_id: 1, text: "Напоминание".to_string() },
+            model::JobAction::UnpinMessage { chat_id: 1, message_id: 12 },
+        ] {
+            service.schedule(action, now).await.unwrap();
+        }
+        let bot = RecordingBotActions::new();
+        let controller = new_job_controller(service);
+
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 3);
+
+        let calls = bot.calls();
+        assert_eq!(calls.len(), 3);
+        for call in [
+            BotCall::LiftRestrictions { chat_id: 1, user_id: 2 },
+            BotCall::SendMessage {
+                chat_id: 1,
+                text: "Напоминание".to_string(),
+                reply_to_message_id: None,
+                reply_markup: None,
+            },
+            BotCall::UnpinChatMessage { chat_id: 1, message_id: 12 },
+        ] {
+            assert!(calls.contains(&call), "{:?} wasn't made", call);
+        }
+        assert!(store.tables().jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unmute_for_a_member_who_left_the_chat_is_dropped() {
+        let store = Arc::new(MemoryStore::default());
+        let service = memory_job_service(&store);
+        service
+            .schedule(model::JobAction::LiftRestrictions { chat_id: 1, user_id: 2 }, chrono::Utc::now())
+            .await
+            .unwrap();
+        let bot = RecordingBotActions::new().with_api_error("restrictChatMember", ApiError::UserNotFound);
+        let controller = new_job_controller(service);
+
+        assert_eq!(controller.run_due_jobs(&bot).await.unwrap(), 1);
+
+        assert!(store.tables().jobs.is_empty());
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{core::persistence::Storage, lib::config::SharedConfig};
+use crate::domains::job::{controller, repositories, service};
+use std::sync::Arc;
+
+pub struct JobDomain {
+    pub controller: Box<dyn controller::JobController>,
+    /// Shared with the domains that schedule jobs.
+    pub service: Arc<dyn service::JobService>,
+}
+
+pub async fn new_job_domain(storage: &Storage, config: SharedConfig) -> JobDomain {
+    let repo = match storage {
+        Storage::Postgres { pool, .. } => repositories::new_job_db_repository(pool.clone()),
+        Storage::Sqlite { pool, .. } => repositories::new_sqlite_job_db_repository(pool.clone()),
+        Storage::Memory(store) => repositories::new_memory_job_db_repository(store.clone()),
+    };
+    let service: Arc<dyn service::JobService> = Arc::from(service::new_job_service(repo, config));
+    let controller = controller::new_job_controller(service.clone());
+    JobDomain { controller, service }
+}
@@ -0,0 +1,10 @@
+mod controller;
+mod domain_builder;
+mod model;
+mod repositories;
+mod service;
+pub use {
+    domain_builder::{new_job_domain, JobDomain},
+    model::JobAction,
+    service::JobService,
+};
@@ -0,0 +1,55 @@
+use serde_json::json;
+
+/// Telegram calls made by the job worker, stored as the `kind` tag and a JSON `payload`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum JobAction {
+    DeleteMessage { chat_id: i64, message_id: i32 },
+    /// Gives back the permissions taken by a mute.
+    LiftRestrictions { chat_id: i64, user_id: i64 },
+    /// Reminders and other delayed announcements.
+    SendMessage { chat_id: i64, text: String },
+    UnpinMessage { chat_id: i64, message_id: i32 },
+}
+
+impl JobAction {
+    pub fn to_parts(&self) -> Result<(String, serde_json::Value), serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        let kind = value["kind"].as_str().unwrap_or_default().to_string();
+        Ok((kind, value["payload"].take()))
+    }
+
+    pub fn from_parts(kind: &str, payload: serde_json::Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(json!({ "kind": kind, "payload": payload }))
+    }
+}
+
+pub struct JobRequest {
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub run_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub job_id: sqlx::types::Uuid,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub run_at: chrono::NaiveDateTime,
+    /// Including the current one.
+    pub attempts: i32,
+}
+
+pub struct ClaimJobsRequest {
+    pub now: chrono::NaiveDateTime,
+    /// Other workers skip the claimed jobs until then, so a crashed worker's jobs are picked up again.
+    pub locked_until: chrono::NaiveDateTime,
+    pub limit: i64,
+}
+
+pub struct JobUpdateRequest {
+    pub job_id: sqlx::types::Uuid,
+    /// Next run for a retry, failure time otherwise.
+    pub at: chrono::NaiveDateTime,
+    pub error: String,
+}
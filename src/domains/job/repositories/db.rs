@@ -0,0 +1,298 @@
+use crate::core::persistence::{JobRow, MemoryStore};
+use crate::domains::job::model;
+use crate::lib::{self, errors::DBError};
+use async_trait::async_trait;
+use anyhow::Context;
+use std::sync::Arc;
+
+#[async_trait]
+pub trait JobDBRepository: Send + Sync {
+    async fn create(&self, body: &model::JobRequest) -> Result<sqlx::types::Uuid, DBError>;
+    /// Locks due jobs for this worker and counts the attempt.
+    async fn claim_due(&self, body: &model::ClaimJobsRequest) -> Result<Vec<model::Job>, DBError>;
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), DBError>;
+    async fn retry(&self, body: &model::JobUpdateRequest) -> Result<(), DBError>;
+    async fn fail(&self, body: &model::JobUpdateRequest) -> Result<(), DBError>;
+    async fn count_pending(&self) -> Result<i64, DBError>;
+}
+
+struct PgJobDBRepositoryImpl {
+    pool: Arc<sqlx::PgPool>,
+}
+
+#[async_trait]
+impl JobDBRepository for PgJobDBRepositoryImpl {
+    async fn create(&self, body: &model::JobRequest) -> Result<sqlx::types::Uuid, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.create"]).start_timer();
+        sqlx::query_file_scalar!(
+            "src/domains/job/repositories/queries/job/create.sql",
+            body.kind,
+            body.payload,
+            body.run_at,
+        )
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to create job in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn claim_due(&self, body: &model::ClaimJobsRequest) -> Result<Vec<model::Job>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.claim_due"]).start_timer();
+        sqlx::query_file_as!(
+            model::Job,
+            "src/domains/job/repositories/queries/job/claim_due.sql",
+            body.now,
+            body.locked_until,
+            body.limit,
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to claim due jobs in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.complete"]).start_timer();
+        sqlx::query_file!("src/domains/job/repositories/queries/job/complete.sql", job_id)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to complete job in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn retry(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.retry"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/job/repositories/queries/job/retry.sql",
+            body.job_id,
+            body.at,
+            body.error,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to reschedule job in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fail(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.fail"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/job/repositories/queries/job/fail.sql",
+            body.job_id,
+            body.at,
+            body.error,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to mark job as failed in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn count_pending(&self) -> Result<i64, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["job.count_pending"]).start_timer();
+        sqlx::query_file_scalar!("src/domains/job/repositories/queries/job/count_pending.sql")
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to count pending jobs in Postgres")
+            .map_err(DBError::Execute)
+    }
+}
+
+pub fn new_job_db_repository(pool: Arc<sqlx::PgPool>) -> Box<dyn JobDBRepository> {
+    Box::new(PgJobDBRepositoryImpl { pool })
+}
+
+struct MemoryJobDBRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+#[async_trait]
+impl JobDBRepository for MemoryJobDBRepositoryImpl {
+    async fn create(&self, body: &model::JobRequest) -> Result<sqlx::types::Uuid, DBError> {
+        let job_id = sqlx::types::Uuid::new_v4();
+        self.store.tables().jobs.push(JobRow {
+            job_id,
+            kind: body.kind.clone(),
+            payload: body.payload.clone(),
+            run_at: body.run_at,
+            attempts: 0,
+            locked_until: None,
+            last_error: None,
+            failed_at: None,
+        });
+        Ok(job_id)
+    }
+
+    async fn claim_due(&self, body: &model::ClaimJobsRequest) -> Result<Vec<model::Job>, DBError> {
+        let mut tables = self.store.tables();
+        let mut due = tables.jobs
+            .iter_mut()
+            .filter(|job| job.run_at <= body.now && job.failed_at.is_none())
+            .filter(|job| job.locked_until.is_none_or(|locked_until| locked_until <= body.now))
+            .collect::<Vec<_>>();
+        due.sort_by_key(|job| job.run_at);
+        Ok(due
+            .into_iter()
+            .take(body.limit as usize)
+            .map(|job| {
+                job.locked_until = Some(body.locked_until);
+                job.attempts += 1;
+                model::Job {
+                    job_id: job.job_id,
+                    kind: job.kind.clone(),
+                    payload: job.payload.clone(),
+                    run_at: job.run_at,
+                    attempts: job.attempts,
+                }
+            })
+            .collect())
+    }
+
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        self.store.tables().jobs.retain(|job| job.job_id != job_id);
+        Ok(())
+    }
+
+    async fn retry(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        if let Some(job) = self.store.tables().jobs.iter_mut().find(|job| job.job_id == body.job_id) {
+            job.run_at = body.at;
+            job.locked_until = None;
+            job.last_error = Some(body.error.clone());
+        }
+        Ok(())
+    }
+
+    async fn fail(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        if let Some(job) = self.store.tables().jobs.iter_mut().find(|job| job.job_id == body.job_id) {
+            job.failed_at = Some(body.at);
+            job.locked_until = None;
+            job.last_error = Some(body.error.clone());
+        }
+        Ok(())
+    }
+
+    async fn count_pending(&self) -> Result<i64, DBError> {
+        Ok(self.store.tables().jobs.iter().filter(|job| job.failed_at.is_none()).count() as i64)
+    }
+}
+
+pub fn new_memory_job_db_repository(store: Arc<MemoryStore>) -> Box<dyn JobDBRepository> {
+    Box::new(MemoryJobDBRepositoryImpl { store })
+}
+
+struct SqliteJobDBRepositoryImpl {
+    pool: Arc<sqlx::SqlitePool>,
+}
+
+#[async_trait]
+impl JobDBRepository for SqliteJobDBRepositoryImpl {
+    async fn create(&self, body: &model::JobRequest) -> Result<sqlx::types::Uuid, DBError> {
+        let job_id = sqlx::types::Uuid::new_v4();
+        sqlx::query(include_str!("queries/sqlite/job/create.sql"))
+            .bind(job_id.to_string())
+            .bind(&body.kind)
+            .bind(body.payload.to_string())
+            .bind(body.run_at)
+            .execute(&*self.pool)
+            .await
+            .map(|_| job_id)
+            .map_err(anyhow::Error::new)
+            .context("Failed to create job in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    /// SQLite has a single writer, so a transaction is enough to keep two claims from taking the same job.
+    async fn claim_due(&self, body: &model::ClaimJobsRequest) -> Result<Vec<model::Job>, DBError> {
+        let claim = async {
+            let mut tx = self.pool.begin().await?;
+            let rows = sqlx::query_as::<_, (String, String, String, chrono::NaiveDateTime, i32)>(
+                include_str!("queries/sqlite/job/fetch_due.sql")
+            )
+                .bind(body.now)
+                .bind(body.limit)
+                .fetch_all(&mut tx)
+                .await?;
+
+            let mut jobs = vec![];
+            for (job_id, kind, payload, run_at, attempts) in rows {
+                sqlx::query(include_str!("queries/sqlite/job/lock.sql"))
+                    .bind(&job_id)
+                    .bind(body.locked_until)
+                    .execute(&mut tx)
+                    .await?;
+                jobs.push(model::Job {
+                    job_id: sqlx::types::Uuid::parse_str(&job_id)?,
+                    kind,
+                    payload: serde_json::from_str(&payload)?,
+                    run_at,
+                    attempts: attempts + 1,
+                });
+            }
+            tx.commit().await?;
+            Ok::<_, anyhow::Error>(jobs)
+        };
+        claim
+            .await
+            .context("Failed to claim due jobs in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/job/complete.sql"))
+            .bind(job_id.to_string())
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to complete job in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn retry(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/job/retry.sql"))
+            .bind(body.job_id.to_string())
+            .bind(body.at)
+            .bind(&body.error)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to reschedule job in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fail(&self, body: &model::JobUpdateRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/job/fail.sql"))
+            .bind(body.job_id.to_string())
+            .bind(body.at)
+            .bind(&body.error)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to mark job as failed in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn count_pending(&self) -> Result<i64, DBError> {
+        sqlx::query_scalar::<_, i64>(include_str!("queries/sqlite/job/count_pending.sql"))
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to count pending jobs in SQLite")
+            .map_err(DBError::Execute)
+    }
+}
+
+pub fn new_sqlite_job_db_repository(pool: Arc<sqlx::SqlitePool>) -> Box<dyn JobDBRepository> {
+    Box::new(SqliteJobDBRepositoryImpl { pool })
+}
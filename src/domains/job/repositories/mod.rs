@@ -0,0 +1,2 @@
+mod db;
+pub use db::{JobDBRepository, new_job_db_repository, new_memory_job_db_repository, new_sqlite_job_db_repository};
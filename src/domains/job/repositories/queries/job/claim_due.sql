@@ -0,0 +1,10 @@
+UPDATE job
+SET locked_until = $2, attempts = job.attempts + 1
+WHERE job.job_id IN (
+    SELECT j.job_id FROM job j
+    WHERE j.run_at <= $1 AND j.failed_at IS NULL AND (j.locked_until IS NULL OR j.locked_until <= $1)
+    ORDER BY j.run_at
+    LIMIT $3
+    FOR UPDATE SKIP LOCKED
+)
+RETURNING job.job_id, job.kind, job.payload, job.run_at, job.attempts;
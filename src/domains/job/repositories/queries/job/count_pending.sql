@@ -0,0 +1 @@
+SELECT COUNT(*) AS "count!" FROM job WHERE job.failed_at IS NULL;
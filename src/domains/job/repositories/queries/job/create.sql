@@ -0,0 +1 @@
+INSERT INTO job (kind, payload, run_at) VALUES ($1, $2, $3) RETURNING job_id;
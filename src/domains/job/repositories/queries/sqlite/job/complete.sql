@@ -0,0 +1 @@
+DELETE FROM job WHERE job.job_id = ?1;
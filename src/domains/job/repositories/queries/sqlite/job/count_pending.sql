@@ -0,0 +1 @@
+SELECT COUNT(*) FROM job WHERE job.failed_at IS NULL;
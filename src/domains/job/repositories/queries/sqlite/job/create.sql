@@ -0,0 +1 @@
+INSERT INTO job (job_id, kind, payload, run_at) VALUES (?1, ?2, ?3, ?4);
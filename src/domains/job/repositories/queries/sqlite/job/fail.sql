@@ -0,0 +1 @@
+UPDATE job SET failed_at = ?2, locked_until = NULL, last_error = ?3 WHERE job.job_id = ?1;
@@ -0,0 +1,4 @@
+SELECT j.job_id, j.kind, j.payload, j.run_at, j.attempts FROM job j
+WHERE j.run_at <= ?1 AND j.failed_at IS NULL AND (j.locked_until IS NULL OR j.locked_until <= ?1)
+ORDER BY j.run_at
+LIMIT ?2;
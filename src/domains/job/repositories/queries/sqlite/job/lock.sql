@@ -0,0 +1 @@
+UPDATE job SET locked_until = ?2, attempts = job.attempts + 1 WHERE job.job_id = ?1;
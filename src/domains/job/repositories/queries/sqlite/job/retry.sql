@@ -0,0 +1 @@
+UPDATE job SET run_at = ?2, locked_until = NULL, last_error = ?3 WHERE job.job_id = ?1;
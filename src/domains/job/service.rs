@@ -0,0 +1,126 @@
+use crate::{lib, domains::job::{model, repositories::JobDBRepository}};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// A claimed job is skipped by other workers for this long, then it's considered abandoned and claimed again.
+const JOB_LEASE_SECONDS: i64 = 120;
+
+#[async_trait]
+pub trait JobService: Send + Sync {
+    /// Stores `action` to be run by the job worker at `run_at`.
+    async fn schedule(&self, action: model::JobAction, run_at: DateTime<Utc>)
+        -> Result<sqlx::types::Uuid, lib::errors::JobError>;
+    async fn claim_due(&self, now: DateTime<Utc>) -> Result<Vec<model::Job>, lib::errors::JobError>;
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), lib::errors::JobError>;
+    /// Reschedules the job with exponential backoff, or marks it as failed once it ran out of attempts
+    /// or `is_permanent` is set.
+    async fn retry_or_fail(&self, job: &model::Job, error: String, is_permanent: bool)
+        -> Result<(), lib::errors::JobError>;
+    async fn count_pending(&self) -> Result<i64, lib::errors::JobError>;
+}
+
+struct JobServiceImpl {
+    repo: Box<dyn JobDBRepository>,
+    config: lib::config::SharedConfig,
+}
+
+/// `backoff_seconds` for the first attempt, doubled for every next one and capped at `max_backoff_seconds`.
+fn backoff(config: &lib::config::JobsConfig, attempts: i32) -> chrono::Duration {
+    let exponent = (attempts - 1).clamp(0, 30) as u32;
+    let seconds = config.backoff_seconds.saturating_mul(2i64.saturating_pow(exponent));
+    chrono::Duration::seconds(seconds.min(config.max_backoff_seconds))
+}
+
+#[async_trait]
+impl JobService for JobServiceImpl {
+    async fn schedule(&self, action: model::JobAction, run_at: DateTime<Utc>)
+        -> Result<sqlx::types::Uuid, lib::errors::JobError> {
+        let (kind, payload) = action
+            .to_parts()
+            .map_err(anyhow::Error::new)
+            .map_err(lib::errors::JobError::Schedule)?;
+        self.repo
+            .create(&model::JobRequest { kind, payload, run_at: run_at.naive_utc() })
+            .await
+            .inspect(|job_id| log::debug!("Scheduled job {} at {}: {:?}", job_id, run_at, action))
+            .map_err(|err| err.into())
+            .map_err(lib::errors::JobError::Schedule)
+    }
+
+    async fn claim_due(&self, now: DateTime<Utc>) -> Result<Vec<model::Job>, lib::errors::JobError> {
+        self.repo
+            .claim_due(&model::ClaimJobsRequest {
+                now: now.naive_utc(),
+                locked_until: (now + chrono::Duration::seconds(JOB_LEASE_SECONDS)).naive_utc(),
+                limit: self.config.load().jobs.batch_size,
+            })
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::JobError::Claim)
+    }
+
+    async fn complete(&self, job_id: sqlx::types::Uuid) -> Result<(), lib::errors::JobError> {
+        self.repo
+            .complete(job_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::JobError::Complete)
+    }
+
+    async fn retry_or_fail(&self, job: &model::Job, error: String, is_permanent: bool)
+        -> Result<(), lib::errors::JobError> {
+        let config = self.config.load().jobs.clone();
+        let now = Utc::now();
+        if is_permanent || job.attempts >= config.max_attempts {
+            log::error!(
+                "Job {} ({}) due at {} failed after {} attempts: {}",
+                job.job_id,
+                job.kind,
+                job.run_at,
+                job.attempts,
+                error,
+            );
+            return self.repo
+                .fail(&model::JobUpdateRequest { job_id: job.job_id, at: now.naive_utc(), error })
+                .await
+                .map_err(|err| err.into())
+                .map_err(lib::errors::JobError::Retry);
+        }
+
+        let run_at = now + backoff(&config, job.attempts);
+        log::warn!("Job {} ({}) will be retried at {}: {}", job.job_id, job.kind, run_at, error);
+        self.repo
+            .retry(&model::JobUpdateRequest { job_id: job.job_id, at: run_at.naive_utc(), error })
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::JobError::Retry)
+    }
+
+    async fn count_pending(&self) -> Result<i64, lib::errors::JobError> {
+        self.repo
+            .count_pending()
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::JobError::CountPending)
+    }
+}
+
+pub fn new_job_service(repo: Box<dyn JobDBRepository>, config: lib::config::SharedConfig) -> Box<dyn JobService> {
+    Box::new(JobServiceImpl { repo, config })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let config = lib::config::JobsConfig {
+            backoff_seconds: 5,
+            max_backoff_seconds: 60,
+            ..Default::default()
+        };
+        let delays = (1..=6).map(|attempts| backoff(&config, attempts).num_seconds()).collect::<Vec<_>>();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+    }
+}
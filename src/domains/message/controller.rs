@@ -1,48 +1,103 @@
-use crate::{domains::message::service::MessageService, lib, Requester};
+use crate::{domains::message::service::MessageService, lib};
 use async_trait::async_trait;
+use std::sync::Arc;
 use teloxide;
+use crate::lib::auto_delete::AutoDelete;
+use crate::lib::enums::{ModerationAction, ReplyCategory};
 use crate::lib::errors::MessageControllerError;
 use crate::lib::types::MessageContext;
 
 #[async_trait]
 pub trait MessageController: Send + Sync {
+    /// `action` is `Ban`, `Delete` or `Report`. `admins` are mentioned when the chat has to step in.
     async fn check_link_in_message(
-        &self, cx: &lib::types::MessageContext,
+        &self, cx: &lib::types::MessageContext, admins: &[i64], action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError>;
+    /// `action` is `Ban` or `Report`.
     async fn check_new_member(
-        &self, cx: &crate::lib::types::MessageContext, new_member: &teloxide::types::User,
+        &self,
+        cx: &crate::lib::types::MessageContext,
+        admins: &[i64],
+        new_member: &teloxide::types::User,
+        action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError>;
+    /// `action` is `Ban` or `Report`.
     async fn check_author(
-        &self, cx: &crate::lib::types::MessageContext,
+        &self, cx: &crate::lib::types::MessageContext, admins: &[i64], action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError>;
+    /// `action` is `Warn` or `Delete`.
     async fn check_politics_in_text(
-        &self, cx: &crate::lib::types::MessageContext,
+        &self, cx: &crate::lib::types::MessageContext, action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError>;
+    /// `action` is `Warn` or `Delete`.
     async fn check_insult_in_text(
-        &self, cx: &crate::lib::types::MessageContext,
+        &self, cx: &crate::lib::types::MessageContext, action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError>;
 }
 
+/// Replies to the message with `msg_text` followed by the mentions of `admins`.
+async fn report_to_admins(cx: &MessageContext, auto_delete: &dyn AutoDelete, admins: &[i64], msg_text: &str)
+    -> Result<(), anyhow::Error> {
+    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
+    let msg_text = msg_text.to_owned() + &admin_mentions.join("");
+    lib::tg_helpers::reply_to(cx, auto_delete, ReplyCategory::Moderation, msg_text).await
+}
+
+/// `Warn` replies to the message with the lecture, `Delete` deletes the message and sends the lecture on its own.
+async fn lecture(
+    cx: &MessageContext,
+    auto_delete: &dyn AutoDelete,
+    action: ModerationAction,
+    reason: &str,
+    msg_text: &str,
+) -> Result<(), MessageControllerError> {
+    match action {
+        ModerationAction::Delete => {
+            cx.requester
+                .delete_message(cx.update.chat_id(), cx.update.id)
+                .await
+                .map_err(MessageControllerError::SendAnswer)?;
+            lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["delete", reason]).inc();
+            lib::tg_helpers::send_message(cx, auto_delete, ReplyCategory::Warning, msg_text.to_string())
+                .await
+                .map_err(MessageControllerError::SendAnswer)
+        }
+        _ => lib::tg_helpers::reply_to(cx, auto_delete, ReplyCategory::Warning, msg_text.to_string())
+            .await
+            .map_err(MessageControllerError::SendAnswer),
+    }
+}
+
 struct MessageControllerImpl {
     service: Box<dyn MessageService>,
+    config: lib::config::SharedConfig,
+    auto_delete: Arc<dyn AutoDelete>,
 }
 
 #[async_trait]
 impl MessageController for MessageControllerImpl {
     async fn check_link_in_message(
-        &self, cx: &lib::types::MessageContext,
+        &self, cx: &lib::types::MessageContext, admins: &[i64], action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError> {
         let chat_id = cx.update.chat_id();
         let sender = cx.update.from().unwrap();
         let text = cx.update.text().unwrap();
 
-        if let Ok(link) = lib::helpers::check_link_in_text(text) {
+        if let Ok(link) = lib::helpers::check_link_in_text(text, &self.config.load().words) {
             log::info!("Found the prohibited link: {}!", link);
 
+            if action == ModerationAction::Report {
+                report_to_admins(cx, &*self.auto_delete, admins, "Замечена подозрительная ссылка! \
+                   Администрация проинформирована и разберётся в ситуации за кратчайшие сроки.")
+                    .await
+                    .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["report", "link"]).inc();
+                return Ok(());
+            }
+
             let result = cx.requester
                 .delete_message(chat_id, cx.update.id)
                 .await
-                .map_err(|err| err.into())
                 .map_err(lib::errors::MessageControllerError::CheckLinkInMessage);
             if let Err(error) = result {
                 log::warn!(
@@ -51,29 +106,44 @@ impl MessageController for MessageControllerImpl {
                     sender.id,
                     error,
                 );
-                let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(cx)
-                    .await
-                    .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
                 let msg_text = "Замечена подозрительная ссылка! \
                    Администрация проинформирована и разберётся в ситуации за кратчайшие сроки."
                     .to_owned()
                     + &admin_mentions.join("");
-                lib::tg_helpers::reply_to(cx, msg_text)
+                lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Moderation, msg_text)
                     .await
                     .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
                 return Err(error);
             }
 
+            if action == ModerationAction::Delete {
+                lib::tg_helpers::send_message(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Moderation,
+                    format!(
+                        "Сообщение пользователя {} было удалено за запрещённую ссылку.",
+                        teloxide::utils::html::user_mention_or_link(sender),
+                    ),
+                )
+                    .await
+                    .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["delete", "link"]).inc();
+                return Ok(());
+            }
+
             let result = cx.requester
                 .kick_chat_member(chat_id, sender.id)
                 .await
-                .map_err(|err| err.into())
                 .map_err(lib::errors::MessageControllerError::CheckLinkInMessage);
 
             match result {
                 Ok(_) => {
                     lib::tg_helpers::send_message(
                         cx,
+                        &*self.auto_delete,
+                        ReplyCategory::Moderation,
                         format!(
                             "Пользователь {} был забанен за запрещённую ссылку в сообщении.",
                             teloxide::utils::html::user_mention_or_link(sender),
@@ -82,6 +152,7 @@ impl MessageController for MessageControllerImpl {
                         .await
                         .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
                     log::info!("Ban user {} from chat {}.", sender.full_name(), chat_id);
+                    lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["ban", "link"]).inc();
                 }
                 Err(error) => {
                     log::warn!(
@@ -90,16 +161,14 @@ impl MessageController for MessageControllerImpl {
                         sender.id,
                         error,
                     );
-                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(cx)
-                        .await
-                        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
                     let msg_text = format!(
                         "Замечен подозрительная ссылка у пользователя {}!\
                         Администрация проинформирована и разберётся в ситуации за кратчайшие сроки.",
                         teloxide::utils::html::user_mention_or_link(sender))
                         .to_owned()
                         + &admin_mentions.join("");
-                    lib::tg_helpers::send_message(cx, msg_text)
+                    lib::tg_helpers::send_message(cx, &*self.auto_delete, ReplyCategory::Moderation, msg_text)
                         .await
                         .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
                 }
@@ -111,10 +180,15 @@ impl MessageController for MessageControllerImpl {
     async fn check_new_member(
         &self,
         cx: &lib::types::MessageContext,
+        admins: &[i64],
         new_member: &teloxide::types::User,
+        action: ModerationAction,
     ) -> Result<(), lib::errors::MessageControllerError> {
         let chat = &cx.update.chat;
         log::info!("new member \"{}\" in chat \"{}\"", new_member.full_name(), chat.title().unwrap());
+        if !self.config.load().cas.enabled {
+            return Ok(());
+        }
 
         let resp = self.service
             .get_cas_status(new_member.id)
@@ -122,23 +196,31 @@ impl MessageController for MessageControllerImpl {
             .map_err(|err| err.into())
             .map_err(lib::errors::MessageControllerError::CheckNewMember)?;
 
-        if resp.ok {
+        if resp.ok && action == ModerationAction::Report {
+            report_to_admins(cx, &*self.auto_delete, admins, "Замечен подозрительный пользователь в соответствии с CAS! \
+                   Администрация проинформирована и разберётся в ситуации за кратчайшие сроки.")
+                .await
+                .map_err(lib::errors::MessageControllerError::CheckNewMember)?;
+            lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["report", "cas"]).inc();
+        } else if resp.ok {
             let result = cx.requester
                 .kick_chat_member(chat.id, new_member.id)
                 .await
                 .map(|_| {
                     log::info!("User {} (id: {}) is banned due to CAS.", new_member.full_name(), new_member.id);
+                    lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["ban", "cas"]).inc();
                 })
-                .map_err(|err| err.into())
                 .map_err(lib::errors::MessageControllerError::CheckNewMember);
 
             match result {
                 Ok(_) => {
-                    let result = cx.requester
-                        .delete_message(chat.id, cx.update.id)
-                        .await
-                        .map_err(|err| err.into())
-                        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage);
+                    if let Err(error) = cx.requester.delete_message(chat.id, cx.update.id).await {
+                        log::warn!(
+                            "Oops, error occurred deleting the join message of the user (id: {}) : {:#?}",
+                            new_member.id,
+                            error,
+                        );
+                    }
                 },
                 Err(error) => {
                     log::warn!(
@@ -147,14 +229,12 @@ impl MessageController for MessageControllerImpl {
                         new_member.id,
                         error,
                     );
-                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(cx)
-                        .await
-                        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
                     let msg_text = "Замечен подозрительный пользователь в соответствии с CAS! \
                    Администрация проинформирована и разберётся в ситуации за кратчайшие сроки."
                         .to_owned()
                         + &admin_mentions.join("");
-                    lib::tg_helpers::reply_to(cx, msg_text)
+                    lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Moderation, msg_text)
                         .await
                         .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
                     return Err(error);
@@ -164,22 +244,40 @@ impl MessageController for MessageControllerImpl {
         Ok(())
     }
 
-    async fn check_author(&self, cx: &MessageContext) -> Result<(), MessageControllerError> {
+    async fn check_author(&self, cx: &MessageContext, admins: &[i64], action: ModerationAction)
+        -> Result<(), MessageControllerError> {
         let chat_id = cx.update.chat_id();
         let user = cx.update.from().unwrap();
-        if lib::helpers::check_is_full_name_clean(user.full_name()) {
+        let is_flagged = lib::helpers::check_is_full_name_clean(user.full_name(), &self.config.load().words);
+        if is_flagged && action == ModerationAction::Report {
+            // The name stays the same on every message, so the admins hear about it once.
+            let is_first_report = self.service.start_report(chat_id, user.id, "name").await.unwrap_or_else(|err| {
+                log::error!("{:?}", err);
+                true
+            });
+            if !is_first_report {
+                return Ok(());
+            }
+            report_to_admins(cx, &*self.auto_delete, admins, "Замечен пользователь с подозрительным именем пользователя! \
+                   Администрация проинформирована и разберётся в ситуации за кратчайшие сроки.")
+                .await
+                .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+            lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["report", "name"]).inc();
+        } else if is_flagged {
             let result = cx.requester
                 .kick_chat_member(chat_id, user.id)
                 .await
                 .map(|_| {
                     log::info!("User {} (id: {}) is banned due to inappropriate full name.", user.full_name(), user.id);
+                    lib::metrics::MODERATION_ACTIONS_TOTAL.with_label_values(&["ban", "name"]).inc();
                 })
-                .map_err(|err| err.into())
                 .map_err(lib::errors::MessageControllerError::CheckLinkInMessage);
             match result {
                 Ok(_) => {
                     lib::tg_helpers::reply_to(
                         cx,
+                        &*self.auto_delete,
+                        ReplyCategory::Moderation,
                         "Пользователь был забанен за запрещённое имя пользователя.".to_string(),
                     )
                         .await
@@ -192,14 +290,12 @@ impl MessageController for MessageControllerImpl {
                         user.id,
                         error,
                     );
-                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(cx)
-                        .await
-                        .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
+                    let admin_mentions = lib::tg_helpers::get_chat_administrator_mentions(admins);
                     let msg_text = "Замечен пользователь с подозрительным именем пользователя! \
                    Администрация проинформирована и разберётся в ситуации за кратчайшие сроки."
                         .to_owned()
                         + &admin_mentions.join("");
-                    lib::tg_helpers::reply_to(cx, msg_text)
+                    lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Moderation, msg_text)
                         .await
                         .map_err(lib::errors::MessageControllerError::CheckLinkInMessage)?;
                     return Err(error);
@@ -209,10 +305,12 @@ impl MessageController for MessageControllerImpl {
         Ok(())
     }
 
-    async fn check_politics_in_text(&self, cx: &MessageContext) -> Result<(), MessageControllerError> {
+    async fn check_politics_in_text(
+        &self, cx: &MessageContext, action: ModerationAction,
+    ) -> Result<(), MessageControllerError> {
         let text = cx.update.text().unwrap();
 
-        if lib::helpers::check_is_politics_in_text(text.to_string()) {
+        if lib::helpers::check_is_politics_in_text(text.to_string(), &self.config.load().words) {
             let msg_text = "Просимо не згадувати політичні теми та не ображати людей за політичною ознакою, щоб уникнути подальшого конфлікту та агресії.
 
 Якщо це повідомлення у відповідь на образу, агресію або пропаганду – використовуйте команду !report. Якщо можливо, видаліть або відредагуйте повідомлення.
@@ -229,17 +327,17 @@ impl MessageController for MessageControllerImpl {
 
 Благодарим за понимание.
 ";
-            lib::tg_helpers::reply_to(cx, msg_text.to_string())
-                .await
-                .map_err(lib::errors::MessageControllerError::SendAnswer)?;
+            lecture(cx, &*self.auto_delete, action, "politics", msg_text).await?;
         }
         Ok(())
     }
 
-    async fn check_insult_in_text(&self, cx: &MessageContext) -> Result<(), MessageControllerError> {
+    async fn check_insult_in_text(
+        &self, cx: &MessageContext, action: ModerationAction,
+    ) -> Result<(), MessageControllerError> {
         let text = cx.update.text().unwrap();
 
-        if lib::helpers::check_is_insult_in_text(text.to_string()) {
+        if lib::helpers::check_is_insult_in_text(text.to_string(), &self.config.load().words) {
             let msg_text = "Просимо виявляти повагу до кожного учасника будь-ким і не використовувати образи, щоб уникнути подальшого конфлікту та агресії.
 Якщо це повідомлення у відповідь на образу, агресію або пропаганду – використовуйте команду !report та адміністрація розбереться із ситуацією. Якщо можливо, видаліть або відредагуйте повідомлення.
 Інакше, ви підвищуєте можливість отримати тимчасове обмеження написання повідомлень, у деяких випадках – отримання бана.
@@ -254,14 +352,143 @@ impl MessageController for MessageControllerImpl {
 
 Благодарим за понимание.
 ";
-            lib::tg_helpers::reply_to(cx, msg_text.to_string())
-                .await
-                .map_err(lib::errors::MessageControllerError::SendAnswer)?;
+            lecture(cx, &*self.auto_delete, action, "insult", msg_text).await?;
         }
         Ok(())
     }
 }
 
-pub fn new_message_controller(service: Box<dyn MessageService>) -> Box<dyn MessageController> {
-    Box::new(MessageControllerImpl { service })
+pub fn new_message_controller(
+    service: Box<dyn MessageService>,
+    config: lib::config::SharedConfig,
+    auto_delete: Arc<dyn AutoDelete>,
+) -> Box<dyn MessageController> {
+    Box::new(MessageControllerImpl { service, config, auto_delete })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::MemoryStore;
+    use crate::domains::message::{repositories, service::new_message_service};
+    use crate::lib::{bot_actions::{BotCall, RecordingBotActions}, test_helpers};
+
+    const ADMINS: &[i64] = &[1];
+
+    async fn controller() -> Box<dyn MessageController> {
+        let store = Arc::new(MemoryStore::default());
+        let config = test_helpers::config();
+        let cache_repo = repositories::new_memory_message_cache_repository(store.clone());
+        let service = new_message_service(cache_repo, config.clone());
+        new_message_controller(service, config, test_helpers::auto_delete(&store).await)
+    }
+
+    #[tokio::test]
+    async fn prohibited_link_deletes_message_and_bans_sender() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let spammer = test_helpers::user(2, "Spammer");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &spammer, "see https://free-cash.io"));
+
+        controller().await.check_link_in_message(&cx, ADMINS, ModerationAction::Ban).await.unwrap();
+
+        let calls = bot.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0], BotCall::DeleteMessage { chat_id: test_helpers::CHAT_ID, message_id: 10 });
+        assert_eq!(calls[1], BotCall::KickChatMember { chat_id: test_helpers::CHAT_ID, user_id: 2 });
+        assert!(matches!(
+            &calls[2],
+            BotCall::SendMessage { text, reply_to_message_id: None, .. } if text.contains("запрещённую ссылку")
+        ));
+    }
+
+    #[tokio::test]
+    async fn prohibited_link_calls_admins_when_delete_fails() {
+        let bot = Arc::new(RecordingBotActions::new().with_failing("deleteMessage"));
+        let spammer = test_helpers::user(2, "Spammer");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &spammer, "see https://free-cash.io"));
+
+        let result = controller()
+            .await
+            .check_link_in_message(&cx, ADMINS, ModerationAction::Ban)
+            .await;
+
+        assert!(result.is_err());
+        let calls = bot.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(matches!(
+            &calls[1],
+            BotCall::SendMessage { text, reply_to_message_id: Some(10), .. } if text.contains("tg://user?id=1")
+        ));
+    }
+
+    #[tokio::test]
+    async fn regular_link_is_ignored() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let member = test_helpers::user(2, "Member");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &member, "docs: https://docs.rs/teloxide"));
+
+        controller().await.check_link_in_message(&cx, ADMINS, ModerationAction::Ban).await.unwrap();
+
+        assert!(bot.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn prohibited_full_name_bans_author() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let spammer = test_helpers::user(2, "Best dating");
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &spammer, "hello"));
+
+        controller().await.check_author(&cx, ADMINS, ModerationAction::Ban).await.unwrap();
+
+        let calls = bot.calls();
+        assert_eq!(calls[0], BotCall::KickChatMember { chat_id: test_helpers::CHAT_ID, user_id: 2 });
+        assert!(matches!(
+            &calls[1],
+            BotCall::SendMessage { text, reply_to_message_id: Some(10), .. } if text.contains("запрещённое имя")
+        ));
+    }
+
+    #[tokio::test]
+    async fn prohibited_full_name_is_reported_once() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let spammer = test_helpers::user(2, "Best dating");
+        let controller = controller().await;
+
+        for message_id in [10, 11, 12] {
+            let cx = test_helpers::message_context(&bot, test_helpers::message(message_id, &spammer, "hello"));
+            controller.check_author(&cx, ADMINS, ModerationAction::Report).await.unwrap();
+        }
+
+        match bot.calls().as_slice() {
+            [BotCall::SendMessage { text, reply_to_message_id: Some(10), .. }] => {
+                assert!(text.contains("подозрительным именем"));
+                assert!(text.contains("tg://user?id=1"));
+            }
+            calls => panic!("Unexpected calls: {:?}", calls),
+        }
+    }
+
+    #[tokio::test]
+    async fn prohibited_link_is_only_deleted_or_reported_when_configured() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let spammer = test_helpers::user(2, "Spammer");
+        let controller = controller().await;
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(10, &spammer, "see https://free-cash.io"));
+        controller.check_link_in_message(&cx, ADMINS, ModerationAction::Delete).await.unwrap();
+        let calls = bot.take_calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], BotCall::DeleteMessage { chat_id: test_helpers::CHAT_ID, message_id: 10 });
+        assert!(matches!(&calls[1], BotCall::SendMessage { text, .. } if text.contains("было удалено")));
+
+        let cx = test_helpers::message_context(&bot, test_helpers::message(11, &spammer, "see https://free-cash.io"));
+        controller.check_link_in_message(&cx, ADMINS, ModerationAction::Report).await.unwrap();
+        let calls = bot.take_calls();
+        let removing = |call: &BotCall| matches!(call, BotCall::DeleteMessage { .. } | BotCall::KickChatMember { .. });
+        assert!(!calls.iter().any(removing));
+        assert!(matches!(
+            calls.last(),
+            Some(BotCall::SendMessage { text, reply_to_message_id: Some(11), .. }) if text.contains("tg://user?id=1")
+        ));
+    }
 }
@@ -1,11 +1,22 @@
-use crate::domains::message::{controller, service};
+use crate::{core::persistence::Storage, lib::{auto_delete::AutoDelete, config::SharedConfig}};
+use crate::domains::message::{controller, repositories, service};
+use std::sync::Arc;
 
 pub struct MessageDomain {
     pub controller: Box<dyn controller::MessageController>,
 }
 
-pub async fn new_message_domain() -> MessageDomain {
-    let service = service::new_message_service();
-    let controller = controller::new_message_controller(service);
+pub async fn new_message_domain(
+    storage: &Storage,
+    config: SharedConfig,
+    auto_delete: Arc<dyn AutoDelete>,
+) -> MessageDomain {
+    let cache_repo = match storage {
+        Storage::Postgres { redis_client, .. } => repositories::new_message_cache_repository(redis_client.clone()),
+        Storage::Sqlite { cache, .. } => repositories::new_memory_message_cache_repository(cache.clone()),
+        Storage::Memory(store) => repositories::new_memory_message_cache_repository(store.clone()),
+    };
+    let service = service::new_message_service(cache_repo, config.clone());
+    let controller = controller::new_message_controller(service, config, auto_delete);
     MessageDomain { controller }
 }
@@ -1,5 +1,6 @@
 mod controller;
 mod domain_builder;
 mod model;
+mod repositories;
 mod service;
 pub use domain_builder::{new_message_domain, MessageDomain};
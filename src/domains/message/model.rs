@@ -1,5 +1,4 @@
 #[derive(serde::Deserialize)]
 pub struct CASResponse {
     pub ok: bool,
-    description: String,
 }
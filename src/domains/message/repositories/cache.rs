@@ -0,0 +1,69 @@
+use crate::core::persistence::MemoryStore;
+use redis::Client;
+use crate::lib::{self, errors::CacheError};
+use async_trait::async_trait;
+use std::sync::Arc;
+use anyhow::Context;
+
+#[async_trait]
+pub trait MessageCacheRepository: Send + Sync {
+    /// `false` when the member was already reported to the admins for the check in the last `seconds`.
+    async fn start_report(&self, chat_id: i64, telegram_id: i64, check: &str, seconds: u64)
+        -> Result<bool, CacheError>;
+}
+
+fn report_key(chat_id: i64, telegram_id: i64, check: &str) -> String {
+    format!("moderation-report-{}-{}-{}", chat_id, telegram_id, check)
+}
+
+struct RedisMessageCacheRepositoryImpl {
+    client: Arc<Client>,
+}
+
+#[async_trait]
+impl MessageCacheRepository for RedisMessageCacheRepositoryImpl {
+    async fn start_report(&self, chat_id: i64, telegram_id: i64, check: &str, seconds: u64)
+        -> Result<bool, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["set_nx_ex"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        redis::cmd("SET")
+            .arg(report_key(chat_id, telegram_id, check))
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async::<_, Option<String>>(&mut conn)
+            .await
+            .map(|reply| reply.is_some())
+            .context("Unable save document using Redis")
+            .map_err(CacheError::Execute)
+    }
+}
+
+pub fn new_message_cache_repository(client: Arc<Client>) -> Box<dyn MessageCacheRepository> {
+    Box::new(RedisMessageCacheRepositoryImpl { client })
+}
+
+struct MemoryMessageCacheRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+#[async_trait]
+impl MessageCacheRepository for MemoryMessageCacheRepositoryImpl {
+    async fn start_report(&self, chat_id: i64, telegram_id: i64, check: &str, seconds: u64)
+        -> Result<bool, CacheError> {
+        Ok(self.store.set_nx_ex(
+            report_key(chat_id, telegram_id, check),
+            "1".to_string(),
+            std::time::Duration::from_secs(seconds),
+        ))
+    }
+}
+
+pub fn new_memory_message_cache_repository(store: Arc<MemoryStore>) -> Box<dyn MessageCacheRepository> {
+    Box::new(MemoryMessageCacheRepositoryImpl { store })
+}
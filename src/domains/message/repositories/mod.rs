@@ -0,0 +1,2 @@
+mod cache;
+pub use cache::{MessageCacheRepository, new_message_cache_repository, new_memory_message_cache_repository};
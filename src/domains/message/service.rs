@@ -1,18 +1,29 @@
 use anyhow::Context;
-use crate::{lib, domains::message::model};
+use crate::{lib, domains::message::{model, repositories::MessageCacheRepository}};
 use async_trait::async_trait;
 
 #[async_trait]
 pub trait MessageService: Send + Sync {
     async fn get_cas_status(&self, user_id: i64) -> Result<model::CASResponse, lib::errors::MessageError>;
+    /// `false` when the member was already reported for the check within `cache.reports_ttl_seconds`.
+    async fn start_report(&self, chat_id: i64, user_id: i64, check: &str) -> Result<bool, lib::errors::MessageError>;
 }
 
-struct MessageServiceImpl;
+struct MessageServiceImpl {
+    cache: Box<dyn MessageCacheRepository>,
+    config: lib::config::SharedConfig,
+}
 
 #[async_trait]
 impl MessageService for MessageServiceImpl {
     async fn get_cas_status(&self, user_id: i64) -> Result<model::CASResponse, lib::errors::MessageError> {
-        let result = reqwest::get(format!("https://api.cas.chat/check?user_id={}", user_id))
+        let _timer = lib::metrics::CAS_LOOKUP_SECONDS.start_timer();
+        let cas_config = self.config.load().cas.clone();
+        let result = reqwest::Client::new()
+            .get(&cas_config.api_url)
+            .query(&[("user_id", user_id)])
+            .timeout(std::time::Duration::from_secs(cas_config.timeout_seconds))
+            .send()
             .await
             .context(format!("Cannot send request to check CAS for user (id: {})", user_id))
             .map_err(lib::errors::MessageError::GetCASStatus)?; // TODO: web repo
@@ -24,8 +35,17 @@ impl MessageService for MessageServiceImpl {
             .map_err(lib::errors::MessageError::GetCASStatus)?;
         Ok(cas_response)
     }
+
+    async fn start_report(&self, chat_id: i64, user_id: i64, check: &str) -> Result<bool, lib::errors::MessageError> {
+        self.cache
+            .start_report(chat_id, user_id, check, self.config.load().cache.reports_ttl_seconds)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::MessageError::StartReport)
+    }
 }
 
-pub fn new_message_service() -> Box<dyn MessageService> {
-    Box::new(MessageServiceImpl {})
+pub fn new_message_service(cache: Box<dyn MessageCacheRepository>, config: lib::config::SharedConfig)
+    -> Box<dyn MessageService> {
+    Box::new(MessageServiceImpl { cache, config })
 }
@@ -1,4 +1,5 @@
 pub mod admin_commands;
 pub mod chat;
+pub mod job;
 pub mod message;
 pub mod user;
@@ -1,37 +1,121 @@
 use crate::{
-    domains::user::{model, service::UserService},
-    lib, Request, Requester,
+    domains::{chat, user::{model, service::UserService}},
+    lib::{self, auto_delete::AutoDelete, enums::ReplyCategory},
 };
 use async_trait::async_trait;
-use teloxide::prelude::*;
+use itertools::Itertools;
+use std::collections::{hash_map::Entry, HashMap};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::Arc;
 
 #[async_trait]
 pub trait UserController: Send + Sync {
-    async fn create_if_not_exists(&self, user: &teloxide::types::User, chat_id: i64, is_admin: bool)
+    async fn create_if_not_exists(
+        &self,
+        user: &teloxide::types::User,
+        chat_id: i64,
+        is_admin: bool,
+        chat_settings: &chat::ChatSettings,
+    ) -> Result<(), lib::errors::UserError>;
+    async fn get_global_rating(&self, cx: &lib::types::MessageContext, username: Option<&str>)
         -> Result<(), lib::errors::UserError>;
-    async fn fetch_users_by_rating(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::UserError>;
+    async fn fetch_users_by_rating(
+        &self,
+        cx: &lib::types::MessageContext,
+        category_name: Option<&str>,
+    ) -> Result<(), lib::errors::UserError>;
     async fn get_info(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::UserError>;
+    async fn get_rating(&self, chat_id: i64, telegram_id: i64)
+        -> Result<sqlx::types::BigDecimal, lib::errors::UserError>;
     async fn create_rating_record(
         &self,
         cx: &lib::types::MessageContext,
         rating_trigger: lib::enums::RatingTriggers,
+        category: Option<model::RatingCategory>,
+        chat_settings: &chat::ChatSettings,
     ) -> Result<(), lib::errors::UserError>;
+    async fn count_message(&self, user: &teloxide::types::User, chat_id: i64) -> Result<(), lib::errors::UserError>;
+    async fn change_rating_optout(&self, cx: &lib::types::MessageContext, is_rating_optout: bool)
+        -> Result<(), lib::errors::UserError>;
+    /// Marks the chat member as left or rejoined.
+    async fn change_membership(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::UserError>;
+    /// Cancels a rating change, by its initiator or by a member who may adjust ratings.
     async fn delete_rating_record_by_user_request(
         &self,
         cx: &lib::types::CallbackContext,
         user_id: i64,
         record_id: &str,
+        can_adjust_rating: bool,
+    ) -> Result<(), lib::errors::UserError>;
+    async fn get_rating_categories(&self, chat_id: i64)
+        -> Result<Vec<model::RatingCategory>, lib::errors::UserError>;
+    async fn list_rating_categories(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::UserError>;
+    async fn save_rating_category(
+        &self,
+        cx: &lib::types::MessageContext,
+        name: &str,
+        triggers: &[&str],
     ) -> Result<(), lib::errors::UserError>;
+    async fn delete_rating_category(&self, cx: &lib::types::MessageContext, name: &str)
+        -> Result<(), lib::errors::UserError>;
+    async fn export_ratings(&self, cx: &lib::types::MessageContext, format: Option<&str>, period: Option<&str>)
+        -> Result<(), lib::errors::UserError>;
+    async fn export_users(&self, cx: &lib::types::MessageContext, format: Option<&str>)
+        -> Result<(), lib::errors::UserError>;
+    async fn import_ratings(
+        &self,
+        cx: &lib::types::MessageContext,
+        chat_settings: &chat::ChatSettings,
+        commit: bool,
+    ) -> Result<(), lib::errors::UserError>;
+    async fn replay_history(&self, chat_id: i64, messages: Vec<model::HistoryMessage>)
+        -> Result<model::HistoryReplaySummary, lib::errors::UserError>;
+}
+
+fn parse_file_format(format: Option<&str>) -> Result<lib::enums::FileFormat, String> {
+    match format {
+        Some(format) => lib::enums::FileFormat::from_str(format)
+            .map_err(|_| "Неизвестный формат экспорта. Доступные форматы: <code>csv</code>, <code>json</code>".to_string()),
+        None => Ok(lib::enums::FileFormat::Csv),
+    }
+}
+
+async fn send_export(
+    cx: &lib::types::MessageContext,
+    auto_delete: &dyn AutoDelete,
+    file_name: String,
+    data: Vec<u8>,
+) -> Result<(), lib::errors::UserError> {
+    let admin = cx.update.from().unwrap();
+    let msg_text = match lib::tg_helpers::send_document(cx, admin.id, file_name, data).await {
+        Ok(_) => "Экспорт отправлен в личные сообщения".to_string(),
+        Err(err) => {
+            log::warn!("Unable to send export to user (id: {}): {:?}", admin.id, err);
+            "Невозможно отправить экспорт в личные сообщения. \
+            Пожалуйста, начните диалог с ботом и повторите попытку.".to_string()
+        }
+    };
+    lib::tg_helpers::reply_to(cx, auto_delete, ReplyCategory::Info, msg_text)
+        .await
+        .map_err(lib::errors::UserError::Export)
 }
 
 struct UserControllerImpl {
     service: Box<dyn UserService>,
+    config: lib::config::SharedConfig,
+    auto_delete: Arc<dyn AutoDelete>,
 }
 
 #[async_trait]
 impl UserController for UserControllerImpl {
-    async fn create_if_not_exists(&self, user: &teloxide::types::User, chat_id: i64, is_admin: bool)
-        -> Result<(), lib::errors::UserError> {
+    async fn create_if_not_exists(
+        &self,
+        user: &teloxide::types::User,
+        chat_id: i64,
+        is_admin: bool,
+        chat_settings: &chat::ChatSettings,
+    ) -> Result<(), lib::errors::UserError> {
         let created = self
             .service
             .create_if_not_exists(model::UserRequest {
@@ -42,17 +126,32 @@ impl UserController for UserControllerImpl {
             }, chat_id)
             .await?;
         if created {
-            let base_rating = sqlx::types::BigDecimal::from(
-                if is_admin { lib::config::BASE_RATING * lib::config::BASE_RATING_ADMIN_MULTIPLIER }
-                else {lib::config::BASE_RATING}
+            let rating_config = self.config.load().rating.clone();
+            let mut base_rating = sqlx::types::BigDecimal::from(
+                if is_admin { rating_config.base * rating_config.admin_multiplier }
+                else {rating_config.base}
             );
+            let mut comment = "Default create record.".to_string();
+            if chat_settings.is_global_reputation_start() && !is_admin {
+                let global_rating = self.service.get_global_rating(user.id).await?;
+                if let Some(amount) = global_rating.amount {
+                    let max_rating = sqlx::types::BigDecimal::from(
+                        rating_config.base * rating_config.admin_multiplier
+                    );
+                    base_rating = amount
+                        .max(sqlx::types::BigDecimal::from(0))
+                        .min(max_rating);
+                    comment = "Default create record based on global reputation.".to_string();
+                }
+            }
             return self.service
                 .create_rating_record(model::RatingRequest {
                     chat_id,
                     user_tg_id: user.id,
                     by_user_tg_id: Option::None,
                     amount: base_rating,
-                    comment: Option::from("Default create record.".to_string()),
+                    comment: Option::from(comment),
+                    rating_category_id: None,
                 }, chat_id)
                 .await
                 .map(|_| ());
@@ -60,24 +159,111 @@ impl UserController for UserControllerImpl {
         Ok(())
     }
 
-    async fn fetch_users_by_rating(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::UserError> {
+    async fn get_global_rating(&self, cx: &lib::types::MessageContext, username: Option<&str>)
+        -> Result<(), lib::errors::UserError> {
+        let user = match username {
+            Some(username) => {
+                let username = username.trim_start_matches('@');
+                match self.service.get_user_by_username(username).await? {
+                    Some(user) => (user.telegram_id, user.full_name()),
+                    None => {
+                        return lib::tg_helpers::reply_to(
+                            cx,
+                            &*self.auto_delete,
+                            ReplyCategory::Error,
+                            format!("Пользователь @{} не найден", username),
+                        )
+                            .await
+                            .map_err(lib::errors::UserError::GetInfo);
+                    }
+                }
+            }
+            None => match cx.update.reply_to_message() {
+                Some(msg) => {
+                    let user = lib::tg_helpers::get_user_to_interact(
+                        msg.from().unwrap().clone(),
+                        msg.sender_chat()
+                    );
+                    (user.id, user.full_name())
+                }
+                None => {
+                    return lib::tg_helpers::reply_to(
+                        cx,
+                        &*self.auto_delete,
+                        ReplyCategory::Error,
+                        "Используйте эту команду в ответ на сообщение или укажите @username!".to_string(),
+                    )
+                        .await
+                        .map_err(lib::errors::UserError::GetInfo);
+                }
+            },
+        };
+        let (telegram_id, full_name) = user;
+
+        let global_rating = self.service.get_global_rating(telegram_id).await?;
+        let text = match global_rating.amount {
+            Some(amount) => format!(
+                "Пользователь: <b>{}</b>\nГлобальная репутация: <b>{:.2}</b> (чатов: {})",
+                full_name,
+                amount,
+                global_rating.chats_count,
+            ),
+            None => format!("Пользователь: <b>{}</b>\nГлобальная репутация отсутствует", full_name),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
+            .await
+            .map_err(lib::errors::UserError::GetInfo)
+    }
+
+    async fn fetch_users_by_rating(
+        &self,
+        cx: &lib::types::MessageContext,
+        category_name: Option<&str>,
+    ) -> Result<(), lib::errors::UserError> {
+        let chat_id = cx.update.chat_id();
+        let category = match category_name {
+            Some(name) => {
+                let categories = self.service.get_rating_categories(chat_id).await?;
+                match categories.into_iter().find(|category| category.name == name) {
+                    Some(category) => Some(category),
+                    None => {
+                        return lib::tg_helpers::reply_to(
+                            cx,
+                            &*self.auto_delete,
+                            ReplyCategory::Error,
+                            format!("Категория рейтинга <b>{}</b> не найдена", name),
+                        )
+                            .await
+                            .map_err(lib::errors::UserError::FetchRatingTop);
+                    }
+                }
+            }
+            None => None,
+        };
         let users = self
             .service
             .fetch_top_by_rating(model::TopUsersRequest {
-                chat_id: cx.update.chat_id(),
+                chat_id,
                 limit: 15,
+                rating_category_id: category.as_ref().map(|category| category.rating_category_id),
             })
             .await?;
-        let mut text = "Топ самых одобряемых пользователей данного чата:".to_string();
+        let mut text = match &category {
+            Some(category) => format!(
+                "Топ самых одобряемых пользователей данного чата в категории <b>{}</b>:",
+                category.name
+            ),
+            None => "Топ самых одобряемых пользователей данного чата:".to_string(),
+        };
         for (index, user) in users.iter().enumerate() {
-            text.push_str(&*format!(
+            text.push_str(&format!(
                 "\n{}. <b>{}</b> <b>{:.2}</b>",
                 index + 1,
                 user.full_name,
                 user.rating_amount
             ));
         }
-        lib::tg_helpers::reply_to(cx, text)
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
             .await
             .map_err(lib::errors::UserError::FetchRatingTop)
     }
@@ -97,24 +283,62 @@ impl UserController for UserControllerImpl {
             .map_err(|err| err.into())
             .map_err(lib::errors::UserError::GetInfo)?;
 
-        let text = format!(
+        let rating_by_category = self
+            .service
+            .get_rating_by_category(model::UserRatingRequest {
+                user_tg_id: user.id,
+                chat_id: cx.update.chat_id(),
+            })
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetInfo)?;
+
+        let chat_user = self
+            .service
+            .get_chat_user(model::UserRatingRequest {
+                user_tg_id: user.id,
+                chat_id: cx.update.chat_id(),
+            })
+            .await?;
+
+        let mut text = format!(
             "Пользователь: <b>{}</b>\nРейтинг: <b>{:.2}</b>",
             user.full_name(),
             rating,
         );
-        lib::tg_helpers::reply_to(cx, text)
+        for category in rating_by_category.iter() {
+            text.push_str(&format!("\n  {}: <b>{:.2}</b>", category.name, category.amount));
+        }
+        if chat_user.is_rating_optout {
+            text.push_str("\nВы отказались от участия в рейтинге, поэтому он скрыт от других участников");
+        }
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
             .await
             .map_err(lib::errors::UserError::GetInfo)
     }
 
+    async fn get_rating(&self, chat_id: i64, telegram_id: i64)
+        -> Result<sqlx::types::BigDecimal, lib::errors::UserError> {
+        self.service
+            .get_rating(model::UserRatingRequest { user_tg_id: telegram_id, chat_id })
+            .await
+    }
+
     async fn create_rating_record(
         &self,
         cx: &lib::types::MessageContext,
         rating_trigger: lib::enums::RatingTriggers,
+        category: Option<model::RatingCategory>,
+        chat_settings: &chat::ChatSettings,
     ) -> Result<(), lib::errors::UserError> {
         let chat_id = cx.update.chat_id();
         if cx.update.sender_chat().is_some() {
-            return lib::tg_helpers::reply_to(cx, "Вы не можете изменять рейтинг в качестве канала".to_string())
+            return lib::tg_helpers::reply_to(
+                cx,
+                &*self.auto_delete,
+                ReplyCategory::Error,
+                "Вы не можете изменять рейтинг в качестве канала".to_string(),
+            )
                 .await
                 .map_err(lib::errors::UserError::InsertRating);
         }
@@ -125,7 +349,32 @@ impl UserController for UserControllerImpl {
         );
 
         if user_initiated.id == user_to_apply.id {
-            return lib::tg_helpers::reply_to(cx, "Вы не можете изменять рейтинг самому себе".to_string())
+            return lib::tg_helpers::reply_to(
+                cx,
+                &*self.auto_delete,
+                ReplyCategory::Error,
+                "Вы не можете изменять рейтинг самому себе".to_string(),
+            )
+                .await
+                .map_err(lib::errors::UserError::InsertRating);
+        }
+
+        let user_to_apply_chat_user = self
+            .service
+            .get_chat_user(model::UserRatingRequest {
+                chat_id,
+                user_tg_id: user_to_apply.id,
+            })
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::InsertRating)?;
+        if user_to_apply_chat_user.is_rating_optout {
+            let text = format!(
+                "Пользователь <b>{}</b> отказался от участия в рейтинге, поэтому его рейтинг нельзя изменить. \
+                Спасибо за понимание!",
+                user_to_apply.full_name(),
+            );
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, text)
                 .await
                 .map_err(lib::errors::UserError::InsertRating);
         }
@@ -138,16 +387,46 @@ impl UserController for UserControllerImpl {
             })
             .await;
 
-        if let Err(_) = user_initiated_rating_result {
-            return lib::tg_helpers::reply_to(cx, "Невозможно изменить рейтинг".to_string())
+        if user_initiated_rating_result.is_err() {
+            let msg_text = "Невозможно изменить рейтинг".to_string();
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, msg_text)
+                .await
+                .map_err(lib::errors::UserError::InsertRating);
+        }
+
+        let user_initiated_rating = user_initiated_rating_result.unwrap();
+
+        let is_restricted = cx
+            .requester
+            .is_chat_member_restricted(chat_id, user_initiated.id)
+            .await
+            .map_err(lib::errors::UserError::InsertRating)?;
+        let eligibility_result = self
+            .service
+            .check_voter_eligibility(model::VoterEligibilityRequest {
+                user_tg_id: user_initiated.id,
+                chat_id,
+                rating: user_initiated_rating.clone(),
+                is_restricted,
+                min_age: chat_settings.voter_min_age(),
+                min_messages: chat_settings.voter_min_messages(),
+                min_rating: chat_settings.voter_min_rating(),
+            })
+            .await;
+        if let Err(err) = eligibility_result {
+            let text = match err {
+                lib::errors::UserError::VoterNotEligible(reason) => reason,
+                _ => "Невозможно изменить рейтинг".to_string()
+            };
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, text)
                 .await
                 .map_err(lib::errors::UserError::InsertRating);
         }
 
         let rating_to_apply_result = rating_trigger
-            .valid_amount(user_initiated_rating_result.unwrap());
+            .valid_amount(user_initiated_rating);
         if let Err(err) = rating_to_apply_result {
-            return lib::tg_helpers::reply_to(cx, err)
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, err)
                 .await
                 .map_err(lib::errors::UserError::InsertRating);
         }
@@ -161,6 +440,7 @@ impl UserController for UserControllerImpl {
                 by_user_tg_id: Option::from(user_initiated.id),
                 amount: rating_to_apply.clone(),
                 comment: Option::from("".to_string()),
+                rating_category_id: category.as_ref().map(|category| category.rating_category_id),
             }, chat_id)
             .await;
         if let Err(ref err) = rating_record_result {
@@ -168,7 +448,7 @@ impl UserController for UserControllerImpl {
                 lib::errors::UserError::RepeatingRequestDuringCooldown(msg) => msg.clone(),
                 _ => "Невозможно изменить рейтинг".to_string()
             };
-            return lib::tg_helpers::reply_to(cx, text)
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, text)
                 .await
                 .map_err(lib::errors::UserError::InsertRating);
         }
@@ -183,7 +463,7 @@ impl UserController for UserControllerImpl {
             .map_err(|err| err.into())
             .map_err(lib::errors::UserError::InsertRating)?;
 
-        let text = format!(
+        let mut text = format!(
             "Пользователь <b>{}</b> изменил рейтинг <b>{}</b> до <b>{:.2}</b> ({}{:.2})",
             user_initiated.full_name(),
             user_to_apply.full_name(),
@@ -191,6 +471,9 @@ impl UserController for UserControllerImpl {
             rating_trigger.get_sign(),
             rating_to_apply.abs(),
         );
+        if let Some(category) = &category {
+            text.push_str(&format!("\nКатегория: <b>{}</b>", category.name));
+        }
 
         let keyboard = teloxide::types::InlineKeyboardMarkup::new(vec![vec![
             teloxide::types::InlineKeyboardButton::callback(
@@ -198,30 +481,67 @@ impl UserController for UserControllerImpl {
                 format!("{} {}", user_initiated.id, rating_record_result.unwrap()),
             ),
         ]]);
-        let msg = cx
-            .reply_to(text)
-            .reply_markup(keyboard)
-            .send()
+        lib::tg_helpers::send_reply(
+            cx,
+            &*self.auto_delete,
+            ReplyCategory::Rating,
+            text,
+            Some(cx.update.id),
+            Some(keyboard),
+        )
             .await
-            .map_err(|err| err.into())
-            .map_err(lib::errors::UserError::InsertRating)?;
+            .map(|_| ())
+            .map_err(lib::errors::UserError::InsertRating)
+    }
 
-        let task = cx
-            .requester
-            .delete_message(msg.chat.id, msg.id);
-        let second_task = cx.delete_message();
+    async fn count_message(&self, user: &teloxide::types::User, chat_id: i64) -> Result<(), lib::errors::UserError> {
+        self.service
+            .count_message(model::UserRatingRequest {
+                user_tg_id: user.id,
+                chat_id,
+            })
+            .await
+    }
 
-        tokio::task::spawn(async {
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-            task
-                .await
-                .map_err(|err| log::warn!("Delay deleting message errored: {:?}", err));
-            second_task
-                .await
-                .map_err(|err| log::warn!("Delay deleting message errored: {:?}", err));
-        });
+    async fn change_rating_optout(&self, cx: &lib::types::MessageContext, is_rating_optout: bool)
+        -> Result<(), lib::errors::UserError> {
+        let user = lib::tg_helpers::get_user_to_interact(
+            cx.update.from().unwrap().clone(),
+            cx.update.sender_chat()
+        );
+        let result = self
+            .service
+            .change_rating_optout(model::RatingOptoutRequest {
+                user_tg_id: user.id,
+                chat_id: cx.update.chat_id(),
+                is_rating_optout,
+            })
+            .await;
+        let msg_text = match (result, is_rating_optout) {
+            (Ok(_), true) => "Вы отказались от участия в рейтинге: ваш рейтинг нельзя изменить, \
+                и вы скрыты из топа. Вернуться можно командой <code>!rating_optin</code>".to_string(),
+            (Ok(_), false) => "Вы снова участвуете в рейтинге".to_string(),
+            (Err(_), _) => "Невозможно изменить участие в рейтинге".to_string(),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, msg_text)
+            .await
+            .map_err(lib::errors::UserError::ChangeRatingOptout)
+    }
 
-        Ok(())
+    async fn change_membership(&self, cx: &lib::types::ChatMemberContext) -> Result<(), lib::errors::UserError> {
+        let update = &cx.update;
+        let is_member = update.new_chat_member.kind.is_present();
+        if update.old_chat_member.kind.is_present() == is_member {
+            return Ok(());
+        }
+        self.service
+            .change_membership(model::MembershipRequest {
+                user_tg_id: update.new_chat_member.user.id,
+                chat_id: update.chat.id,
+                is_member,
+                at: chrono::NaiveDateTime::from_timestamp(update.date, 0),
+            })
+            .await
     }
 
     async fn delete_rating_record_by_user_request(
@@ -229,42 +549,627 @@ impl UserController for UserControllerImpl {
         cx: &lib::types::CallbackContext,
         user_id: i64,
         record_id: &str,
+        can_adjust_rating: bool,
     )  -> Result<(), lib::errors::UserError> {
-        if cx.update.from.id == user_id {
+        if cx.update.from.id == user_id || can_adjust_rating {
             self.service
                 .delete_rating_record(record_id)
                 .await?;
             cx.requester
-                .answer_callback_query(&cx.update.id)
-                .text("Изменение рейтинга отменено")
-                .show_alert(true)
-                .send()
+                .answer_callback_query(&cx.update.id, "Изменение рейтинга отменено".to_string(), true)
                 .await
-                .map(|_| ())
-                .map_err(|err| err.into())
                 .map_err(lib::errors::UserError::DeleteRating)?;
             let message = cx.update.message.as_ref().unwrap();
             cx.requester
                 .delete_message(message.chat_id(), message.id)
-                .send()
                 .await
-                .map(|_| ())
-                .map_err(|err| err.into())
                 .map_err(lib::errors::UserError::DeleteRating)
         } else {
             cx.requester
-                .answer_callback_query(&cx.update.id)
-                .text("Это действие может совершить только инициатор данного действия")
-                .show_alert(true)
-                .send()
+                .answer_callback_query(
+                    &cx.update.id,
+                    "Это действие может совершить только инициатор данного действия или модератор".to_string(),
+                    true,
+                )
                 .await
-                .map(|_| ())
-                .map_err(|err| err.into())
                 .map_err(lib::errors::UserError::DeleteRating)
         }
     }
+
+    async fn get_rating_categories(&self, chat_id: i64)
+        -> Result<Vec<model::RatingCategory>, lib::errors::UserError> {
+        self.service.get_rating_categories(chat_id).await
+    }
+
+    async fn list_rating_categories(&self, cx: &lib::types::MessageContext) -> Result<(), lib::errors::UserError> {
+        let categories = self.service.get_rating_categories(cx.update.chat_id()).await?;
+        let text = if categories.is_empty() {
+            "В данном чате нет категорий рейтинга".to_string()
+        } else {
+            let mut text = "Категории рейтинга данного чата:".to_string();
+            for category in categories.iter() {
+                text.push_str(&format!("\n<b>{}</b>: {}", category.name, category.triggers.join(" ")));
+            }
+            text
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
+            .await
+            .map_err(lib::errors::UserError::GetRatingCategories)
+    }
+
+    async fn save_rating_category(
+        &self,
+        cx: &lib::types::MessageContext,
+        name: &str,
+        triggers: &[&str],
+    ) -> Result<(), lib::errors::UserError> {
+        let result = self
+            .service
+            .save_rating_category(model::RatingCategoryRequest {
+                chat_id: cx.update.chat_id(),
+                name: name.to_string(),
+                triggers: triggers.iter().map(|trigger| trigger.to_lowercase()).collect(),
+            })
+            .await;
+        let msg_text = match result {
+            Ok(_) => format!("Категория рейтинга <b>{}</b> сохранена", name),
+            Err(_) => "Невозможно сохранить категорию рейтинга".to_string(),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::UserError::SaveRatingCategory)
+    }
+
+    async fn delete_rating_category(&self, cx: &lib::types::MessageContext, name: &str)
+        -> Result<(), lib::errors::UserError> {
+        let result = self
+            .service
+            .delete_rating_category(cx.update.chat_id(), name)
+            .await;
+        let msg_text = match result {
+            Ok(true) => format!("Категория рейтинга <b>{}</b> удалена", name),
+            Ok(false) => format!("Категория рейтинга <b>{}</b> не найдена", name),
+            Err(_) => "Невозможно удалить категорию рейтинга".to_string(),
+        };
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Settings, msg_text)
+            .await
+            .map_err(lib::errors::UserError::DeleteRatingCategory)
+    }
+
+    async fn export_ratings(&self, cx: &lib::types::MessageContext, format: Option<&str>, period: Option<&str>)
+        -> Result<(), lib::errors::UserError> {
+        let chat_id = cx.update.chat_id();
+        let format = match parse_file_format(format) {
+            Ok(format) => format,
+            Err(msg_text) => {
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, msg_text)
+                    .await
+                    .map_err(lib::errors::UserError::Export);
+            }
+        };
+        let since = match period.map(lib::enums::TimeUnits::from_str) {
+            Some(Ok(period)) => chrono::Utc::now().naive_utc() - period.to_duration(),
+            Some(Err(_)) => {
+                return lib::tg_helpers::reply_to(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Error,
+                    "Некорректный период, пример, <code>30d</code>".to_string(),
+                )
+                    .await
+                    .map_err(lib::errors::UserError::Export);
+            }
+            None => chrono::NaiveDateTime::from_timestamp(0, 0),
+        };
+
+        let data = self
+            .service
+            .export_ratings(model::RatingExportRequest { chat_id, since }, format)
+            .await?;
+        let file_name = format!(
+            "ratings_{}_{}.{}",
+            chat_id,
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            format.extension(),
+        );
+        send_export(cx, &*self.auto_delete, file_name, data).await
+    }
+
+    async fn export_users(&self, cx: &lib::types::MessageContext, format: Option<&str>)
+        -> Result<(), lib::errors::UserError> {
+        let chat_id = cx.update.chat_id();
+        let format = match parse_file_format(format) {
+            Ok(format) => format,
+            Err(msg_text) => {
+                return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Error, msg_text)
+                    .await
+                    .map_err(lib::errors::UserError::Export);
+            }
+        };
+
+        let data = self.service.export_users(chat_id, format).await?;
+        let file_name = format!(
+            "users_{}_{}.{}",
+            chat_id,
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            format.extension(),
+        );
+        send_export(cx, &*self.auto_delete, file_name, data).await
+    }
+
+    async fn import_ratings(
+        &self,
+        cx: &lib::types::MessageContext,
+        chat_settings: &chat::ChatSettings,
+        commit: bool,
+    ) -> Result<(), lib::errors::UserError> {
+        let chat_id = cx.update.chat_id();
+        let document = match cx.update.reply_to_message().and_then(|msg| msg.document()) {
+            Some(document) => document,
+            None => {
+                return lib::tg_helpers::reply_to(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Error,
+                    "Используйте эту команду в ответ на сообщение с файлом (CSV или JSON)!".to_string(),
+                )
+                    .await
+                    .map_err(lib::errors::UserError::Import);
+            }
+        };
+
+        let data = lib::tg_helpers::download_file(cx, &document.file_id)
+            .await
+            .map_err(lib::errors::UserError::Import)?;
+        let format = lib::enums::FileFormat::detect(document.file_name.as_deref(), &data);
+        let parsed_records = match format.deserialize::<model::RatingImportRecord>(&data) {
+            Ok(parsed_records) => parsed_records,
+            Err(err) => {
+                return lib::tg_helpers::reply_to(
+                    cx,
+                    &*self.auto_delete,
+                    ReplyCategory::Error,
+                    format!("Невозможно прочитать файл: {}", err),
+                )
+                    .await
+                    .map_err(lib::errors::UserError::Import);
+            }
+        };
+
+        let mut records = vec![];
+        let mut invalid_rows = vec![];
+        for (index, record) in parsed_records.into_iter().enumerate() {
+            match record {
+                Ok(record) if record.telegram_id != 0 && !record.name.trim().is_empty() && record.amount.is_finite() => {
+                    records.push(record);
+                }
+                _ => invalid_rows.push(index + 1),
+            }
+        }
+        let users_count = records.iter().map(|record| record.telegram_id).unique().count();
+
+        if !commit {
+            let mut text = format!(
+                "<b>Пробный импорт рейтинга:</b>\nЗаписей: <b>{}</b>\nПользователей: <b>{}</b>\nСумма рейтинга: <b>{:.2}</b>",
+                records.len(),
+                users_count,
+                records.iter().map(|record| record.amount).sum::<f64>(),
+            );
+            if !invalid_rows.is_empty() {
+                text.push_str(&format!(
+                    "\nНекорректных записей: <b>{}</b> (строки: {})",
+                    invalid_rows.len(),
+                    invalid_rows.iter().take(10).join(", "),
+                ));
+            }
+            text.push_str("\n\nДля записи используйте <code>!import commit</code> в ответ на файл");
+            return lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
+                .await
+                .map_err(lib::errors::UserError::Import);
+        }
+
+        for record in records.iter().unique_by(|record| record.telegram_id) {
+            let user = teloxide::types::User {
+                id: record.telegram_id,
+                is_bot: false,
+                first_name: record.name.trim().to_string(),
+                last_name: None,
+                username: None,
+                language_code: None,
+            };
+            self.create_if_not_exists(&user, chat_id, false, chat_settings).await?;
+        }
+
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        let mut duplicates_count = 0;
+        for record in records.iter() {
+            let amount = sqlx::types::BigDecimal::try_from(math::round::half_up(record.amount, 2))
+                .map_err(anyhow::Error::new)
+                .map_err(lib::errors::UserError::Import)?
+                .with_scale(2);
+            // identical rows in one file are different records, so the key counts them
+            let record_key = format!(
+                "import:{}:{}:{}:{}",
+                record.telegram_id,
+                record.by.unwrap_or_default(),
+                record.timestamp.unwrap_or_default(),
+                amount,
+            );
+            let occurrence = occurrences.entry(record_key.clone()).or_insert(0);
+            *occurrence += 1;
+            let imported = self.service
+                .import_rating_record(model::RatingImportRequest {
+                    user_tg_id: record.telegram_id,
+                    by_user_tg_id: record.by,
+                    chat_id,
+                    comment: Option::from("Imported.".to_string()),
+                    amount,
+                    created_at: record
+                        .timestamp
+                        .and_then(|timestamp| chrono::NaiveDateTime::from_timestamp_opt(timestamp, 0)),
+                    rating_category_id: None,
+                    source_id: Some(format!("{}:{}", record_key, occurrence)),
+                })
+                .await?;
+            if imported.is_none() {
+                duplicates_count += 1;
+            }
+        }
+
+        let text = format!(
+            "Импорт рейтинга завершён. Записей: <b>{}</b>, пользователей: <b>{}</b>, пропущено: <b>{}</b>, \
+            уже импортировано ранее: <b>{}</b>",
+            records.len() - duplicates_count,
+            users_count,
+            invalid_rows.len(),
+            duplicates_count,
+        );
+        lib::tg_helpers::reply_to(cx, &*self.auto_delete, ReplyCategory::Info, text)
+            .await
+            .map_err(lib::errors::UserError::Import)
+    }
+
+    async fn replay_history(&self, chat_id: i64, mut messages: Vec<model::HistoryMessage>)
+        -> Result<model::HistoryReplaySummary, lib::errors::UserError> {
+        messages.sort_by_key(|message| (message.date, message.id));
+        let categories = self.service.get_rating_categories(chat_id).await?;
+        let rating_config = self.config.load().rating.clone();
+        let cooldown = chrono::Duration::seconds(rating_config.cooldown_seconds as i64);
+
+        let mut summary = model::HistoryReplaySummary::default();
+        let mut authors: HashMap<i64, i64> = HashMap::new();
+        let mut first_seen: HashMap<i64, chrono::NaiveDateTime> = HashMap::new();
+        let mut messages_count: HashMap<i64, i64> = HashMap::new();
+        let mut last_actions: HashMap<(i64, i64), chrono::NaiveDateTime> = HashMap::new();
+
+        for message in messages.iter() {
+            summary.messages += 1;
+            authors.insert(message.id, message.user_tg_id);
+            *messages_count.entry(message.user_tg_id).or_insert(0) += 1;
+
+            if let Entry::Vacant(entry) = first_seen.entry(message.user_tg_id) {
+                entry.insert(message.date);
+                let created = self
+                    .service
+                    .create_if_not_exists(model::UserRequest {
+                        telegram_id: message.user_tg_id,
+                        username: None,
+                        first_name: message.full_name.clone(),
+                        last_name: None,
+                    }, chat_id)
+                    .await?;
+                if created {
+                    summary.created_users += 1;
+                    self.service
+                        .import_rating_record(model::RatingImportRequest {
+                            user_tg_id: message.user_tg_id,
+                            by_user_tg_id: None,
+                            chat_id,
+                            comment: Option::from("Default create record.".to_string()),
+                            amount: sqlx::types::BigDecimal::from(rating_config.base),
+                            created_at: Some(message.date),
+                            rating_category_id: None,
+                            source_id: None,
+                        })
+                        .await?;
+                }
+            }
+
+            let user_to_apply_id = match message.reply_to_message_id.and_then(|id| authors.get(&id)) {
+                Some(user_id) if *user_id != message.user_tg_id => *user_id,
+                _ => continue,
+            };
+            let (rating_trigger, category) = match lib::enums::RatingTriggers::from_str_with_categories(
+                message.text.trim(),
+                &categories,
+            ) {
+                Ok(parsed_trigger) => parsed_trigger,
+                Err(_) => continue,
+            };
+
+            let action_key = (user_to_apply_id, message.user_tg_id);
+            if let Some(last_action) = last_actions.get(&action_key) {
+                if message.date - *last_action < cooldown {
+                    summary.skipped_by_cooldown += 1;
+                    continue;
+                }
+            }
+
+            let user_to_apply = self
+                .service
+                .get_chat_user(model::UserRatingRequest {
+                    user_tg_id: user_to_apply_id,
+                    chat_id,
+                })
+                .await?;
+            if user_to_apply.is_rating_optout {
+                summary.skipped_by_optout += 1;
+                continue;
+            }
+
+            let user_initiated_rating = self
+                .service
+                .get_rating_at(model::UserRatingRequest {
+                    user_tg_id: message.user_tg_id,
+                    chat_id,
+                }, message.date)
+                .await?;
+            let amount = match rating_trigger.valid_amount(user_initiated_rating) {
+                Ok(amount) => amount,
+                Err(_) => {
+                    summary.skipped_by_power += 1;
+                    continue;
+                }
+            };
+
+            let imported = self.service
+                .import_rating_record(model::RatingImportRequest {
+                    user_tg_id: user_to_apply_id,
+                    by_user_tg_id: Some(message.user_tg_id),
+                    chat_id,
+                    comment: Option::from("Imported from chat history.".to_string()),
+                    amount,
+                    created_at: Some(message.date),
+                    rating_category_id: category.map(|category| category.rating_category_id),
+                    source_id: Some(format!("history:{}", message.id)),
+                })
+                .await?;
+            last_actions.insert(action_key, message.date);
+            match imported {
+                Some(_) => summary.ratings += 1,
+                None => summary.skipped_as_imported += 1,
+            }
+        }
+
+        for (user_tg_id, count) in messages_count.into_iter() {
+            self.service
+                .backfill_chat_user_stats(model::ChatUserBackfillRequest {
+                    user_tg_id,
+                    chat_id,
+                    messages_count: count,
+                    first_seen_at: first_seen[&user_tg_id],
+                })
+                .await?;
+        }
+        Ok(summary)
+    }
 }
 
-pub fn new_user_controller(service: Box<dyn UserService>) -> Box<dyn UserController> {
-    Box::new(UserControllerImpl { service })
+pub fn new_user_controller(
+    service: Box<dyn UserService>,
+    config: lib::config::SharedConfig,
+    auto_delete: Arc<dyn AutoDelete>,
+) -> Box<dyn UserController> {
+    Box::new(UserControllerImpl { service, config, auto_delete })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::MemoryStore;
+    use crate::domains::user::{repositories, service};
+    use crate::lib::{bot_actions::{BotCall, RecordingBotActions}, test_helpers};
+
+    fn chat_settings() -> chat::ChatSettings {
+        chat::ChatSettings {
+            chat_id: test_helpers::CHAT_ID,
+            values: chat::registry::SettingValues::default(),
+            commands: Default::default(),
+        }
+    }
+
+    async fn memory_controller(store: &Arc<MemoryStore>) -> Box<dyn UserController> {
+        let config = test_helpers::config();
+        new_user_controller(
+            service::new_user_service(
+                repositories::new_memory_user_db_repository(store.clone()),
+                repositories::new_memory_user_cache_repository(store.clone()),
+                config.clone(),
+            ),
+            config,
+            test_helpers::auto_delete(store).await,
+        )
+    }
+
+    /// Members start with a rating of 100, so a plain vote is worth 10.
+    async fn join(store: &Arc<MemoryStore>, users: &[&teloxide::types::User]) {
+        for user in users {
+            memory_controller(store)
+                .await
+                .create_if_not_exists(user, test_helpers::CHAT_ID, false, &chat_settings())
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn vote(
+        bot: &Arc<RecordingBotActions>,
+        store: &Arc<MemoryStore>,
+        voter: &teloxide::types::User,
+        target: &teloxide::types::User,
+    ) -> Result<(), lib::errors::UserError> {
+        let cx = test_helpers::message_context(bot, test_helpers::reply(10, voter, "+", target));
+        memory_controller(store)
+            .await
+            .create_rating_record(&cx, lib::enums::RatingTriggers::Increase(None), None, &chat_settings())
+            .await
+    }
+
+    fn votes(store: &MemoryStore) -> Vec<sqlx::types::BigDecimal> {
+        store.tables().ratings
+            .iter()
+            .filter(|rating| rating.by_chat_user_id.is_some())
+            .map(|rating| rating.amount.clone())
+            .collect()
+    }
+
+    fn reply_texts(bot: &RecordingBotActions) -> Vec<String> {
+        bot.calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                BotCall::SendMessage { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rating_vote_is_recorded_with_cancel_button() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let store = Arc::new(MemoryStore::default());
+        let (voter, helper) = (test_helpers::user(1, "Voter"), test_helpers::user(2, "Helper"));
+        join(&store, &[&voter, &helper]).await;
+
+        vote(&bot, &store, &voter, &helper).await.unwrap();
+
+        assert_eq!(votes(&store), vec![sqlx::types::BigDecimal::from(10)]);
+        match bot.calls().as_slice() {
+            [BotCall::SendMessage { text, reply_to_message_id: Some(10), reply_markup: Some(_), .. }] => {
+                assert!(text.contains("изменил рейтинг <b>Helper</b>"));
+            }
+            calls => panic!("Unexpected calls: {:?}", calls),
+        }
+    }
+
+    #[tokio::test]
+    async fn rating_vote_schedules_deleting_both_messages() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let store = Arc::new(MemoryStore::default());
+        let (voter, helper) = (test_helpers::user(1, "Voter"), test_helpers::user(2, "Helper"));
+        join(&store, &[&voter, &helper]).await;
+
+        vote(&bot, &store, &voter, &helper).await.unwrap();
+
+        let tables = store.tables();
+        let payloads = tables.jobs.iter().map(|job| (job.kind.as_str(), job.payload.clone())).collect::<Vec<_>>();
+        assert_eq!(payloads, vec![
+            ("delete_message", serde_json::json!({ "chat_id": test_helpers::CHAT_ID, "message_id": 1 })),
+            ("delete_message", serde_json::json!({ "chat_id": test_helpers::CHAT_ID, "message_id": 10 })),
+        ]);
+        assert!(tables.jobs.iter().all(|job| job.run_at > chrono::Utc::now().naive_utc()));
+    }
+
+    #[tokio::test]
+    async fn rating_vote_for_yourself_is_rejected() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let store = Arc::new(MemoryStore::default());
+        let voter = test_helpers::user(1, "Voter");
+        join(&store, &[&voter]).await;
+
+        vote(&bot, &store, &voter, &voter).await.unwrap();
+
+        assert_eq!(reply_texts(&bot), vec!["Вы не можете изменять рейтинг самому себе".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn rating_vote_for_opted_out_member_is_refused() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let store = Arc::new(MemoryStore::default());
+        let (voter, private) = (test_helpers::user(1, "Voter"), test_helpers::user(2, "Private"));
+        join(&store, &[&voter, &private]).await;
+        store.tables().chat_user_mut(private.id, test_helpers::CHAT_ID).unwrap().is_rating_optout = true;
+
+        vote(&bot, &store, &voter, &private).await.unwrap();
+
+        assert!(votes(&store).is_empty());
+        assert!(reply_texts(&bot)[0].contains("отказался от участия в рейтинге"));
+    }
+
+    #[tokio::test]
+    async fn import_skips_invalid_rows_and_records_imported_earlier() {
+        let csv = "telegram_id,name,amount,timestamp,by\n3,Alice,12.5,1600000000,\n4,Bob,-2,,3\n0,,1,,\n";
+        let bot = Arc::new(RecordingBotActions::new().with_file("exported", csv.as_bytes().to_vec()));
+        let store = Arc::new(MemoryStore::default());
+        let admin = test_helpers::user(1, "Admin");
+        let chat = serde_json::json!({ "id": test_helpers::CHAT_ID, "type": "supergroup", "title": "Test chat" });
+        let message = serde_json::json!({
+            "message_id": 11,
+            "date": test_helpers::MESSAGE_DATE,
+            "chat": chat,
+            "from": admin,
+            "text": "!import commit",
+            "reply_to_message": {
+                "message_id": 10,
+                "date": test_helpers::MESSAGE_DATE,
+                "chat": chat,
+                "from": admin,
+                "document": {
+                    "file_id": "exported",
+                    "file_unique_id": "exported",
+                    "file_name": "ratings.csv",
+                    "mime_type": "text/csv",
+                },
+            },
+        });
+        let cx = test_helpers::message_context(&bot, serde_json::from_value(message).unwrap());
+
+        for _ in 0..2 {
+            memory_controller(&store).await.import_ratings(&cx, &chat_settings(), true).await.unwrap();
+        }
+
+        let imported = store.tables().ratings
+            .iter()
+            .filter(|rating| rating.comment.as_deref() == Some("Imported."))
+            .map(|rating| rating.amount.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(imported, vec!["12.50".to_string(), "-2.00".to_string()]);
+        let texts = reply_texts(&bot);
+        assert!(texts[0].contains("Записей: <b>2</b>, пользователей: <b>2</b>, пропущено: <b>1</b>"));
+        assert!(texts[1].contains("уже импортировано ранее: <b>2</b>"));
+    }
+
+    #[tokio::test]
+    async fn opted_out_member_still_sees_own_rating() {
+        let bot = Arc::new(RecordingBotActions::new());
+        let store = Arc::new(MemoryStore::default());
+        let (voter, private) = (test_helpers::user(1, "Voter"), test_helpers::user(2, "Private"));
+        join(&store, &[&voter, &private]).await;
+        vote(&bot, &store, &voter, &private).await.unwrap();
+        store.tables().chat_user_mut(private.id, test_helpers::CHAT_ID).unwrap().is_rating_optout = true;
+        bot.take_calls();
+
+        let controller = memory_controller(&store).await;
+        let rating = controller.get_rating(test_helpers::CHAT_ID, private.id).await.unwrap();
+        let cx = test_helpers::message_context(&bot, test_helpers::message(20, &private, "!me"));
+        controller.get_info(&cx).await.unwrap();
+
+        assert_eq!(rating, sqlx::types::BigDecimal::from(110));
+        assert!(reply_texts(&bot)[0].contains("Рейтинг: <b>110.00</b>"));
+    }
+
+    #[tokio::test]
+    async fn rating_vote_from_restricted_member_is_refused() {
+        let bot = Arc::new(RecordingBotActions::new().with_restricted(1));
+        let store = Arc::new(MemoryStore::default());
+        let (muted, helper) = (test_helpers::user(1, "Muted"), test_helpers::user(2, "Helper"));
+        join(&store, &[&muted, &helper]).await;
+
+        vote(&bot, &store, &muted, &helper).await.unwrap();
+
+        assert!(votes(&store).is_empty());
+        assert_eq!(
+            reply_texts(&bot),
+            vec!["Пользователи с ограниченными правами не могут изменять чужой рейтинг".to_string()],
+        );
+    }
 }
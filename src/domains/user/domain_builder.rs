@@ -1,15 +1,27 @@
+use crate::{core::persistence::Storage, lib::{auto_delete::AutoDelete, config::SharedConfig}};
 use crate::domains::user::{controller, repositories, service};
 use std::sync::Arc;
-use redis;
 
 pub struct UserDomain {
     pub controller: Box<dyn controller::UserController>,
 }
 
-pub async fn new_user_domain(pool: Arc<sqlx::PgPool>, redis_client: Arc<redis::Client>) -> UserDomain {
-    let db_repo = repositories::new_user_db_repository(pool);
-    let cache_repo = repositories::new_user_cache_repository(redis_client);
-    let service = service::new_user_service(db_repo, cache_repo);
-    let controller = controller::new_user_controller(service);
+pub async fn new_user_domain(storage: &Storage, config: SharedConfig, auto_delete: Arc<dyn AutoDelete>) -> UserDomain {
+    let (db_repo, cache_repo) = match storage {
+        Storage::Postgres { pool, redis_client } => (
+            repositories::new_user_db_repository(pool.clone()),
+            repositories::new_user_cache_repository(redis_client.clone()),
+        ),
+        Storage::Sqlite { pool, cache } => (
+            repositories::new_sqlite_user_db_repository(pool.clone()),
+            repositories::new_memory_user_cache_repository(cache.clone()),
+        ),
+        Storage::Memory(store) => (
+            repositories::new_memory_user_db_repository(store.clone()),
+            repositories::new_memory_user_cache_repository(store.clone()),
+        ),
+    };
+    let service = service::new_user_service(db_repo, cache_repo, config.clone());
+    let controller = controller::new_user_controller(service, config, auto_delete);
     UserDomain { controller }
 }
@@ -3,4 +3,4 @@ mod domain_builder;
 mod model;
 mod repositories;
 mod service;
-pub use domain_builder::{new_user_domain, UserDomain};
+pub use {domain_builder::{new_user_domain, UserDomain}, model::HistoryMessage};
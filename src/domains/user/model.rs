@@ -8,15 +8,63 @@ pub struct UserRequest {
     pub last_name: Option<String>,
 }
 
+#[derive(Debug)]
+pub struct UserResponse {
+    pub telegram_id: i64,
+    pub first_name: String,
+    pub last_name: Option<String>,
+}
+
+impl UserResponse {
+    pub fn full_name(&self) -> String {
+        match &self.last_name {
+            Some(last_name) => format!("{} {}", self.first_name, last_name),
+            None => self.first_name.clone(),
+        }
+    }
+}
+
 pub struct ChatUserRequest {
     pub user_id: sqlx::types::Uuid,
     pub chat_id: i64,
 }
 
+#[derive(Debug)]
+pub struct ChatUser {
+    pub created_at: chrono::NaiveDateTime,
+    pub messages_count: i64,
+    pub is_rating_optout: bool,
+}
+
+pub struct RatingOptoutRequest {
+    pub user_tg_id: i64,
+    pub chat_id: i64,
+    pub is_rating_optout: bool,
+}
+
+/// A chat member left the chat or came back, at `at`.
+pub struct MembershipRequest {
+    pub user_tg_id: i64,
+    pub chat_id: i64,
+    pub is_member: bool,
+    pub at: chrono::NaiveDateTime,
+}
+
+pub struct VoterEligibilityRequest {
+    pub user_tg_id: i64,
+    pub chat_id: i64,
+    pub rating: sqlx::types::BigDecimal,
+    pub is_restricted: bool,
+    pub min_age: i32,
+    pub min_messages: i64,
+    pub min_rating: sqlx::types::BigDecimal,
+}
+
 #[derive(Debug)]
 pub struct TopUsersRequest {
     pub chat_id: i64,
     pub limit: i64,
+    pub rating_category_id: Option<sqlx::types::Uuid>,
 }
 #[derive(Debug)]
 pub struct TopUsersResponse {
@@ -31,6 +79,38 @@ pub struct RatingRequest {
     pub chat_id: i64,
     pub comment: Option<String>,
     pub amount: sqlx::types::BigDecimal,
+    pub rating_category_id: Option<sqlx::types::Uuid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RatingCategory {
+    pub rating_category_id: sqlx::types::Uuid,
+    pub name: String,
+    pub triggers: Vec<String>,
+}
+
+impl crate::lib::enums::RatingCategoryTriggers for RatingCategory {
+    fn triggers(&self) -> &[String] {
+        &self.triggers
+    }
+}
+
+pub struct RatingCategoryRequest {
+    pub chat_id: i64,
+    pub name: String,
+    pub triggers: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct UserRatingByCategoryResponse {
+    pub name: String,
+    pub amount: sqlx::types::BigDecimal,
+}
+
+#[derive(Debug)]
+pub struct GlobalRatingResponse {
+    pub amount: Option<sqlx::types::BigDecimal>,
+    pub chats_count: i64,
 }
 
 pub struct UserRatingRequest {
@@ -49,4 +129,82 @@ impl UserRatingActionRequest {
     pub fn get_key(&self) -> String {
         format!("{}-{}-{}", self.user_id, self.by_user_id, self.chat_id)
     }
-}
\ No newline at end of file
+}
+pub struct RatingExportRequest {
+    pub chat_id: i64,
+    pub since: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RatingExportRecord {
+    pub rating_id: String,
+    pub telegram_id: i64,
+    pub full_name: String,
+    pub by_telegram_id: Option<i64>,
+    pub by_full_name: Option<String>,
+    pub category: Option<String>,
+    pub amount: String,
+    pub comment: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct UserExportRecord {
+    pub telegram_id: i64,
+    pub username: Option<String>,
+    pub full_name: String,
+    pub rating_amount: String,
+    pub messages_count: i64,
+    pub is_rating_optout: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RatingImportRecord {
+    pub telegram_id: i64,
+    pub name: String,
+    pub amount: f64,
+    pub timestamp: Option<i64>,
+    pub by: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct RatingImportRequest {
+    pub user_tg_id: i64,
+    pub by_user_tg_id: Option<i64>,
+    pub chat_id: i64,
+    pub comment: Option<String>,
+    pub amount: sqlx::types::BigDecimal,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub rating_category_id: Option<sqlx::types::Uuid>,
+    /// Set for records that can be imported again, a repeated import skips them.
+    pub source_id: Option<String>,
+}
+
+pub struct ChatUserBackfillRequest {
+    pub user_tg_id: i64,
+    pub chat_id: i64,
+    pub messages_count: i64,
+    pub first_seen_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct HistoryMessage {
+    pub id: i64,
+    pub user_tg_id: i64,
+    pub full_name: String,
+    pub date: chrono::NaiveDateTime,
+    pub reply_to_message_id: Option<i64>,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct HistoryReplaySummary {
+    pub messages: usize,
+    pub created_users: usize,
+    pub ratings: usize,
+    pub skipped_by_cooldown: usize,
+    pub skipped_by_power: usize,
+    pub skipped_by_optout: usize,
+    pub skipped_as_imported: usize,
+}
@@ -1,15 +1,17 @@
+use crate::core::persistence::MemoryStore;
 use crate::domains::user::model::UserRatingActionRequest;
-use crate::lib::config::RATING_COOLDOWN;
 use redis::{Client, AsyncCommands};
-use crate::lib::errors::CacheError;
+use crate::lib::{self, errors::CacheError};
 use async_trait::async_trait;
 use std::sync::Arc;
 use anyhow::Context;
 
 #[async_trait]
 pub trait UserCacheRepository: Send + Sync {
-    async fn save_rating_action(&self, body: UserRatingActionRequest) -> Result<(), CacheError>;
+    /// Starts the rating cooldown unless it's already running, in one step. Returns whether it was started.
+    async fn start_rating_cooldown(&self, body: UserRatingActionRequest, seconds: u64) -> Result<bool, CacheError>;
     async fn get_rating_action_expired_time(&self, body: UserRatingActionRequest) -> Result<i64, CacheError>;
+    async fn delete_rating_action(&self, body: UserRatingActionRequest) -> Result<(), CacheError>;
 }
 
 struct RedisUserCacheRepositoryImpl {
@@ -18,20 +20,28 @@ struct RedisUserCacheRepositoryImpl {
 
 #[async_trait]
 impl UserCacheRepository for RedisUserCacheRepositoryImpl {
-    async fn save_rating_action(&self, body: UserRatingActionRequest) -> Result<(), CacheError> {
+    async fn start_rating_cooldown(&self, body: UserRatingActionRequest, seconds: u64) -> Result<bool, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["set_nx_ex"]).start_timer();
         let mut conn = self.client
             .get_async_connection()
             .await
             .context("Failed to initiate async Redis connection")?;
 
-        conn
-            .set_ex(body.get_key(), true, RATING_COOLDOWN as usize)
+        redis::cmd("SET")
+            .arg(body.get_key())
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async::<_, Option<String>>(&mut conn)
             .await
+            .map(|reply| reply.is_some())
             .context("Unable save document using Redis")
             .map_err(CacheError::Execute)
     }
 
     async fn get_rating_action_expired_time(&self, body: UserRatingActionRequest) -> Result<i64, CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["ttl"]).start_timer();
         let mut conn = self.client
             .get_async_connection()
             .await
@@ -43,8 +53,46 @@ impl UserCacheRepository for RedisUserCacheRepositoryImpl {
             .context("Unable get document TTL using Redis")
             .map_err(CacheError::Execute)
     }
+
+    async fn delete_rating_action(&self, body: UserRatingActionRequest) -> Result<(), CacheError> {
+        let _timer = lib::metrics::REDIS_COMMAND_SECONDS.with_label_values(&["del"]).start_timer();
+        let mut conn = self.client
+            .get_async_connection()
+            .await
+            .context("Failed to initiate async Redis connection")?;
+
+        conn
+            .del(body.get_key())
+            .await
+            .context("Unable delete document using Redis")
+            .map_err(CacheError::Execute)
+    }
 }
 
 pub fn new_user_cache_repository(client: Arc<Client>) -> Box<dyn UserCacheRepository> {
     Box::new(RedisUserCacheRepositoryImpl { client })
 }
+
+struct MemoryUserCacheRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+#[async_trait]
+impl UserCacheRepository for MemoryUserCacheRepositoryImpl {
+    async fn start_rating_cooldown(&self, body: UserRatingActionRequest, seconds: u64) -> Result<bool, CacheError> {
+        Ok(self.store.set_nx_ex(body.get_key(), "1".to_string(), std::time::Duration::from_secs(seconds)))
+    }
+
+    async fn get_rating_action_expired_time(&self, body: UserRatingActionRequest) -> Result<i64, CacheError> {
+        Ok(self.store.ttl(&body.get_key()))
+    }
+
+    async fn delete_rating_action(&self, body: UserRatingActionRequest) -> Result<(), CacheError> {
+        self.store.del(&body.get_key());
+        Ok(())
+    }
+}
+
+pub fn new_memory_user_cache_repository(store: Arc<MemoryStore>) -> Box<dyn UserCacheRepository> {
+    Box::new(MemoryUserCacheRepositoryImpl { store })
+}
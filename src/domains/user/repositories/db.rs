@@ -1,19 +1,42 @@
-use crate::domains::user::model;
+use crate::core::persistence::{ChatUserRow, MemoryStore, MemoryTables, RatingCategoryRow, RatingRow, UserRow};
+use crate::domains::{chat::registry, user::model};
 use async_trait::async_trait;
+use itertools::Itertools;
 use std::sync::Arc;
 use sqlx;
 use anyhow::Context;
-use crate::lib::errors::DBError;
+use crate::lib::{self, errors::DBError};
+
+/// The weight of chats that didn't set `global_reputation_weight`.
+fn default_global_reputation_weight() -> sqlx::types::BigDecimal {
+    registry::SettingValues::default().decimal(&registry::GLOBAL_REPUTATION_WEIGHT)
+}
 
 #[async_trait]
 pub trait UserDBRepository: Send + Sync {
     async fn fetch_user_id(&self, telegram_id: i64) -> Result<sqlx::types::Uuid, DBError>;
+    async fn fetch_user_by_username(&self, username: &str) -> Result<Option<model::UserResponse>, DBError>;
     async fn create(&self, body: &model::UserRequest) -> Result<bool, DBError>;
     async fn create_chat_user(&self, body: model::ChatUserRequest) -> Result<bool, DBError>;
+    async fn fetch_chat_user(&self, body: &model::UserRatingRequest) -> Result<model::ChatUser, DBError>;
+    async fn increment_messages_count(&self, body: &model::UserRatingRequest) -> Result<(), DBError>;
+    async fn backfill_chat_user_stats(&self, body: &model::ChatUserBackfillRequest) -> Result<(), DBError>;
+    async fn change_rating_optout(&self, body: &model::RatingOptoutRequest) -> Result<(), DBError>;
+    /// Sets `left_at` when the member leaves, clears it and sets `rejoined_at` when they come back.
+    async fn change_membership(&self, body: &model::MembershipRequest) -> Result<(), DBError>;
     async fn fetch_rating(
         &self,
         body: model::UserRatingRequest,
     ) -> Result<sqlx::types::BigDecimal, DBError>;
+    /// Sums the ratings created up to `at`, the history replay uses it for the vote power at that moment.
+    /// Ratings without a voter (the start rating, imported balances) count whenever they were created.
+    async fn fetch_rating_at(
+        &self,
+        body: model::UserRatingRequest,
+        at: chrono::NaiveDateTime,
+    ) -> Result<sqlx::types::BigDecimal, DBError>;
+
+    async fn fetch_global_rating(&self, telegram_id: i64) -> Result<model::GlobalRatingResponse, DBError>;
 
     async fn fetch_top_by_rating(
         &self,
@@ -25,7 +48,28 @@ pub trait UserDBRepository: Send + Sync {
         body: &model::RatingRequest,
     ) -> Result<sqlx::types::Uuid, DBError>;
 
+    /// Returns `None` when a rating with the same `source_id` was already imported.
+    async fn import_rating_record(
+        &self,
+        body: &model::RatingImportRequest,
+    ) -> Result<Option<sqlx::types::Uuid>, DBError>;
+
     async fn delete_rating_record(&self, record_id: sqlx::types::Uuid) -> Result<(), DBError>;
+
+    async fn fetch_rating_by_category(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<Vec<model::UserRatingByCategoryResponse>, DBError>;
+
+    async fn fetch_rating_categories(&self, chat_id: i64) -> Result<Vec<model::RatingCategory>, DBError>;
+    async fn create_rating_category(&self, body: &model::RatingCategoryRequest) -> Result<(), DBError>;
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, DBError>;
+
+    async fn fetch_ratings_export(
+        &self,
+        body: &model::RatingExportRequest,
+    ) -> Result<Vec<model::RatingExportRecord>, DBError>;
+    async fn fetch_users_export(&self, chat_id: i64) -> Result<Vec<model::UserExportRecord>, DBError>;
 }
 
 struct PgUserDBRepositoryImpl {
@@ -35,6 +79,7 @@ struct PgUserDBRepositoryImpl {
 #[async_trait]
 impl UserDBRepository for PgUserDBRepositoryImpl {
     async fn fetch_user_id(&self, telegram_id: i64) -> Result<sqlx::types::Uuid, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_user_id"]).start_timer();
         sqlx::query_file_scalar!(
             "src/domains/user/repositories/queries/user/fetch_user_id.sql",
             telegram_id,
@@ -46,7 +91,22 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
             .map_err(DBError::Execute)
     }
 
+    async fn fetch_user_by_username(&self, username: &str) -> Result<Option<model::UserResponse>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_user_by_username"]).start_timer();
+        sqlx::query_file_as!(
+            model::UserResponse,
+            "src/domains/user/repositories/queries/user/fetch_by_username.sql",
+            username,
+        )
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user by username in Postgres")
+            .map_err(DBError::Execute)
+    }
+
     async fn create(&self, body: &model::UserRequest) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.create"]).start_timer();
         sqlx::query_file!(
             "src/domains/user/repositories/queries/user/create.sql",
             body.telegram_id,
@@ -63,6 +123,7 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
     }
 
     async fn create_chat_user(&self, body: model::ChatUserRequest) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.create_chat_user"]).start_timer();
         sqlx::query_file!(
             "src/domains/user/repositories/queries/chat_user/create.sql",
             body.user_id,
@@ -76,10 +137,91 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
             .map_err(DBError::Execute)
     }
 
+    async fn fetch_chat_user(&self, body: &model::UserRatingRequest) -> Result<model::ChatUser, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_chat_user"]).start_timer();
+        sqlx::query_file_as!(
+            model::ChatUser,
+            "src/domains/user/repositories/queries/chat_user/fetch.sql",
+            body.user_tg_id,
+            body.chat_id,
+        )
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat user in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn increment_messages_count(&self, body: &model::UserRatingRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.increment_messages_count"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/chat_user/increment_messages_count.sql",
+            body.user_tg_id,
+            body.chat_id,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to increment chat user messages count in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn backfill_chat_user_stats(&self, body: &model::ChatUserBackfillRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.backfill_chat_user_stats"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/chat_user/backfill_stats.sql",
+            body.user_tg_id,
+            body.chat_id,
+            body.messages_count,
+            body.first_seen_at,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to backfill chat user stats in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn change_rating_optout(&self, body: &model::RatingOptoutRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.change_rating_optout"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/chat_user/change_rating_optout.sql",
+            body.user_tg_id,
+            body.chat_id,
+            body.is_rating_optout,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat user rating opt-out in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn change_membership(&self, body: &model::MembershipRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.change_membership"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/chat_user/change_membership.sql",
+            body.user_tg_id,
+            body.chat_id,
+            body.is_member,
+            body.at,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat user membership in Postgres")
+            .map_err(DBError::Execute)
+    }
+
     async fn fetch_rating(
         &self,
         body: model::UserRatingRequest,
     ) -> Result<sqlx::types::BigDecimal, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_rating"]).start_timer();
         sqlx::query_file_scalar!(
             "src/domains/user/repositories/queries/rating/fetch_user_rating.sql",
             body.user_tg_id,
@@ -92,15 +234,51 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
             .map_err(DBError::Execute)
     }
 
+    async fn fetch_rating_at(
+        &self,
+        body: model::UserRatingRequest,
+        at: chrono::NaiveDateTime,
+    ) -> Result<sqlx::types::BigDecimal, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_rating_at"]).start_timer();
+        sqlx::query_file_scalar!(
+            "src/domains/user/repositories/queries/rating/fetch_user_rating_at.sql",
+            body.user_tg_id,
+            body.chat_id,
+            at,
+        )
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user rating at a moment in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_global_rating(&self, telegram_id: i64) -> Result<model::GlobalRatingResponse, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_global_rating"]).start_timer();
+        sqlx::query_file_as!(
+            model::GlobalRatingResponse,
+            "src/domains/user/repositories/queries/rating/fetch_global_rating.sql",
+            telegram_id,
+            default_global_reputation_weight(),
+        )
+            .fetch_one(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user global rating in Postgres")
+            .map_err(DBError::Execute)
+    }
+
     async fn fetch_top_by_rating(
         &self,
         body: model::TopUsersRequest,
     ) -> Result<Vec<model::TopUsersResponse>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_top_by_rating"]).start_timer();
         sqlx::query_file_as!(
             model::TopUsersResponse,
             "src/domains/user/repositories/queries/user/fetch_top_users_by_rating.sql",
             body.chat_id,
             body.limit,
+            body.rating_category_id,
         )
             .fetch_all(&*self.pool)
             .await
@@ -113,13 +291,15 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
         &self,
         body: &model::RatingRequest,
     ) -> Result<sqlx::types::Uuid, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.create_rating_record"]).start_timer();
         sqlx::query_file_scalar!(
             "src/domains/user/repositories/queries/rating/create.sql",
             body.user_tg_id,
             body.by_user_tg_id,
             body.chat_id,
             body.amount,
-            body.comment
+            body.comment,
+            body.rating_category_id,
         )
             .fetch_one(&*self.pool)
             .await
@@ -128,7 +308,31 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
             .map_err(DBError::Execute)
     }
 
+    async fn import_rating_record(
+        &self,
+        body: &model::RatingImportRequest,
+    ) -> Result<Option<sqlx::types::Uuid>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.import_rating_record"]).start_timer();
+        sqlx::query_file_scalar!(
+            "src/domains/user/repositories/queries/rating/import.sql",
+            body.user_tg_id,
+            body.by_user_tg_id,
+            body.chat_id,
+            body.amount,
+            body.comment,
+            body.created_at,
+            body.rating_category_id,
+            body.source_id,
+        )
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to import rating in Postgres")
+            .map_err(DBError::Execute)
+    }
+
     async fn delete_rating_record(&self, record_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.delete_rating_record"]).start_timer();
         sqlx::query_file!(
             "src/domains/user/repositories/queries/rating/delete_record.sql",
             record_id,
@@ -140,8 +344,994 @@ impl UserDBRepository for PgUserDBRepositoryImpl {
             .context("Failed to delete rating record in Postgres")
             .map_err(DBError::Execute)
     }
+
+    async fn fetch_rating_by_category(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<Vec<model::UserRatingByCategoryResponse>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_rating_by_category"]).start_timer();
+        sqlx::query_file_as!(
+            model::UserRatingByCategoryResponse,
+            "src/domains/user/repositories/queries/rating/fetch_user_rating_by_category.sql",
+            body.user_tg_id,
+            body.chat_id,
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user rating by category in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_rating_categories(&self, chat_id: i64) -> Result<Vec<model::RatingCategory>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_rating_categories"]).start_timer();
+        sqlx::query_file_as!(
+            model::RatingCategory,
+            "src/domains/user/repositories/queries/rating_category/fetch.sql",
+            chat_id,
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch rating categories in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_rating_category(&self, body: &model::RatingCategoryRequest) -> Result<(), DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.create_rating_category"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/rating_category/create.sql",
+            body.chat_id,
+            body.name,
+            &body.triggers,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to create rating category in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.delete_rating_category"]).start_timer();
+        sqlx::query_file!(
+            "src/domains/user/repositories/queries/rating_category/delete.sql",
+            chat_id,
+            name,
+        )
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to delete rating category in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_ratings_export(
+        &self,
+        body: &model::RatingExportRequest,
+    ) -> Result<Vec<model::RatingExportRecord>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_ratings_export"]).start_timer();
+        sqlx::query_file_as!(
+            model::RatingExportRecord,
+            "src/domains/user/repositories/queries/export/ratings.sql",
+            body.chat_id,
+            body.since,
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch ratings export in Postgres")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_users_export(&self, chat_id: i64) -> Result<Vec<model::UserExportRecord>, DBError> {
+        let _timer = lib::metrics::POSTGRES_QUERY_SECONDS.with_label_values(&["user.fetch_users_export"]).start_timer();
+        sqlx::query_file_as!(
+            model::UserExportRecord,
+            "src/domains/user/repositories/queries/export/users.sql",
+            chat_id,
+        )
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch users export in Postgres")
+            .map_err(DBError::Execute)
+    }
 }
 
 pub fn new_user_db_repository(pool: Arc<sqlx::PgPool>) -> Box<dyn UserDBRepository> {
     Box::new(PgUserDBRepositoryImpl { pool })
 }
+
+struct MemoryUserDBRepositoryImpl {
+    store: Arc<MemoryStore>,
+}
+
+const EXPORT_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+fn zero_amount() -> sqlx::types::BigDecimal {
+    sqlx::types::BigDecimal::from(0).with_scale(2)
+}
+
+fn ratings_of(tables: &MemoryTables, chat_user_id: sqlx::types::Uuid) -> impl Iterator<Item = &RatingRow> {
+    tables.ratings.iter().filter(move |rating| rating.chat_user_id == Some(chat_user_id))
+}
+
+fn user_of<'a>(tables: &'a MemoryTables, chat_user: &ChatUserRow) -> &'a UserRow {
+    tables.users
+        .iter()
+        .find(|user| user.user_id == chat_user.user_id)
+        .expect("Chat user without user in memory")
+}
+
+fn chat_user_id_of(tables: &MemoryTables, user_tg_id: Option<i64>, chat_id: i64) -> Option<sqlx::types::Uuid> {
+    user_tg_id
+        .and_then(|user_tg_id| tables.chat_user(user_tg_id, chat_id))
+        .map(|chat_user| chat_user.chat_user_id)
+}
+
+#[async_trait]
+impl UserDBRepository for MemoryUserDBRepositoryImpl {
+    async fn fetch_user_id(&self, telegram_id: i64) -> Result<sqlx::types::Uuid, DBError> {
+        self.store
+            .tables()
+            .users
+            .iter()
+            .find(|user| user.telegram_id == telegram_id)
+            .map(|user| user.user_id)
+            .context(format!("User (telegram_id: {}) not found in memory", telegram_id))
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_user_by_username(&self, username: &str) -> Result<Option<model::UserResponse>, DBError> {
+        Ok(self.store
+            .tables()
+            .users
+            .iter()
+            .find(|user| user.username.as_ref().map(|name| name.to_lowercase()) == Some(username.to_lowercase()))
+            .map(|user| model::UserResponse {
+                telegram_id: user.telegram_id,
+                first_name: user.first_name.clone(),
+                last_name: user.last_name.clone(),
+            }))
+    }
+
+    async fn create(&self, body: &model::UserRequest) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        match tables.users.iter_mut().find(|user| user.telegram_id == body.telegram_id) {
+            Some(user) => {
+                let username = body.username.clone().or_else(|| user.username.clone());
+                let last_name = body.last_name.clone().or_else(|| user.last_name.clone());
+                if user.username == username && user.first_name == body.first_name && user.last_name == last_name {
+                    return Ok(false);
+                }
+                user.username = username;
+                user.first_name = body.first_name.clone();
+                user.last_name = last_name;
+                Ok(true)
+            }
+            None => {
+                tables.users.push(UserRow {
+                    user_id: sqlx::types::Uuid::new_v4(),
+                    telegram_id: body.telegram_id,
+                    username: body.username.clone(),
+                    first_name: body.first_name.clone(),
+                    last_name: body.last_name.clone(),
+                });
+                Ok(true)
+            }
+        }
+    }
+
+    async fn create_chat_user(&self, body: model::ChatUserRequest) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        if tables.chat_users
+            .iter()
+            .any(|chat_user| chat_user.user_id == body.user_id && chat_user.chat_id == body.chat_id) {
+            return Ok(false);
+        }
+        tables.chat_users.push(ChatUserRow {
+            chat_user_id: sqlx::types::Uuid::new_v4(),
+            user_id: body.user_id,
+            chat_id: body.chat_id,
+            created_at: chrono::Utc::now().naive_utc(),
+            messages_count: 0,
+            is_rating_optout: false,
+            left_at: None,
+            rejoined_at: None,
+        });
+        Ok(true)
+    }
+
+    async fn fetch_chat_user(&self, body: &model::UserRatingRequest) -> Result<model::ChatUser, DBError> {
+        self.store
+            .tables()
+            .chat_user(body.user_tg_id, body.chat_id)
+            .map(|chat_user| model::ChatUser {
+                created_at: chat_user.created_at,
+                messages_count: chat_user.messages_count,
+                is_rating_optout: chat_user.is_rating_optout,
+            })
+            .context(format!(
+                "Chat user (telegram_id: {}, chat_id: {}) not found in memory",
+                body.user_tg_id,
+                body.chat_id,
+            ))
+            .map_err(DBError::Execute)
+    }
+
+    async fn increment_messages_count(&self, body: &model::UserRatingRequest) -> Result<(), DBError> {
+        if let Some(chat_user) = self.store.tables().chat_user_mut(body.user_tg_id, body.chat_id) {
+            chat_user.messages_count += 1;
+        }
+        Ok(())
+    }
+
+    async fn backfill_chat_user_stats(&self, body: &model::ChatUserBackfillRequest) -> Result<(), DBError> {
+        if let Some(chat_user) = self.store.tables().chat_user_mut(body.user_tg_id, body.chat_id) {
+            chat_user.messages_count = chat_user.messages_count.max(body.messages_count);
+            chat_user.created_at = chat_user.created_at.min(body.first_seen_at);
+        }
+        Ok(())
+    }
+
+    async fn change_rating_optout(&self, body: &model::RatingOptoutRequest) -> Result<(), DBError> {
+        if let Some(chat_user) = self.store.tables().chat_user_mut(body.user_tg_id, body.chat_id) {
+            chat_user.is_rating_optout = body.is_rating_optout;
+        }
+        Ok(())
+    }
+
+    async fn change_membership(&self, body: &model::MembershipRequest) -> Result<(), DBError> {
+        if let Some(chat_user) = self.store.tables().chat_user_mut(body.user_tg_id, body.chat_id) {
+            match (body.is_member, chat_user.left_at) {
+                (true, Some(_)) => {
+                    chat_user.left_at = None;
+                    chat_user.rejoined_at = Some(body.at);
+                }
+                (false, None) => chat_user.left_at = Some(body.at),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_rating(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<sqlx::types::BigDecimal, DBError> {
+        let tables = self.store.tables();
+        let amount = match tables.chat_user(body.user_tg_id, body.chat_id) {
+            Some(chat_user) => ratings_of(&tables, chat_user.chat_user_id)
+                .fold(zero_amount(), |sum, rating| sum + &rating.amount),
+            None => zero_amount(),
+        };
+        Ok(lib::helpers::round_amount(amount))
+    }
+
+    async fn fetch_rating_at(
+        &self,
+        body: model::UserRatingRequest,
+        at: chrono::NaiveDateTime,
+    ) -> Result<sqlx::types::BigDecimal, DBError> {
+        let tables = self.store.tables();
+        let amount = match tables.chat_user(body.user_tg_id, body.chat_id) {
+            Some(chat_user) => ratings_of(&tables, chat_user.chat_user_id)
+                .filter(|rating| rating.created_at <= at || rating.by_chat_user_id.is_none())
+                .fold(zero_amount(), |sum, rating| sum + &rating.amount),
+            None => zero_amount(),
+        };
+        Ok(lib::helpers::round_amount(amount))
+    }
+
+    async fn fetch_global_rating(&self, telegram_id: i64) -> Result<model::GlobalRatingResponse, DBError> {
+        let tables = self.store.tables();
+        let user_id = tables.users.iter().find(|user| user.telegram_id == telegram_id).map(|user| user.user_id);
+        let chat_ratings = tables.chat_users
+            .iter()
+            .filter(|chat_user| Some(chat_user.user_id) == user_id && !chat_user.is_rating_optout)
+            .filter_map(|chat_user| {
+                let weight = tables.chat_setting
+                    .iter()
+                    .find(|setting| {
+                        setting.chat_id == chat_user.chat_id && setting.name == registry::GLOBAL_REPUTATION_WEIGHT.key
+                    })
+                    .and_then(|setting| setting.value.parse().ok())
+                    .unwrap_or_else(default_global_reputation_weight);
+                if weight <= sqlx::types::BigDecimal::from(0) {
+                    return None;
+                }
+                let mut ratings = ratings_of(&tables, chat_user.chat_user_id).peekable();
+                ratings.peek()?;
+                let amount = ratings.fold(zero_amount(), |sum, rating| sum + &rating.amount);
+                Some((amount, weight))
+            })
+            .collect_vec();
+
+        let weights_sum = chat_ratings
+            .iter()
+            .fold(sqlx::types::BigDecimal::from(0), |sum, (_, weight)| sum + weight);
+        let amount = match chat_ratings.is_empty() {
+            true => None,
+            false => Some(lib::helpers::round_amount(
+                chat_ratings.iter().fold(zero_amount(), |sum, (amount, weight)| sum + amount * weight) / weights_sum,
+            )),
+        };
+        Ok(model::GlobalRatingResponse { amount, chats_count: chat_ratings.len() as i64 })
+    }
+
+    async fn fetch_top_by_rating(
+        &self,
+        body: model::TopUsersRequest,
+    ) -> Result<Vec<model::TopUsersResponse>, DBError> {
+        let tables = self.store.tables();
+        Ok(tables.chat_users
+            .iter()
+            .filter(|chat_user| chat_user.chat_id == body.chat_id && !chat_user.is_rating_optout)
+            .filter_map(|chat_user| {
+                let mut ratings = ratings_of(&tables, chat_user.chat_user_id)
+                    .filter(|rating| body.rating_category_id.is_none()
+                        || rating.rating_category_id == body.rating_category_id)
+                    .peekable();
+                ratings.peek()?;
+                Some(model::TopUsersResponse {
+                    full_name: user_of(&tables, chat_user).full_name(),
+                    rating_amount: ratings.fold(zero_amount(), |sum, rating| sum + &rating.amount),
+                })
+            })
+            .sorted_by(|a, b| b.rating_amount.cmp(&a.rating_amount))
+            .take(body.limit as usize)
+            .collect())
+    }
+
+    async fn create_rating_record(
+        &self,
+        body: &model::RatingRequest,
+    ) -> Result<sqlx::types::Uuid, DBError> {
+        let mut tables = self.store.tables();
+        let rating_id = sqlx::types::Uuid::new_v4();
+        let rating = RatingRow {
+            rating_id,
+            chat_user_id: chat_user_id_of(&tables, Some(body.user_tg_id), body.chat_id),
+            by_chat_user_id: chat_user_id_of(&tables, body.by_user_tg_id, body.chat_id),
+            amount: body.amount.clone(),
+            comment: body.comment.clone(),
+            created_at: chrono::Utc::now().naive_utc(),
+            rating_category_id: body.rating_category_id,
+            source_id: None,
+        };
+        tables.ratings.push(rating);
+        Ok(rating_id)
+    }
+
+    async fn import_rating_record(
+        &self,
+        body: &model::RatingImportRequest,
+    ) -> Result<Option<sqlx::types::Uuid>, DBError> {
+        let mut tables = self.store.tables();
+        let chat_user_id = chat_user_id_of(&tables, Some(body.user_tg_id), body.chat_id);
+        if body.source_id.is_some() && tables.ratings
+            .iter()
+            .any(|rating| rating.chat_user_id == chat_user_id && rating.source_id == body.source_id) {
+            return Ok(None);
+        }
+        let rating_id = sqlx::types::Uuid::new_v4();
+        let rating = RatingRow {
+            rating_id,
+            chat_user_id,
+            by_chat_user_id: chat_user_id_of(&tables, body.by_user_tg_id, body.chat_id),
+            amount: body.amount.clone(),
+            comment: body.comment.clone(),
+            created_at: body.created_at.unwrap_or_else(|| chrono::Utc::now().naive_utc()),
+            rating_category_id: body.rating_category_id,
+            source_id: body.source_id.clone(),
+        };
+        tables.ratings.push(rating);
+        Ok(Some(rating_id))
+    }
+
+    async fn delete_rating_record(&self, record_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        self.store.tables().ratings.retain(|rating| rating.rating_id != record_id);
+        Ok(())
+    }
+
+    async fn fetch_rating_by_category(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<Vec<model::UserRatingByCategoryResponse>, DBError> {
+        let tables = self.store.tables();
+        let chat_user = match tables.chat_user(body.user_tg_id, body.chat_id) {
+            Some(chat_user) => chat_user,
+            None => return Ok(vec![]),
+        };
+        Ok(ratings_of(&tables, chat_user.chat_user_id)
+            .filter_map(|rating| {
+                tables.rating_categories
+                    .iter()
+                    .find(|category| Some(category.rating_category_id) == rating.rating_category_id)
+                    .map(|category| (category.name.clone(), rating.amount.clone()))
+            })
+            .into_group_map()
+            .into_iter()
+            .map(|(name, amounts)| model::UserRatingByCategoryResponse {
+                name,
+                amount: lib::helpers::round_amount(amounts.into_iter().fold(zero_amount(), |sum, amount| sum + amount)),
+            })
+            .sorted_by(|a, b| b.amount.cmp(&a.amount))
+            .collect())
+    }
+
+    async fn fetch_rating_categories(&self, chat_id: i64) -> Result<Vec<model::RatingCategory>, DBError> {
+        Ok(self.store
+            .tables()
+            .rating_categories
+            .iter()
+            .filter(|category| category.chat_id == chat_id)
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .map(|category| model::RatingCategory {
+                rating_category_id: category.rating_category_id,
+                name: category.name.clone(),
+                triggers: category.triggers.clone(),
+            })
+            .collect())
+    }
+
+    async fn create_rating_category(&self, body: &model::RatingCategoryRequest) -> Result<(), DBError> {
+        let mut tables = self.store.tables();
+        match tables.rating_categories
+            .iter_mut()
+            .find(|category| category.chat_id == body.chat_id && category.name == body.name) {
+            Some(category) => category.triggers = body.triggers.clone(),
+            None => tables.rating_categories.push(RatingCategoryRow {
+                rating_category_id: sqlx::types::Uuid::new_v4(),
+                chat_id: body.chat_id,
+                name: body.name.clone(),
+                triggers: body.triggers.clone(),
+            }),
+        }
+        Ok(())
+    }
+
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, DBError> {
+        let mut tables = self.store.tables();
+        let rating_category_id = match tables.rating_categories
+            .iter()
+            .find(|category| category.chat_id == chat_id && category.name == name) {
+            Some(category) => category.rating_category_id,
+            None => return Ok(false),
+        };
+        tables.rating_categories.retain(|category| category.rating_category_id != rating_category_id);
+        tables.ratings
+            .iter_mut()
+            .filter(|rating| rating.rating_category_id == Some(rating_category_id))
+            .for_each(|rating| rating.rating_category_id = None);
+        Ok(true)
+    }
+
+    async fn fetch_ratings_export(
+        &self,
+        body: &model::RatingExportRequest,
+    ) -> Result<Vec<model::RatingExportRecord>, DBError> {
+        let tables = self.store.tables();
+        let find_chat_user = |chat_user_id: Option<sqlx::types::Uuid>| {
+            tables.chat_users.iter().find(|chat_user| Some(chat_user.chat_user_id) == chat_user_id)
+        };
+        Ok(tables.ratings
+            .iter()
+            .filter(|rating| rating.created_at >= body.since)
+            .filter_map(|rating| {
+                let chat_user = find_chat_user(rating.chat_user_id).filter(|cu| cu.chat_id == body.chat_id)?;
+                let user = user_of(&tables, chat_user);
+                let by_user = find_chat_user(rating.by_chat_user_id).map(|by_cu| user_of(&tables, by_cu));
+                Some(model::RatingExportRecord {
+                    rating_id: rating.rating_id.to_string(),
+                    telegram_id: user.telegram_id,
+                    full_name: user.full_name(),
+                    by_telegram_id: by_user.map(|by_user| by_user.telegram_id),
+                    by_full_name: by_user.map(UserRow::full_name),
+                    category: tables.rating_categories
+                        .iter()
+                        .find(|category| Some(category.rating_category_id) == rating.rating_category_id)
+                        .map(|category| category.name.clone()),
+                    amount: rating.amount.to_string(),
+                    comment: rating.comment.clone(),
+                    created_at: rating.created_at.format(EXPORT_DATETIME_FORMAT).to_string(),
+                })
+            })
+            .sorted_by_key(|record| record.created_at.clone())
+            .collect())
+    }
+
+    async fn fetch_users_export(&self, chat_id: i64) -> Result<Vec<model::UserExportRecord>, DBError> {
+        let tables = self.store.tables();
+        Ok(tables.chat_users
+            .iter()
+            .filter(|chat_user| chat_user.chat_id == chat_id)
+            .map(|chat_user| {
+                let amount = ratings_of(&tables, chat_user.chat_user_id)
+                    .fold(zero_amount(), |sum, rating| sum + &rating.amount);
+                (chat_user, user_of(&tables, chat_user), amount)
+            })
+            .sorted_by(|(_, _, a), (_, _, b)| b.cmp(a))
+            .map(|(chat_user, user, amount)| model::UserExportRecord {
+                telegram_id: user.telegram_id,
+                username: user.username.clone(),
+                full_name: user.full_name(),
+                rating_amount: amount.to_string(),
+                messages_count: chat_user.messages_count,
+                is_rating_optout: chat_user.is_rating_optout,
+                created_at: chat_user.created_at.format(EXPORT_DATETIME_FORMAT).to_string(),
+            })
+            .collect())
+    }
+}
+
+pub fn new_memory_user_db_repository(store: Arc<MemoryStore>) -> Box<dyn UserDBRepository> {
+    Box::new(MemoryUserDBRepositoryImpl { store })
+}
+
+struct SqliteUserDBRepositoryImpl {
+    pool: Arc<sqlx::SqlitePool>,
+}
+
+/// Ids are generated here and stored as text, SQLite has neither `uuid` nor `gen_random_uuid()`.
+fn parse_uuid(id: String) -> Result<sqlx::types::Uuid, sqlx::Error> {
+    sqlx::types::Uuid::parse_str(&id).map_err(|err| sqlx::Error::Decode(Box::new(err)))
+}
+
+#[async_trait]
+impl UserDBRepository for SqliteUserDBRepositoryImpl {
+    async fn fetch_user_id(&self, telegram_id: i64) -> Result<sqlx::types::Uuid, DBError> {
+        sqlx::query_scalar::<_, String>(include_str!("queries/sqlite/user/fetch_user_id.sql"))
+            .bind(telegram_id)
+            .fetch_one(&*self.pool)
+            .await
+            .and_then(parse_uuid)
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user id in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_user_by_username(&self, username: &str) -> Result<Option<model::UserResponse>, DBError> {
+        sqlx::query_as::<_, (i64, String, Option<String>)>(
+            include_str!("queries/sqlite/user/fetch_by_username.sql")
+        )
+            .bind(username)
+            .fetch_optional(&*self.pool)
+            .await
+            .map(|row| row.map(|(telegram_id, first_name, last_name)| model::UserResponse {
+                telegram_id,
+                first_name,
+                last_name,
+            }))
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user by username in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create(&self, body: &model::UserRequest) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/user/create.sql"))
+            .bind(sqlx::types::Uuid::new_v4().to_string())
+            .bind(body.telegram_id)
+            .bind(&body.username)
+            .bind(&body.first_name)
+            .bind(&body.last_name)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to create user in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_chat_user(&self, body: model::ChatUserRequest) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_user/create.sql"))
+            .bind(sqlx::types::Uuid::new_v4().to_string())
+            .bind(body.user_id.to_string())
+            .bind(body.chat_id)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to create chat user in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_chat_user(&self, body: &model::UserRatingRequest) -> Result<model::ChatUser, DBError> {
+        sqlx::query_as::<_, (chrono::NaiveDateTime, i64, bool)>(
+            include_str!("queries/sqlite/chat_user/fetch.sql")
+        )
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map(|(created_at, messages_count, is_rating_optout)| model::ChatUser {
+                created_at,
+                messages_count,
+                is_rating_optout,
+            })
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch chat user in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn increment_messages_count(&self, body: &model::UserRatingRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_user/increment_messages_count.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to increment chat user messages count in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn backfill_chat_user_stats(&self, body: &model::ChatUserBackfillRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_user/backfill_stats.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .bind(body.messages_count)
+            .bind(body.first_seen_at)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to backfill chat user stats in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn change_rating_optout(&self, body: &model::RatingOptoutRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_user/change_rating_optout.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .bind(body.is_rating_optout)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat user rating opt-out in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn change_membership(&self, body: &model::MembershipRequest) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/chat_user/change_membership.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .bind(body.is_member)
+            .bind(body.at)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to change chat user membership in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_rating(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<sqlx::types::BigDecimal, DBError> {
+        sqlx::query_scalar::<_, f64>(include_str!("queries/sqlite/rating/fetch_user_rating.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .fetch_one(&*self.pool)
+            .await
+            .map(lib::helpers::decimal_from_f64)
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user rating in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_rating_at(
+        &self,
+        body: model::UserRatingRequest,
+        at: chrono::NaiveDateTime,
+    ) -> Result<sqlx::types::BigDecimal, DBError> {
+        sqlx::query_scalar::<_, f64>(include_str!("queries/sqlite/rating/fetch_user_rating_at.sql"))
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .bind(at)
+            .fetch_one(&*self.pool)
+            .await
+            .map(lib::helpers::decimal_from_f64)
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user rating at a moment in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_global_rating(&self, telegram_id: i64) -> Result<model::GlobalRatingResponse, DBError> {
+        sqlx::query_as::<_, (Option<f64>, i64)>(include_str!("queries/sqlite/rating/fetch_global_rating.sql"))
+            .bind(telegram_id)
+            .bind(lib::helpers::f64_from_decimal(&default_global_reputation_weight()))
+            .fetch_one(&*self.pool)
+            .await
+            .map(|(amount, chats_count)| model::GlobalRatingResponse {
+                amount: amount.map(lib::helpers::decimal_from_f64),
+                chats_count,
+            })
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user global rating in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_top_by_rating(
+        &self,
+        body: model::TopUsersRequest,
+    ) -> Result<Vec<model::TopUsersResponse>, DBError> {
+        sqlx::query_as::<_, (String, f64)>(include_str!("queries/sqlite/user/fetch_top_users_by_rating.sql"))
+            .bind(body.chat_id)
+            .bind(body.limit)
+            .bind(body.rating_category_id.map(|id| id.to_string()))
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|(full_name, rating_amount)| model::TopUsersResponse {
+                    full_name,
+                    rating_amount: lib::helpers::decimal_from_f64(rating_amount),
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch top users in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_rating_record(
+        &self,
+        body: &model::RatingRequest,
+    ) -> Result<sqlx::types::Uuid, DBError> {
+        let rating_id = sqlx::types::Uuid::new_v4();
+        sqlx::query(include_str!("queries/sqlite/rating/create.sql"))
+            .bind(rating_id.to_string())
+            .bind(body.user_tg_id)
+            .bind(body.by_user_tg_id)
+            .bind(body.chat_id)
+            .bind(lib::helpers::f64_from_decimal(&body.amount))
+            .bind(&body.comment)
+            .bind(body.rating_category_id.map(|id| id.to_string()))
+            .execute(&*self.pool)
+            .await
+            .map(|_| rating_id)
+            .map_err(anyhow::Error::new)
+            .context("Failed to create rating in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn import_rating_record(
+        &self,
+        body: &model::RatingImportRequest,
+    ) -> Result<Option<sqlx::types::Uuid>, DBError> {
+        let rating_id = sqlx::types::Uuid::new_v4();
+        sqlx::query(include_str!("queries/sqlite/rating/import.sql"))
+            .bind(rating_id.to_string())
+            .bind(body.user_tg_id)
+            .bind(body.by_user_tg_id)
+            .bind(body.chat_id)
+            .bind(lib::helpers::f64_from_decimal(&body.amount))
+            .bind(&body.comment)
+            .bind(body.created_at)
+            .bind(body.rating_category_id.map(|id| id.to_string()))
+            .bind(&body.source_id)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0).then_some(rating_id))
+            .map_err(anyhow::Error::new)
+            .context("Failed to import rating in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn delete_rating_record(&self, record_id: sqlx::types::Uuid) -> Result<(), DBError> {
+        sqlx::query(include_str!("queries/sqlite/rating/delete_record.sql"))
+            .bind(record_id.to_string())
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to delete rating record in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_rating_by_category(
+        &self,
+        body: model::UserRatingRequest,
+    ) -> Result<Vec<model::UserRatingByCategoryResponse>, DBError> {
+        sqlx::query_as::<_, (String, f64)>(
+            include_str!("queries/sqlite/rating/fetch_user_rating_by_category.sql")
+        )
+            .bind(body.user_tg_id)
+            .bind(body.chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|(name, amount)| model::UserRatingByCategoryResponse {
+                    name,
+                    amount: lib::helpers::decimal_from_f64(amount),
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch user rating by category in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_rating_categories(&self, chat_id: i64) -> Result<Vec<model::RatingCategory>, DBError> {
+        let rows = sqlx::query_as::<_, (String, String, String)>(
+            include_str!("queries/sqlite/rating_category/fetch.sql")
+        )
+            .bind(chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch rating categories in SQLite")
+            .map_err(DBError::Execute)?;
+
+        rows
+            .into_iter()
+            .map(|(rating_category_id, name, triggers)| Ok(model::RatingCategory {
+                rating_category_id: parse_uuid(rating_category_id)?,
+                name,
+                triggers: serde_json::from_str(&triggers)?,
+            }))
+            .collect::<Result<Vec<_>, anyhow::Error>>()
+            .context("Failed to decode rating categories from SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn create_rating_category(&self, body: &model::RatingCategoryRequest) -> Result<(), DBError> {
+        let triggers = serde_json::to_string(&body.triggers)
+            .context("Failed to encode rating category triggers")
+            .map_err(DBError::Execute)?;
+        sqlx::query(include_str!("queries/sqlite/rating_category/create.sql"))
+            .bind(sqlx::types::Uuid::new_v4().to_string())
+            .bind(body.chat_id)
+            .bind(&body.name)
+            .bind(triggers)
+            .execute(&*self.pool)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::new)
+            .context("Failed to create rating category in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, DBError> {
+        sqlx::query(include_str!("queries/sqlite/rating_category/delete.sql"))
+            .bind(chat_id)
+            .bind(name)
+            .execute(&*self.pool)
+            .await
+            .map(|r| r.rows_affected().gt(&0))
+            .map_err(anyhow::Error::new)
+            .context("Failed to delete rating category in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_ratings_export(
+        &self,
+        body: &model::RatingExportRequest,
+    ) -> Result<Vec<model::RatingExportRecord>, DBError> {
+        sqlx::query_as::<_, (
+            String, i64, String, Option<i64>, Option<String>, Option<String>, String, Option<String>, String,
+        )>(include_str!("queries/sqlite/export/ratings.sql"))
+            .bind(body.chat_id)
+            .bind(body.since)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|row| model::RatingExportRecord {
+                    rating_id: row.0,
+                    telegram_id: row.1,
+                    full_name: row.2,
+                    by_telegram_id: row.3,
+                    by_full_name: row.4,
+                    category: row.5,
+                    amount: row.6,
+                    comment: row.7,
+                    created_at: row.8,
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch ratings export in SQLite")
+            .map_err(DBError::Execute)
+    }
+
+    async fn fetch_users_export(&self, chat_id: i64) -> Result<Vec<model::UserExportRecord>, DBError> {
+        sqlx::query_as::<_, (i64, Option<String>, String, String, i64, bool, String)>(
+            include_str!("queries/sqlite/export/users.sql")
+        )
+            .bind(chat_id)
+            .fetch_all(&*self.pool)
+            .await
+            .map(|rows| rows
+                .into_iter()
+                .map(|row| model::UserExportRecord {
+                    telegram_id: row.0,
+                    username: row.1,
+                    full_name: row.2,
+                    rating_amount: row.3,
+                    messages_count: row.4,
+                    is_rating_optout: row.5,
+                    created_at: row.6,
+                })
+                .collect())
+            .map_err(anyhow::Error::new)
+            .context("Failed to fetch users export in SQLite")
+            .map_err(DBError::Execute)
+    }
+}
+
+pub fn new_sqlite_user_db_repository(pool: Arc<sqlx::SqlitePool>) -> Box<dyn UserDBRepository> {
+    Box::new(SqliteUserDBRepositoryImpl { pool })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::persistence::{new_sqlite_pool, run_sqlite_migrations};
+
+    const CHAT_ID: i64 = -100;
+
+    async fn import_twice(repo: Box<dyn UserDBRepository>) {
+        let user = |username: Option<&str>| model::UserRequest {
+            telegram_id: 1,
+            username: username.map(str::to_string),
+            first_name: "Name".to_string(),
+            last_name: None,
+        };
+        repo.create(&user(Some("known"))).await.unwrap();
+        assert!(!repo.create(&user(None)).await.unwrap());
+        assert_eq!(repo.fetch_user_by_username("known").await.unwrap().map(|user| user.telegram_id), Some(1));
+
+        let user_id = repo.fetch_user_id(1).await.unwrap();
+        repo.create_chat_user(model::ChatUserRequest { user_id, chat_id: CHAT_ID }).await.unwrap();
+        let record = || model::RatingImportRequest {
+            user_tg_id: 1,
+            by_user_tg_id: None,
+            chat_id: CHAT_ID,
+            comment: None,
+            amount: sqlx::types::BigDecimal::from(5),
+            created_at: None,
+            rating_category_id: None,
+            source_id: Some("import:1:0:0:5.00:1".to_string()),
+        };
+        let backfill = model::ChatUserBackfillRequest {
+            user_tg_id: 1,
+            chat_id: CHAT_ID,
+            messages_count: 7,
+            first_seen_at: chrono::NaiveDate::from_ymd(2021, 1, 1).and_hms(0, 0, 0),
+        };
+        for _ in 0..2 {
+            repo.import_rating_record(&record()).await.unwrap();
+            repo.backfill_chat_user_stats(&backfill).await.unwrap();
+        }
+
+        let request = || model::UserRatingRequest { user_tg_id: 1, chat_id: CHAT_ID };
+        assert_eq!(repo.import_rating_record(&record()).await.unwrap(), None);
+        assert_eq!(repo.fetch_rating(request()).await.unwrap(), sqlx::types::BigDecimal::from(5).with_scale(2));
+        assert_eq!(repo.fetch_chat_user(&request()).await.unwrap().messages_count, 7);
+    }
+
+    #[tokio::test]
+    async fn repeated_import_is_skipped_in_memory() {
+        import_twice(new_memory_user_db_repository(Arc::new(MemoryStore::default()))).await;
+    }
+
+    #[tokio::test]
+    async fn repeated_import_is_skipped_in_sqlite() {
+        // every pooled connection gets its own in-memory database, so the test uses a file
+        let path = std::env::temp_dir().join(format!("comparty-bot-{}.db", sqlx::types::Uuid::new_v4()));
+        let pool = new_sqlite_pool(&format!("sqlite://{}", path.display())).await.unwrap();
+        run_sqlite_migrations(&pool).await;
+        sqlx::query("INSERT INTO chat(chat_id, title) VALUES (?1, 'chat')")
+            .bind(CHAT_ID)
+            .execute(&pool)
+            .await
+            .unwrap();
+        import_twice(new_sqlite_user_db_repository(Arc::new(pool))).await;
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,4 +1,4 @@
 mod db;
 mod cache;
-pub use db::{UserDBRepository, new_user_db_repository};
-pub use cache::{UserCacheRepository, new_user_cache_repository};
\ No newline at end of file
+pub use db::{UserDBRepository, new_user_db_repository, new_memory_user_db_repository, new_sqlite_user_db_repository};
+pub use cache::{UserCacheRepository, new_user_cache_repository, new_memory_user_cache_repository};
\ No newline at end of file
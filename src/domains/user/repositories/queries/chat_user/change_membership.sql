@@ -0,0 +1,5 @@
+UPDATE chat_user
+SET left_at = CASE WHEN $3 THEN NULL ELSE COALESCE(chat_user.left_at, $4) END,
+    rejoined_at = CASE WHEN $3 AND chat_user.left_at IS NOT NULL THEN $4 ELSE chat_user.rejoined_at END
+FROM "user" u
+WHERE u.user_id = chat_user.user_id AND u.telegram_id = $1 AND chat_user.chat_id = $2;
\ No newline at end of file
@@ -0,0 +1,4 @@
+UPDATE chat_user
+SET messages_count = chat_user.messages_count + 1
+FROM "user" u
+WHERE u.user_id = chat_user.user_id AND u.telegram_id = $1 AND chat_user.chat_id = $2;
\ No newline at end of file
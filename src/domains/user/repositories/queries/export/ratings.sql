@@ -0,0 +1,17 @@
+SELECT r.rating_id::text AS "rating_id!",
+       u.telegram_id AS "telegram_id!",
+       CONCAT(u.first_name, ' ', u.last_name) AS "full_name!",
+       by_u.telegram_id AS "by_telegram_id?",
+       CASE WHEN by_u.user_id IS NULL THEN NULL ELSE CONCAT(by_u.first_name, ' ', by_u.last_name) END AS "by_full_name?",
+       rc.name AS "category?",
+       r.amount::text AS "amount!",
+       r.comment AS "comment?",
+       to_char(r.created_at, 'YYYY-MM-DD"T"HH24:MI:SS') AS "created_at!"
+FROM rating r
+INNER JOIN chat_user cu ON cu.chat_user_id = r.chat_user_id
+INNER JOIN "user" u ON u.user_id = cu.user_id
+LEFT JOIN chat_user by_cu ON by_cu.chat_user_id = r.by_chat_user_id
+LEFT JOIN "user" by_u ON by_u.user_id = by_cu.user_id
+LEFT JOIN rating_category rc ON rc.rating_category_id = r.rating_category_id
+WHERE cu.chat_id = $1 AND r.created_at >= $2
+ORDER BY r.created_at;
\ No newline at end of file
@@ -0,0 +1,13 @@
+SELECT u.telegram_id AS "telegram_id!",
+       u.username AS "username?",
+       CONCAT(u.first_name, ' ', u.last_name) AS "full_name!",
+       COALESCE(SUM(r.amount), 0.00)::text AS "rating_amount!",
+       cu.messages_count AS "messages_count!",
+       cu.is_rating_optout AS "is_rating_optout!",
+       to_char(cu.created_at, 'YYYY-MM-DD"T"HH24:MI:SS') AS "created_at!"
+FROM chat_user cu
+INNER JOIN "user" u ON u.user_id = cu.user_id
+LEFT JOIN rating r ON r.chat_user_id = cu.chat_user_id
+WHERE cu.chat_id = $1
+GROUP BY u.user_id, cu.chat_user_id
+ORDER BY COALESCE(SUM(r.amount), 0.00) DESC;
\ No newline at end of file
@@ -0,0 +1,12 @@
+INSERT INTO rating(chat_user_id, by_chat_user_id, amount, comment, created_at, rating_category_id, source_id)
+VALUES (
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = $1 AND cu.chat_id = $3),
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = $2 AND cu.chat_id = $3),
+        $4,
+        $5,
+        COALESCE($6, current_timestamp),
+        $7,
+        $8
+)
+ON CONFLICT (chat_user_id, source_id) DO NOTHING
+RETURNING rating_id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+INSERT INTO rating_category(chat_id, name, triggers)
+VALUES ($1, $2, $3)
+ON CONFLICT (chat_id, name) DO UPDATE
+SET triggers = $3;
\ No newline at end of file
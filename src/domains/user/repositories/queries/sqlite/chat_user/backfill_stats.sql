@@ -0,0 +1,3 @@
+UPDATE chat_user
+SET messages_count = MAX(chat_user.messages_count, ?3), created_at = MIN(chat_user.created_at, ?4)
+WHERE chat_user.chat_id = ?2 AND chat_user.user_id = (SELECT u.user_id FROM "user" u WHERE u.telegram_id = ?1);
@@ -0,0 +1,4 @@
+UPDATE chat_user
+SET left_at = CASE WHEN ?3 THEN NULL ELSE COALESCE(chat_user.left_at, ?4) END,
+    rejoined_at = CASE WHEN ?3 AND chat_user.left_at IS NOT NULL THEN ?4 ELSE chat_user.rejoined_at END
+WHERE chat_user.chat_id = ?2 AND chat_user.user_id = (SELECT u.user_id FROM "user" u WHERE u.telegram_id = ?1);
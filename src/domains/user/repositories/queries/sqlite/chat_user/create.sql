@@ -0,0 +1 @@
+INSERT INTO chat_user(chat_user_id, user_id, chat_id, created_at) VALUES (?1, ?2, ?3, current_timestamp) ON CONFLICT DO NOTHING;
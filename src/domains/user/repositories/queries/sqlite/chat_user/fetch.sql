@@ -0,0 +1,3 @@
+SELECT cu.created_at, cu.messages_count, cu.is_rating_optout FROM chat_user cu
+INNER JOIN "user" u ON u.user_id = cu.user_id
+WHERE u.telegram_id = ?1 AND cu.chat_id = ?2;
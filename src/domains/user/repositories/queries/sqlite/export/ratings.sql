@@ -0,0 +1,17 @@
+SELECT r.rating_id,
+       u.telegram_id,
+       u.first_name || ' ' || COALESCE(u.last_name, '') AS full_name,
+       by_u.telegram_id AS by_telegram_id,
+       CASE WHEN by_u.user_id IS NULL THEN NULL ELSE by_u.first_name || ' ' || COALESCE(by_u.last_name, '') END AS by_full_name,
+       rc.name AS category,
+       CAST(r.amount AS TEXT) AS amount,
+       r.comment,
+       strftime('%Y-%m-%dT%H:%M:%S', r.created_at) AS created_at
+FROM rating r
+INNER JOIN chat_user cu ON cu.chat_user_id = r.chat_user_id
+INNER JOIN "user" u ON u.user_id = cu.user_id
+LEFT JOIN chat_user by_cu ON by_cu.chat_user_id = r.by_chat_user_id
+LEFT JOIN "user" by_u ON by_u.user_id = by_cu.user_id
+LEFT JOIN rating_category rc ON rc.rating_category_id = r.rating_category_id
+WHERE cu.chat_id = ?1 AND r.created_at >= ?2
+ORDER BY r.created_at;
@@ -0,0 +1,13 @@
+SELECT u.telegram_id,
+       u.username,
+       u.first_name || ' ' || COALESCE(u.last_name, '') AS full_name,
+       CAST(ROUND(COALESCE(SUM(r.amount), 0.0), 2) AS TEXT) AS rating_amount,
+       cu.messages_count,
+       cu.is_rating_optout,
+       strftime('%Y-%m-%dT%H:%M:%S', cu.created_at) AS created_at
+FROM chat_user cu
+INNER JOIN "user" u ON u.user_id = cu.user_id
+LEFT JOIN rating r ON r.chat_user_id = cu.chat_user_id
+WHERE cu.chat_id = ?1
+GROUP BY u.user_id, cu.chat_user_id
+ORDER BY COALESCE(SUM(r.amount), 0.0) DESC;
@@ -0,0 +1,10 @@
+INSERT INTO rating(rating_id, chat_user_id, by_chat_user_id, amount, comment, created_at, rating_category_id)
+VALUES (
+        ?1,
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = ?2 AND cu.chat_id = ?4),
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = ?3 AND cu.chat_id = ?4),
+        ?5,
+        ?6,
+        current_timestamp,
+        ?7
+);
@@ -0,0 +1,14 @@
+SELECT ROUND(SUM(weighted.amount * weighted.weight) / NULLIF(SUM(weighted.weight), 0), 2) AS amount,
+       COUNT(weighted.chat_id) AS chats_count
+FROM (
+    SELECT chat_rating.chat_id, chat_rating.amount, COALESCE(CAST(cs.value AS REAL), ?2) AS weight
+    FROM (
+        SELECT cu.chat_id, SUM(r.amount) AS amount FROM rating r
+        INNER JOIN chat_user cu ON cu.chat_user_id = r.chat_user_id
+        INNER JOIN "user" u ON u.user_id = cu.user_id
+        WHERE u.telegram_id = ?1 AND NOT cu.is_rating_optout
+        GROUP BY cu.chat_id
+    ) chat_rating
+    LEFT JOIN chat_setting cs ON cs.chat_id = chat_rating.chat_id AND cs.name = 'global_reputation_weight'
+) weighted
+WHERE weighted.weight > 0;
@@ -0,0 +1,7 @@
+SELECT rc.name, ROUND(COALESCE(SUM(r.amount), 0.0), 2) AS amount FROM rating r
+INNER JOIN rating_category rc ON rc.rating_category_id = r.rating_category_id
+INNER JOIN chat_user cu ON cu.chat_user_id = r.chat_user_id
+INNER JOIN "user" u ON u.user_id = cu.user_id
+WHERE u.telegram_id = ?1 AND cu.chat_id = ?2
+GROUP BY rc.name
+ORDER BY amount DESC;
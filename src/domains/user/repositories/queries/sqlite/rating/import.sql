@@ -0,0 +1,12 @@
+INSERT INTO rating(rating_id, chat_user_id, by_chat_user_id, amount, comment, created_at, rating_category_id, source_id)
+VALUES (
+        ?1,
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = ?2 AND cu.chat_id = ?4),
+        (SELECT cu.chat_user_id FROM chat_user cu INNER JOIN "user" u on u.user_id = cu.user_id WHERE u.telegram_id = ?3 AND cu.chat_id = ?4),
+        ?5,
+        ?6,
+        COALESCE(?7, current_timestamp),
+        ?8,
+        ?9
+)
+ON CONFLICT (chat_user_id, source_id) DO NOTHING;
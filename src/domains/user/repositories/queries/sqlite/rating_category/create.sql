@@ -0,0 +1,4 @@
+INSERT INTO rating_category(rating_category_id, chat_id, name, triggers)
+VALUES (?1, ?2, ?3, ?4)
+ON CONFLICT (chat_id, name) DO UPDATE
+SET triggers = ?4;
@@ -0,0 +1 @@
+DELETE FROM rating_category WHERE chat_id = ?1 AND name = ?2;
@@ -0,0 +1 @@
+SELECT rc.rating_category_id, rc.name, rc.triggers FROM rating_category rc WHERE rc.chat_id = ?1 ORDER BY rc.name;
@@ -0,0 +1,10 @@
+INSERT INTO "user"(user_id, telegram_id, username, first_name, last_name)
+VALUES (?1, ?2, ?3, ?4, ?5)
+ON CONFLICT (telegram_id)
+DO UPDATE
+SET username = COALESCE(excluded.username, "user".username),
+    first_name = excluded.first_name,
+    last_name = COALESCE(excluded.last_name, "user".last_name)
+WHERE "user".username IS NOT COALESCE(excluded.username, "user".username)
+   OR "user".first_name != excluded.first_name
+   OR "user".last_name IS NOT COALESCE(excluded.last_name, "user".last_name);
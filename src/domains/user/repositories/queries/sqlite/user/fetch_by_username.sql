@@ -0,0 +1 @@
+SELECT u.telegram_id, u.first_name, u.last_name FROM "user" u WHERE LOWER(u.username) = LOWER(?1);
@@ -0,0 +1,9 @@
+SELECT u.first_name || ' ' || COALESCE(u.last_name, '') AS full_name,
+       CAST(COALESCE(SUM(r.amount), 0.0) AS REAL) AS rating_amount
+FROM rating r
+INNER JOIN chat_user cu ON cu.chat_user_id = r.chat_user_id
+INNER JOIN "user" u ON u.user_id = cu.user_id
+WHERE cu.chat_id = ?1 AND NOT cu.is_rating_optout AND (?3 IS NULL OR r.rating_category_id = ?3)
+GROUP BY u.user_id, u.first_name, u.last_name
+ORDER BY rating_amount DESC
+LIMIT ?2;
@@ -2,5 +2,9 @@ INSERT INTO "user"(telegram_id, username, first_name, last_name)
 VALUES ($1, $2, $3, $4)
 ON CONFLICT (telegram_id)
 DO UPDATE
-SET username = $2, first_name = $3, last_name = $4
-WHERE "user".username != $2 OR "user".first_name != $3 OR "user".last_name != $4;
\ No newline at end of file
+SET username = COALESCE(EXCLUDED.username, "user".username),
+    first_name = EXCLUDED.first_name,
+    last_name = COALESCE(EXCLUDED.last_name, "user".last_name)
+WHERE "user".username IS DISTINCT FROM COALESCE(EXCLUDED.username, "user".username)
+   OR "user".first_name != EXCLUDED.first_name
+   OR "user".last_name IS DISTINCT FROM COALESCE(EXCLUDED.last_name, "user".last_name);
\ No newline at end of file
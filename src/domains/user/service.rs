@@ -6,18 +6,45 @@ use sqlx;
 pub trait UserService: Send + Sync {
     async fn get_rating(&self, body: model::UserRatingRequest)
         -> Result<sqlx::types::BigDecimal, lib::errors::UserError>;
+    async fn get_rating_at(&self, body: model::UserRatingRequest, at: chrono::NaiveDateTime)
+        -> Result<sqlx::types::BigDecimal, lib::errors::UserError>;
+    async fn get_global_rating(&self, telegram_id: i64)
+        -> Result<model::GlobalRatingResponse, lib::errors::UserError>;
+    async fn get_user_by_username(&self, username: &str)
+        -> Result<Option<model::UserResponse>, lib::errors::UserError>;
     async fn fetch_top_by_rating(&self, body: model::TopUsersRequest)
         -> Result<Vec<model::TopUsersResponse>, lib::errors::UserError>;
     async fn create_if_not_exists(&self, body: model::UserRequest, chat_id: i64)
         -> Result<bool, lib::errors::UserError>;
     async fn create_rating_record(&self, body: model::RatingRequest, chat_id: i64)
         -> Result<sqlx::types::Uuid, lib::errors::UserError>;
+    async fn import_rating_record(&self, body: model::RatingImportRequest)
+        -> Result<Option<sqlx::types::Uuid>, lib::errors::UserError>;
     async fn delete_rating_record(&self, record_id: &str) -> Result<(), lib::errors::UserError>;
+    async fn count_message(&self, body: model::UserRatingRequest) -> Result<(), lib::errors::UserError>;
+    async fn backfill_chat_user_stats(&self, body: model::ChatUserBackfillRequest)
+        -> Result<(), lib::errors::UserError>;
+    async fn check_voter_eligibility(&self, body: model::VoterEligibilityRequest)
+        -> Result<(), lib::errors::UserError>;
+    async fn get_chat_user(&self, body: model::UserRatingRequest) -> Result<model::ChatUser, lib::errors::UserError>;
+    async fn change_rating_optout(&self, body: model::RatingOptoutRequest) -> Result<(), lib::errors::UserError>;
+    async fn change_membership(&self, body: model::MembershipRequest) -> Result<(), lib::errors::UserError>;
+    async fn get_rating_by_category(&self, body: model::UserRatingRequest)
+        -> Result<Vec<model::UserRatingByCategoryResponse>, lib::errors::UserError>;
+    async fn get_rating_categories(&self, chat_id: i64)
+        -> Result<Vec<model::RatingCategory>, lib::errors::UserError>;
+    async fn save_rating_category(&self, body: model::RatingCategoryRequest) -> Result<(), lib::errors::UserError>;
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, lib::errors::UserError>;
+    async fn export_ratings(&self, body: model::RatingExportRequest, format: lib::enums::FileFormat)
+        -> Result<Vec<u8>, lib::errors::UserError>;
+    async fn export_users(&self, chat_id: i64, format: lib::enums::FileFormat)
+        -> Result<Vec<u8>, lib::errors::UserError>;
 }
 
 struct UserServiceImpl {
     db_repo: Box<dyn UserDBRepository>,
     cache_repo: Box<dyn UserCacheRepository>,
+    config: lib::config::SharedConfig,
 }
 
 #[async_trait]
@@ -31,6 +58,33 @@ impl UserService for UserServiceImpl {
             .map_err(lib::errors::UserError::GetRating)
     }
 
+    async fn get_rating_at(&self, body: model::UserRatingRequest, at: chrono::NaiveDateTime)
+        -> Result<sqlx::types::BigDecimal, lib::errors::UserError> {
+        self.db_repo
+            .fetch_rating_at(body, at)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetRating)
+    }
+
+    async fn get_global_rating(&self, telegram_id: i64)
+        -> Result<model::GlobalRatingResponse, lib::errors::UserError> {
+        self.db_repo
+            .fetch_global_rating(telegram_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetRating)
+    }
+
+    async fn get_user_by_username(&self, username: &str)
+        -> Result<Option<model::UserResponse>, lib::errors::UserError> {
+        self.db_repo
+            .fetch_user_by_username(username)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetInfo)
+    }
+
     async fn fetch_top_by_rating(&self, body: model::TopUsersRequest)
         -> Result<Vec<model::TopUsersResponse>, lib::errors::UserError> {
         self.db_repo
@@ -67,7 +121,7 @@ impl UserService for UserServiceImpl {
         self.db_repo
             .create_chat_user(model::ChatUserRequest{ user_id, chat_id })
             .await
-            .map(|created| {
+            .inspect(|&created| {
                 if created {
                     log::info!(
                         "Successfully inserted chat user instance (user_id: {}, chat_id: {})",
@@ -75,7 +129,6 @@ impl UserService for UserServiceImpl {
                         chat_id
                     );
                 }
-                created
             })
             .map_err(|err| err.into())
             .map_err(lib::errors::UserError::Insert)
@@ -88,22 +141,32 @@ impl UserService for UserServiceImpl {
             by_user_id: body.by_user_tg_id.unwrap_or_default(),  // TODO:
             chat_id
         };
-        let expire_time = self.cache_repo
-            .get_rating_action_expired_time(request)
+        let is_started = self.cache_repo
+            .start_rating_cooldown(request, self.config.load().rating.cooldown_seconds as u64)
             .await
             .map_err(|err| err.into())
             .map_err(lib::errors::UserError::InsertRating)?;
 
-        if expire_time > 0 {
+        if !is_started {
+            let expire_time = self.cache_repo
+                .get_rating_action_expired_time(request)
+                .await
+                .map_err(|err| err.into())
+                .map_err(lib::errors::UserError::InsertRating)?;
+            lib::metrics::RATING_COOLDOWN_REJECTIONS_TOTAL.inc();
             return Err(lib::errors::UserError::RepeatingRequestDuringCooldown(
-                format!("Вы слишком часто инициируете изменение рейтинга. Подождите {}s", expire_time)
+                format!("Вы слишком часто инициируете изменение рейтинга. Подождите {}s", expire_time.max(1))
             ));
         }
+        log::info!(
+            "REDIS: Successfully inserted rating cooldown (user_id: {}, by_user_id: {}, chat_id: {})",
+            body.user_tg_id, body.by_user_tg_id.unwrap_or_default(), chat_id,
+        );
 
         let record_id = self.db_repo
             .create_rating_record(&body)
             .await
-            .map(|r| {
+            .inspect(|_| {
                 log::info!(
                     "Successfully inserted new rating record (user_id: {}, by_user_id: {}, \
                     chat_id: {}, comment: {}, amount: {:.2})",
@@ -113,23 +176,48 @@ impl UserService for UserServiceImpl {
                     body.comment.clone().unwrap_or_default(),
                     body.amount
                 );
-                r})
+            })
             .map_err(|err| err.into())
-            .map_err(lib::errors::UserError::InsertRating)?;
+            .map_err(lib::errors::UserError::InsertRating);
+        if record_id.is_err() {
+            // The vote wasn't counted, so it shouldn't hold the voter back.
+            let _ = self.cache_repo
+                .delete_rating_action(request)
+                .await
+                .map_err(|err| { log::error!("{:?}", err); });
+        }
+        let record_id = record_id?;
+        let direction = if body.amount < sqlx::types::BigDecimal::from(0) { "decrease" } else { "increase" };
+        lib::metrics::RATING_VOTES_TOTAL.with_label_values(&[direction]).inc();
+
+        Ok(record_id)
+    }
 
-        self.cache_repo
-            .save_rating_action(request)
+    async fn import_rating_record(&self, body: model::RatingImportRequest)
+        -> Result<Option<sqlx::types::Uuid>, lib::errors::UserError> {
+        self.db_repo
+            .import_rating_record(&body)
             .await
-            .map(|_| {
-                log::info!(
-                    "REDIS: Successfully inserted or updated rating cooldown \
-                    (user_id: {}, by_user_id: {}, chat_id: {})",
-                    body.user_tg_id, body.by_user_tg_id.unwrap_or_default(), chat_id,
-                );
+            .inspect(|r| {
+                match r {
+                    Some(_) => log::info!(
+                        "Successfully imported rating record (user_id: {}, by_user_id: {}, \
+                        chat_id: {}, amount: {:.2})",
+                        body.user_tg_id,
+                        body.by_user_tg_id.unwrap_or_default(),
+                        body.chat_id,
+                        body.amount
+                    ),
+                    None => log::info!(
+                        "Skipped already imported rating record (user_id: {}, chat_id: {}, source_id: {})",
+                        body.user_tg_id,
+                        body.chat_id,
+                        body.source_id.as_deref().unwrap_or_default(),
+                    ),
+                }
             })
-            .map_err(|err| { log::error!("{:?}", err); });
-
-        Ok(record_id)
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::Import)
     }
 
     async fn delete_rating_record(&self, record_id: &str) -> Result<(), lib::errors::UserError> {
@@ -144,8 +232,201 @@ impl UserService for UserServiceImpl {
             .map_err(|err| err.into())
             .map_err(lib::errors::UserError::DeleteRating)
     }
+
+    async fn count_message(&self, body: model::UserRatingRequest) -> Result<(), lib::errors::UserError> {
+        self.db_repo
+            .increment_messages_count(&body)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::CountMessage)
+    }
+
+    async fn backfill_chat_user_stats(&self, body: model::ChatUserBackfillRequest)
+        -> Result<(), lib::errors::UserError> {
+        self.db_repo
+            .backfill_chat_user_stats(&body)
+            .await
+            .map(|_| {
+                log::info!(
+                    "Successfully backfilled chat user stats (user_id: {}, chat_id: {}, messages_count: {})",
+                    body.user_tg_id,
+                    body.chat_id,
+                    body.messages_count,
+                );
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::Import)
+    }
+
+    async fn check_voter_eligibility(&self, body: model::VoterEligibilityRequest)
+        -> Result<(), lib::errors::UserError> {
+        if body.is_restricted {
+            return Err(lib::errors::UserError::VoterNotEligible(
+                "Пользователи с ограниченными правами не могут изменять чужой рейтинг".to_string()
+            ));
+        }
+
+        let chat_user = self.db_repo
+            .fetch_chat_user(&model::UserRatingRequest {
+                user_tg_id: body.user_tg_id,
+                chat_id: body.chat_id,
+            })
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::InsertRating)?;
+
+        if chat_user.is_rating_optout {
+            return Err(lib::errors::UserError::VoterNotEligible(
+                "Вы отказались от участия в рейтинге. Чтобы изменять чужой рейтинг, используйте !rating_optin"
+                    .to_string()
+            ));
+        }
+
+        let age = chrono::Utc::now().naive_utc() - chat_user.created_at;
+        if age < chrono::Duration::seconds(body.min_age as i64) {
+            return Err(lib::errors::UserError::VoterNotEligible(format!(
+                "Изменять чужой рейтинг можно спустя {}s после первого сообщения в чате (осталось: {}s)",
+                body.min_age,
+                body.min_age as i64 - age.num_seconds(),
+            )));
+        }
+
+        if chat_user.messages_count < body.min_messages {
+            return Err(lib::errors::UserError::VoterNotEligible(format!(
+                "Для изменения чужого рейтинга необходимо написать в чате не менее {} сообщений (написано: {})",
+                body.min_messages,
+                chat_user.messages_count,
+            )));
+        }
+
+        if body.rating < body.min_rating {
+            return Err(lib::errors::UserError::VoterNotEligible(format!(
+                "Для изменения чужого рейтинга необходим рейтинг не менее {:.2} (рейтинг: {:.2})",
+                body.min_rating,
+                body.rating,
+            )));
+        }
+        Ok(())
+    }
+
+    async fn get_chat_user(&self, body: model::UserRatingRequest) -> Result<model::ChatUser, lib::errors::UserError> {
+        self.db_repo
+            .fetch_chat_user(&body)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetInfo)
+    }
+
+    async fn change_rating_optout(&self, body: model::RatingOptoutRequest) -> Result<(), lib::errors::UserError> {
+        self.db_repo
+            .change_rating_optout(&body)
+            .await
+            .map(|_| {
+                log::info!(
+                    "Successfully changed chat user rating opt-out (user_id: {}, chat_id: {}, is_rating_optout: {})",
+                    body.user_tg_id,
+                    body.chat_id,
+                    body.is_rating_optout,
+                );
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::ChangeRatingOptout)
+    }
+
+    async fn change_membership(&self, body: model::MembershipRequest) -> Result<(), lib::errors::UserError> {
+        self.db_repo
+            .change_membership(&body)
+            .await
+            .map(|_| {
+                log::info!(
+                    "Successfully changed chat user membership (user_id: {}, chat_id: {}, is_member: {})",
+                    body.user_tg_id,
+                    body.chat_id,
+                    body.is_member,
+                );
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::ChangeMembership)
+    }
+
+    async fn get_rating_by_category(&self, body: model::UserRatingRequest)
+        -> Result<Vec<model::UserRatingByCategoryResponse>, lib::errors::UserError> {
+        self.db_repo
+            .fetch_rating_by_category(body)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetRating)
+    }
+
+    async fn get_rating_categories(&self, chat_id: i64)
+        -> Result<Vec<model::RatingCategory>, lib::errors::UserError> {
+        self.db_repo
+            .fetch_rating_categories(chat_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::GetRatingCategories)
+    }
+
+    async fn save_rating_category(&self, body: model::RatingCategoryRequest) -> Result<(), lib::errors::UserError> {
+        self.db_repo
+            .create_rating_category(&body)
+            .await
+            .map(|_| {
+                log::info!(
+                    "Successfully inserted or updated rating category (chat_id: {}, name: {}, triggers: {})",
+                    body.chat_id,
+                    body.name,
+                    body.triggers.join(" "),
+                );
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::SaveRatingCategory)
+    }
+
+    async fn delete_rating_category(&self, chat_id: i64, name: &str) -> Result<bool, lib::errors::UserError> {
+        self.db_repo
+            .delete_rating_category(chat_id, name)
+            .await
+            .inspect(|&deleted| {
+                if deleted {
+                    log::info!("Successfully deleted rating category (chat_id: {}, name: {})", chat_id, name);
+                }
+            })
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::DeleteRatingCategory)
+    }
+
+    async fn export_ratings(&self, body: model::RatingExportRequest, format: lib::enums::FileFormat)
+        -> Result<Vec<u8>, lib::errors::UserError> {
+        let records = self.db_repo
+            .fetch_ratings_export(&body)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::Export)?;
+        log::info!("Exporting {} rating records (chat_id: {})", records.len(), body.chat_id);
+        format
+            .serialize(&records)
+            .map_err(lib::errors::UserError::Export)
+    }
+
+    async fn export_users(&self, chat_id: i64, format: lib::enums::FileFormat)
+        -> Result<Vec<u8>, lib::errors::UserError> {
+        let records = self.db_repo
+            .fetch_users_export(chat_id)
+            .await
+            .map_err(|err| err.into())
+            .map_err(lib::errors::UserError::Export)?;
+        log::info!("Exporting {} chat users (chat_id: {})", records.len(), chat_id);
+        format
+            .serialize(&records)
+            .map_err(lib::errors::UserError::Export)
+    }
 }
 
-pub fn new_user_service(db_repo: Box<dyn UserDBRepository>, cache_repo: Box<dyn UserCacheRepository>) -> Box<dyn UserService> {
-    Box::new(UserServiceImpl { db_repo, cache_repo })
+pub fn new_user_service(
+    db_repo: Box<dyn UserDBRepository>,
+    cache_repo: Box<dyn UserCacheRepository>,
+    config: lib::config::SharedConfig,
+) -> Box<dyn UserService> {
+    Box::new(UserServiceImpl { db_repo, cache_repo, config })
 }
@@ -1,13 +1,17 @@
+use crate::{
+    core,
+    injected::domain_holder::{new_domain_holder, DomainHolder},
+    lib::{config::SharedConfig, errors::ConfigError},
+};
 use std::sync::Arc;
-use crate::{core, injected::domain_holder::{new_domain_holder, DomainHolder}};
 
 pub struct AppEnvironment {
     pub domain_holder: DomainHolder,
+    pub storage: Arc<core::persistence::Storage>,
 }
 
-pub async fn setup_environment() -> AppEnvironment {
-    let db_pool = Arc::new(core::persistence::new_pg_pool().await);
-    let redis_client = Arc::new(core::persistence::new_redis_client().await);
-    let domain_holder = new_domain_holder(db_pool, redis_client).await;
-    AppEnvironment { domain_holder }
+pub async fn setup_environment(config: SharedConfig) -> Result<AppEnvironment, ConfigError> {
+    let storage = core::persistence::new_storage(&config.load().storage).await?;
+    let domain_holder = new_domain_holder(&storage, config).await;
+    Ok(AppEnvironment { domain_holder, storage: Arc::new(storage) })
 }
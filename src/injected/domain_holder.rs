@@ -1,18 +1,24 @@
-pub use crate::domains::{admin_commands, chat, message, user};
-use std::sync::Arc;
+pub use crate::domains::{admin_commands, chat, job, message, user};
+use crate::{core::persistence::Storage, lib::config::SharedConfig};
 
 pub struct DomainHolder {
     pub admin_commands: admin_commands::AdminCommandsDomain,
     pub user: user::UserDomain,
     pub chat: chat::ChatDomain,
+    pub job: job::JobDomain,
     pub message: message::MessageDomain,
+    pub config: SharedConfig,
 }
 
-pub async fn new_domain_holder(db_pool: Arc<sqlx::PgPool>, cache_client: Arc<redis::Client>) -> DomainHolder {
+pub async fn new_domain_holder(storage: &Storage, config: SharedConfig) -> DomainHolder {
+    let job = job::new_job_domain(storage, config.clone()).await;
+    let chat = chat::new_chat_domain(storage, job.service.clone(), config.clone()).await;
     DomainHolder {
-        admin_commands: admin_commands::new_admin_commands_domain().await,
-        message: message::new_message_domain().await,
-        user: user::new_user_domain(db_pool.clone(), cache_client.clone()).await,
-        chat: chat::new_chat_domain(db_pool.clone()).await,
+        admin_commands: admin_commands::new_admin_commands_domain(chat.auto_delete.clone(), job.service.clone()).await,
+        message: message::new_message_domain(storage, config.clone(), chat.auto_delete.clone()).await,
+        user: user::new_user_domain(storage, config.clone(), chat.auto_delete.clone()).await,
+        chat,
+        job,
+        config,
     }
 }
@@ -2,4 +2,4 @@ mod app_environment;
 mod domain_holder;
 
 pub use app_environment::setup_environment;
-pub(crate) use domain_holder::DomainHolder;
+pub(crate) use domain_holder::{new_domain_holder, DomainHolder};
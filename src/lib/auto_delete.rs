@@ -0,0 +1,12 @@
+use crate::lib::enums::ReplyCategory;
+use async_trait::async_trait;
+use teloxide::types::Chat;
+
+/// Deletes bot replies, and optionally the messages they answer, after the TTL configured for the chat.
+/// Built by the chat domain and handed to the controllers that reply.
+#[async_trait]
+pub trait AutoDelete: Send + Sync {
+    /// Called for every message sent through [`crate::lib::tg_helpers`], `trigger_message_id` is the message
+    /// it answers. Errors are logged, the message is already sent.
+    async fn reply_sent(&self, chat: &Chat, trigger_message_id: Option<i32>, category: ReplyCategory, message_id: i32);
+}
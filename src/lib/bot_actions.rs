@@ -0,0 +1,454 @@
+use crate::lib::{self, types::ConfBot};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use teloxide::net::Download;
+use teloxide::payloads::{
+    AnswerCallbackQuerySetters, EditMessageTextSetters, RestrictChatMemberSetters, SendMessageSetters,
+    UnpinChatMessageSetters,
+};
+use teloxide::prelude::{Request, Requester};
+use teloxide::types::{InlineKeyboardMarkup, User};
+use teloxide::{ApiError, RequestError};
+
+/// Telegram calls made by the controllers, so they can run against a fake in tests and replays.
+#[async_trait]
+pub trait BotActions: Send + Sync {
+    /// Sends a message and returns its id.
+    async fn send_message(
+        &self,
+        chat_id: i64,
+        text: String,
+        reply_to_message_id: Option<i32>,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<i32, anyhow::Error>;
+    /// Replaces the text of a message, and its keyboard when `reply_markup` is given.
+    async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+        text: String,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<(), anyhow::Error>;
+    async fn send_document(&self, chat_id: i64, file_name: String, data: Vec<u8>) -> Result<(), anyhow::Error>;
+    async fn delete_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error>;
+    /// Takes away every permission until `until_date`.
+    async fn restrict_chat_member(&self, chat_id: i64, user_id: i64, until_date: DateTime<Utc>)
+        -> Result<(), anyhow::Error>;
+    /// Lets a restricted member send messages, media, polls and link previews again.
+    async fn lift_restrictions(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error>;
+    async fn kick_chat_member(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error>;
+    async fn unpin_chat_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error>;
+    async fn get_chat_administrators(&self, chat_id: i64) -> Result<Vec<User>, anyhow::Error>;
+    async fn is_chat_member_restricted(&self, chat_id: i64, user_id: i64) -> Result<bool, anyhow::Error>;
+    async fn answer_callback_query(&self, callback_query_id: &str, text: String, show_alert: bool)
+        -> Result<(), anyhow::Error>;
+    async fn download_file(&self, file_id: &str) -> Result<Vec<u8>, anyhow::Error>;
+}
+
+/// Counts the failed call in the metrics before handing the error over.
+fn api_error<E: std::error::Error + Send + Sync + 'static>(method: &str, err: E) -> anyhow::Error {
+    lib::metrics::TELEGRAM_API_ERRORS_TOTAL.with_label_values(&[method]).inc();
+    anyhow::Error::new(err)
+}
+
+struct TeloxideBotActions {
+    bot: ConfBot,
+}
+
+#[async_trait]
+impl BotActions for TeloxideBotActions {
+    async fn send_message(
+        &self,
+        chat_id: i64,
+        text: String,
+        reply_to_message_id: Option<i32>,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<i32, anyhow::Error> {
+        let mut request = self.bot.send_message(chat_id, text);
+        if let Some(message_id) = reply_to_message_id {
+            request = request.reply_to_message_id(message_id);
+        }
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
+        request
+            .send()
+            .await
+            .map(|message| message.id)
+            .map_err(|err| api_error("sendMessage", err))
+            .context("Telegram API error")
+    }
+
+    async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+        text: String,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<(), anyhow::Error> {
+        let mut request = self.bot.edit_message_text(chat_id, message_id, text);
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
+        request
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("editMessageText", err))
+            .context("Telegram API error")
+    }
+
+    async fn send_document(&self, chat_id: i64, file_name: String, data: Vec<u8>) -> Result<(), anyhow::Error> {
+        self.bot
+            .send_document(chat_id, teloxide::types::InputFile::memory(file_name, data))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("sendDocument", err))
+            .context("Telegram API error")
+    }
+
+    async fn delete_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error> {
+        self.bot
+            .delete_message(chat_id, message_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("deleteMessage", err))
+            .context("Telegram API error")
+    }
+
+    async fn restrict_chat_member(&self, chat_id: i64, user_id: i64, until_date: DateTime<Utc>)
+        -> Result<(), anyhow::Error> {
+        self.bot
+            .restrict_chat_member(chat_id, user_id, teloxide::types::ChatPermissions::default())
+            .until_date(until_date)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("restrictChatMember", err))
+            .context("Telegram API error")
+    }
+
+    async fn lift_restrictions(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error> {
+        let permissions = teloxide::types::ChatPermissions::new()
+            .can_send_messages(true)
+            .can_send_media_messages(true)
+            .can_send_polls(true)
+            .can_send_other_messages(true)
+            .can_add_web_page_previews(true);
+        self.bot
+            .restrict_chat_member(chat_id, user_id, permissions)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("restrictChatMember", err))
+            .context("Telegram API error")
+    }
+
+    async fn kick_chat_member(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error> {
+        self.bot
+            .kick_chat_member(chat_id, user_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("kickChatMember", err))
+            .context("Telegram API error")
+    }
+
+    async fn unpin_chat_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error> {
+        self.bot
+            .unpin_chat_message(chat_id)
+            .message_id(message_id)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("unpinChatMessage", err))
+            .context("Telegram API error")
+    }
+
+    async fn get_chat_administrators(&self, chat_id: i64) -> Result<Vec<User>, anyhow::Error> {
+        self.bot
+            .get_chat_administrators(chat_id)
+            .send()
+            .await
+            .map(|admins| admins.into_iter().map(|admin| admin.user).collect())
+            .map_err(|err| api_error("getChatAdministrators", err))
+            .context("Failed to fetch chat admins")
+    }
+
+    async fn is_chat_member_restricted(&self, chat_id: i64, user_id: i64) -> Result<bool, anyhow::Error> {
+        self.bot
+            .get_chat_member(chat_id, user_id)
+            .send()
+            .await
+            .map(|member| matches!(member.kind, teloxide::types::ChatMemberKind::Restricted(_)))
+            .map_err(|err| api_error("getChatMember", err))
+            .context("Telegram API error")
+    }
+
+    async fn answer_callback_query(&self, callback_query_id: &str, text: String, show_alert: bool)
+        -> Result<(), anyhow::Error> {
+        self.bot
+            .answer_callback_query(callback_query_id)
+            .text(text)
+            .show_alert(show_alert)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|err| api_error("answerCallbackQuery", err))
+            .context("Telegram API error")
+    }
+
+    async fn download_file(&self, file_id: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let file = self.bot
+            .get_file(file_id)
+            .send()
+            .await
+            .map_err(|err| api_error("getFile", err))
+            .context("Telegram API error")?;
+        let mut data = vec![];
+        self.bot
+            .inner()
+            .inner()
+            .download_file(&file.file_path, &mut data)
+            .await
+            .map_err(|err| api_error("downloadFile", err))
+            .context("Unable to download file from Telegram")?;
+        Ok(data)
+    }
+}
+
+pub fn new_bot_actions(bot: ConfBot) -> Arc<dyn BotActions> {
+    Arc::new(TeloxideBotActions { bot })
+}
+
+/// A call received by [`RecordingBotActions`], serialized with the Bot API method name.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "method", rename_all = "camelCase")]
+pub enum BotCall {
+    SendMessage {
+        chat_id: i64,
+        text: String,
+        reply_to_message_id: Option<i32>,
+        reply_markup: Option<serde_json::Value>,
+    },
+    EditMessageText {
+        chat_id: i64,
+        message_id: i32,
+        text: String,
+        reply_markup: Option<serde_json::Value>,
+    },
+    SendDocument {
+        chat_id: i64,
+        file_name: String,
+        size: usize,
+    },
+    DeleteMessage {
+        chat_id: i64,
+        message_id: i32,
+    },
+    RestrictChatMember {
+        chat_id: i64,
+        user_id: i64,
+        until_date: i64,
+    },
+    #[serde(rename = "restrictChatMember")]
+    LiftRestrictions {
+        chat_id: i64,
+        user_id: i64,
+    },
+    KickChatMember {
+        chat_id: i64,
+        user_id: i64,
+    },
+    UnpinChatMessage {
+        chat_id: i64,
+        message_id: i32,
+    },
+    AnswerCallbackQuery {
+        callback_query_id: String,
+        text: String,
+        show_alert: bool,
+    },
+}
+
+impl BotCall {
+    fn method(&self) -> &'static str {
+        match self {
+            BotCall::SendMessage { .. } => "sendMessage",
+            BotCall::EditMessageText { .. } => "editMessageText",
+            BotCall::SendDocument { .. } => "sendDocument",
+            BotCall::DeleteMessage { .. } => "deleteMessage",
+            BotCall::RestrictChatMember { .. } => "restrictChatMember",
+            BotCall::LiftRestrictions { .. } => "restrictChatMember",
+            BotCall::KickChatMember { .. } => "kickChatMember",
+            BotCall::UnpinChatMessage { .. } => "unpinChatMessage",
+            BotCall::AnswerCallbackQuery { .. } => "answerCallbackQuery",
+        }
+    }
+}
+
+/// In-memory [`BotActions`] that records every call instead of talking to Telegram.
+#[derive(Default)]
+pub struct RecordingBotActions {
+    admins: Vec<User>,
+    restricted_user_ids: HashSet<i64>,
+    failing_methods: HashSet<&'static str>,
+    api_errors: HashMap<&'static str, ApiError>,
+    files: HashMap<String, Vec<u8>>,
+    calls: Mutex<Vec<BotCall>>,
+    last_message_id: Mutex<i32>,
+    admin_lookups: Mutex<usize>,
+}
+
+impl RecordingBotActions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_admins(mut self, admins: Vec<User>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    pub fn take_calls(&self) -> Vec<BotCall> {
+        std::mem::take(&mut *self.calls.lock().unwrap())
+    }
+
+    fn record(&self, call: BotCall) -> Result<(), anyhow::Error> {
+        let method = call.method();
+        self.calls.lock().unwrap().push(call);
+        if let Some(kind) = self.api_errors.get(method) {
+            let err = RequestError::ApiError { kind: kind.clone(), status_code: reqwest::StatusCode::BAD_REQUEST };
+            return Err(anyhow::Error::new(err)).context("Telegram API error");
+        }
+        match self.failing_methods.contains(method) {
+            true => Err(anyhow::anyhow!("Telegram API error: {} is set to fail", method)),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl RecordingBotActions {
+    pub fn with_restricted(mut self, user_id: i64) -> Self {
+        self.restricted_user_ids.insert(user_id);
+        self
+    }
+
+    /// Makes every call of the Bot API `method` (e.g. `kickChatMember`) fail after being recorded.
+    pub fn with_failing(mut self, method: &'static str) -> Self {
+        self.failing_methods.insert(method);
+        self
+    }
+
+    /// Makes every call of `method` fail with the given Telegram API error, like the real bot would.
+    pub fn with_api_error(mut self, method: &'static str, kind: ApiError) -> Self {
+        self.api_errors.insert(method, kind);
+        self
+    }
+
+    pub fn with_file(mut self, file_id: &str, data: Vec<u8>) -> Self {
+        self.files.insert(file_id.to_string(), data);
+        self
+    }
+
+    pub fn calls(&self) -> Vec<BotCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// How many times the chat administrators were asked for. They aren't recorded as calls.
+    pub fn admin_lookups(&self) -> usize {
+        *self.admin_lookups.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl BotActions for RecordingBotActions {
+    async fn send_message(
+        &self,
+        chat_id: i64,
+        text: String,
+        reply_to_message_id: Option<i32>,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<i32, anyhow::Error> {
+        self.record(BotCall::SendMessage {
+            chat_id,
+            text,
+            reply_to_message_id,
+            reply_markup: reply_markup.map(|markup| serde_json::to_value(markup).unwrap_or_default()),
+        })?;
+        let mut last_message_id = self.last_message_id.lock().unwrap();
+        *last_message_id += 1;
+        Ok(*last_message_id)
+    }
+
+    async fn edit_message_text(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+        text: String,
+        reply_markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<(), anyhow::Error> {
+        self.record(BotCall::EditMessageText {
+            chat_id,
+            message_id,
+            text,
+            reply_markup: reply_markup.map(|markup| serde_json::to_value(markup).unwrap_or_default()),
+        })
+    }
+
+    async fn send_document(&self, chat_id: i64, file_name: String, data: Vec<u8>) -> Result<(), anyhow::Error> {
+        self.record(BotCall::SendDocument { chat_id, file_name, size: data.len() })
+    }
+
+    async fn delete_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error> {
+        self.record(BotCall::DeleteMessage { chat_id, message_id })
+    }
+
+    async fn restrict_chat_member(&self, chat_id: i64, user_id: i64, until_date: DateTime<Utc>)
+        -> Result<(), anyhow::Error> {
+        self.record(BotCall::RestrictChatMember { chat_id, user_id, until_date: until_date.timestamp() })
+    }
+
+    async fn lift_restrictions(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error> {
+        self.record(BotCall::LiftRestrictions { chat_id, user_id })
+    }
+
+    async fn kick_chat_member(&self, chat_id: i64, user_id: i64) -> Result<(), anyhow::Error> {
+        self.record(BotCall::KickChatMember { chat_id, user_id })
+    }
+
+    async fn unpin_chat_message(&self, chat_id: i64, message_id: i32) -> Result<(), anyhow::Error> {
+        self.record(BotCall::UnpinChatMessage { chat_id, message_id })
+    }
+
+    async fn get_chat_administrators(&self, _chat_id: i64) -> Result<Vec<User>, anyhow::Error> {
+        *self.admin_lookups.lock().unwrap() += 1;
+        Ok(self.admins.clone())
+    }
+
+    async fn is_chat_member_restricted(&self, _chat_id: i64, user_id: i64) -> Result<bool, anyhow::Error> {
+        Ok(self.restricted_user_ids.contains(&user_id))
+    }
+
+    async fn answer_callback_query(&self, callback_query_id: &str, text: String, show_alert: bool)
+        -> Result<(), anyhow::Error> {
+        self.record(BotCall::AnswerCallbackQuery {
+            callback_query_id: callback_query_id.to_string(),
+            text,
+            show_alert,
+        })
+    }
+
+    async fn download_file(&self, file_id: &str) -> Result<Vec<u8>, anyhow::Error> {
+        self.files
+            .get(file_id)
+            .cloned()
+            .context(format!("Unknown file {}", file_id))
+    }
+}
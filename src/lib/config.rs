@@ -1,16 +1,589 @@
-pub const STOP_WORDS_IN_LINK: [&str; 18] = [
+use crate::lib::{self, errors::ConfigError};
+use arc_swap::ArcSwap;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+const STOP_WORDS_IN_LINK: [&str; 18] = [
     "babes", "forsale", "girl", "jewelry", "nudit", "poker", "porn", "pron", "sex", "teen",
     "money", "free", "jwh", "cash", "xxx", "fuck", "devki", "devushki"
 ];
 
-pub const STOP_FULL_NAME_WORDS: [&str; 11] = [
+const STOP_FULL_NAME_WORDS: [&str; 11] = [
     "18+", "sex", "секс", "pron", "porn", "порн", "прон", "ставки", "betting", "знакомств",
     "dating"
 ];
 
-pub const POLITIC_WORDS: [&str; 11] = ["хохол", "нацист", "москал", "хохлят", "фашист", "салоед", "болбаш", "укроп", "нацик", "спецоперация", "кацап"];
-pub const INSULT_WORDS: [&str; 12] = ["дебил", "долбоёб", "долбоёб", "долбойоб", "дибил", "дебіл", "дібіл", "гондон", "гандон", "тупой", "биомусор", "хуесос"];
+const POLITIC_WORDS: [&str; 11] = ["хохол", "нацист", "москал", "хохлят", "фашист", "салоед", "болбаш", "укроп", "нацик", "спецоперация", "кацап"];
+const INSULT_WORDS: [&str; 12] = ["дебил", "долбоёб", "долбоёб", "долбойоб", "дибил", "дебіл", "дібіл", "гондон", "гандон", "тупой", "биомусор", "хуесос"];
+
+/// Everything the bot reads at startup. Loaded from `CONFIG_PATH` (`config.toml` by default, optional),
+/// then overridden by the env variables the bot used before the config file existed.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub telegram: TelegramConfig,
+    pub storage: StorageConfig,
+    pub http: HttpConfig,
+    pub webhook: WebhookConfig,
+    pub shutdown: ShutdownConfig,
+    /// Sections below are reloaded on SIGHUP.
+    pub rating: RatingConfig,
+    pub words: WordsConfig,
+    pub cas: CasConfig,
+    pub jobs: JobsConfig,
+    pub cache: CacheConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    pub token: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// `database` or `memory`.
+    pub backend: String,
+    /// Picks the database of the `database` backend by its scheme: `postgres://` (or `postgresql://`) or `sqlite:`.
+    /// When it's empty, Postgres is configured from `postgres`.
+    pub database_url: String,
+    pub postgres: PostgresConfig,
+    pub redis_url: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: "database".to_string(),
+            database_url: String::new(),
+            postgres: PostgresConfig::default(),
+            redis_url: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Database {
+    Postgres,
+    Sqlite,
+}
+
+impl StorageConfig {
+    pub fn database(&self) -> Result<Database, String> {
+        if self.database_url.is_empty() {
+            return Ok(Database::Postgres);
+        }
+        match self.database_url.split(':').next() {
+            Some("postgres") | Some("postgresql") => Ok(Database::Postgres),
+            Some("sqlite") => Ok(Database::Sqlite),
+            scheme => Err(format!(
+                "storage.database_url (DATABASE_URL): unsupported scheme {:?}, expected postgres://, postgresql:// \
+                or sqlite:",
+                scheme.unwrap_or_default(),
+            )),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub database: String,
+}
+
+impl Default for PostgresConfig {
+    fn default() -> Self {
+        PostgresConfig {
+            host: String::new(),
+            port: 5432,
+            user: String::new(),
+            password: String::new(),
+            database: String::new(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Serves metrics, health checks and the webhook.
+    pub address: SocketAddr,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig { address: SocketAddr::from(([0, 0, 0, 0], 8000)) }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Public HTTPS address Telegram sends updates to, e.g. `https://bot.example.com`.
+    /// Long polling is used when it's not set.
+    pub url: Option<String>,
+    pub path: String,
+    pub secret_token: Option<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            url: None,
+            path: "/webhook".to_string(),
+            secret_token: None,
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn public_url(&self) -> Option<String> {
+        self.url
+            .as_ref()
+            .map(|url| format!("{}/{}", url.trim_end_matches('/'), self.path.trim_start_matches('/')))
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// How long running handlers may take to finish after SIGTERM/SIGINT.
+    pub deadline_seconds: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { deadline_seconds: 10 }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RatingConfig {
+    /// Starting rating of a new chat member, multiplied by `admin_multiplier` for admins.
+    pub base: i32,
+    pub admin_multiplier: i32,
+    pub cooldown_seconds: i32,
+}
+
+impl Default for RatingConfig {
+    fn default() -> Self {
+        RatingConfig {
+            base: 100,
+            admin_multiplier: 5,
+            cooldown_seconds: 30,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WordsConfig {
+    pub link_stop_words: Vec<String>,
+    pub full_name_stop_words: Vec<String>,
+    pub politic_words: Vec<String>,
+    pub insult_words: Vec<String>,
+}
+
+impl Default for WordsConfig {
+    fn default() -> Self {
+        fn owned(words: &[&str]) -> Vec<String> {
+            words.iter().map(|word| word.to_string()).collect()
+        }
+        WordsConfig {
+            link_stop_words: owned(&STOP_WORDS_IN_LINK),
+            full_name_stop_words: owned(&STOP_FULL_NAME_WORDS),
+            politic_words: owned(&POLITIC_WORDS),
+            insult_words: owned(&INSULT_WORDS),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CasConfig {
+    pub enabled: bool,
+    pub api_url: String,
+    pub timeout_seconds: u64,
+}
+
+impl Default for CasConfig {
+    fn default() -> Self {
+        CasConfig {
+            enabled: true,
+            api_url: "https://api.cas.chat/check".to_string(),
+            timeout_seconds: 5,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    /// How often the worker looks for due jobs.
+    pub poll_interval_seconds: u64,
+    pub batch_size: i64,
+    /// A job that failed this many times is kept in the table with `failed_at` set and never run again.
+    pub max_attempts: i32,
+    /// Delay before the first retry, doubled with every attempt up to `max_backoff_seconds`.
+    pub backoff_seconds: i64,
+    pub max_backoff_seconds: i64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            poll_interval_seconds: 1,
+            batch_size: 50,
+            max_attempts: 5,
+            backoff_seconds: 5,
+            max_backoff_seconds: 600,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long the administrators of a chat are cached. Promotions and demotions show up after that.
+    pub admins_ttl_seconds: u64,
+    /// A member caught by the name check is reported to the admins once in this time, not for every message.
+    pub reports_ttl_seconds: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { admins_ttl_seconds: 60, reports_ttl_seconds: 86400 }
+    }
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`, for the bot's own modules.
+    pub level: String,
+    /// Levels of other modules by path, e.g. `teloxide = "info"`.
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "trace".to_string(),
+            modules: vec![("teloxide".to_string(), "info".to_string())].into_iter().collect(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var("CONFIG_PATH").ok().filter(|path| !path.is_empty());
+        let content = match &path {
+            Some(path) => Some(
+                std::fs::read_to_string(path).map_err(|err| ConfigError::Read(path.clone(), err))?
+            ),
+            None => std::fs::read_to_string(DEFAULT_CONFIG_PATH).ok(),
+        };
+        let mut config = match content {
+            Some(content) => Config::from_toml(&content).map_err(|err| ConfigError::Parse(
+                path.unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string()),
+                err,
+            ))?,
+            None => Config::default(),
+        };
+
+        let mut errors = config.apply_env_overrides();
+        errors.extend(config.validate());
+        match errors.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError::Invalid(errors)),
+        }
+    }
+
+    /// Returns the env variables that couldn't be parsed.
+    fn apply_env_overrides(&mut self) -> Vec<String> {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name).ok().filter(|value| !value.is_empty())
+        }
+        fn parse<T: FromStr>(name: &str, target: &mut T, errors: &mut Vec<String>) {
+            if let Some(value) = var(name) {
+                match value.parse() {
+                    Ok(parsed) => *target = parsed,
+                    Err(_) => errors.push(format!("{}: cannot parse {:?}", name, value)),
+                }
+            }
+        }
+
+        let mut errors = vec![];
+        parse("TELOXIDE_TOKEN", &mut self.telegram.token, &mut errors);
+        parse("STORAGE_BACKEND", &mut self.storage.backend, &mut errors);
+        parse("DATABASE_URL", &mut self.storage.database_url, &mut errors);
+        parse("POSTGRES_HOST", &mut self.storage.postgres.host, &mut errors);
+        parse("POSTGRES_PORT", &mut self.storage.postgres.port, &mut errors);
+        parse("POSTGRES_USER", &mut self.storage.postgres.user, &mut errors);
+        parse("POSTGRES_PASSWORD", &mut self.storage.postgres.password, &mut errors);
+        parse("POSTGRES_DB", &mut self.storage.postgres.database, &mut errors);
+        parse("REDIS_URL", &mut self.storage.redis_url, &mut errors);
+        parse("HTTP_ADDRESS", &mut self.http.address, &mut errors);
+        parse("WEBHOOK_PATH", &mut self.webhook.path, &mut errors);
+        parse("LOG_LEVEL", &mut self.logging.level, &mut errors);
+        if let Some(url) = var("WEBHOOK_URL") {
+            self.webhook.url = Some(url);
+        }
+        if let Some(token) = var("WEBHOOK_SECRET_TOKEN") {
+            self.webhook.secret_token = Some(token);
+        }
+        errors
+    }
+
+    /// Every problem at once, prefixed with the config key it's about.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        match self.storage.backend.as_str() {
+            "database" => match self.storage.database() {
+                Ok(Database::Sqlite) => {}
+                Ok(Database::Postgres) => {
+                    let postgres = &self.storage.postgres;
+                    let mut required = vec![("storage.redis_url (REDIS_URL)", &self.storage.redis_url)];
+                    if self.storage.database_url.is_empty() {
+                        required.extend([
+                            ("storage.postgres.host (POSTGRES_HOST)", &postgres.host),
+                            ("storage.postgres.user (POSTGRES_USER)", &postgres.user),
+                            ("storage.postgres.database (POSTGRES_DB)", &postgres.database),
+                        ]);
+                    }
+                    for (key, value) in required {
+                        if value.is_empty() {
+                            errors.push(format!("{} is required for Postgres", key));
+                        }
+                    }
+                }
+                Err(err) => errors.push(err),
+            },
+            "memory" => {}
+            backend => errors.push(unknown_backend_error(backend)),
+        }
+
+        if let Some(url) = &self.webhook.url {
+            if !url.starts_with("https://") {
+                errors.push("webhook.url (WEBHOOK_URL) must be an https:// address".to_string());
+            }
+        }
+        if !self.webhook.path.starts_with('/') {
+            errors.push("webhook.path (WEBHOOK_PATH) must start with /".to_string());
+        }
+        if let Some(token) = &self.webhook.secret_token {
+            let is_valid_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+            if !(1..=256).contains(&token.len()) || !token.chars().all(is_valid_char) {
+                errors.push(
+                    "webhook.secret_token (WEBHOOK_SECRET_TOKEN) must be 1-256 characters of A-Z, a-z, 0-9, _ and -"
+                        .to_string()
+                );
+            }
+        }
+
+        if self.shutdown.deadline_seconds == 0 {
+            errors.push("shutdown.deadline_seconds must be positive".to_string());
+        }
+        if self.rating.base <= 0 {
+            errors.push("rating.base must be positive".to_string());
+        }
+        if self.rating.admin_multiplier < 1 {
+            errors.push("rating.admin_multiplier must be at least 1".to_string());
+        }
+        if self.rating.cooldown_seconds <= 0 {
+            errors.push("rating.cooldown_seconds must be positive".to_string());
+        }
+        if self.cas.api_url.is_empty() {
+            errors.push("cas.api_url must not be empty".to_string());
+        }
+        if self.cas.timeout_seconds == 0 {
+            errors.push("cas.timeout_seconds must be positive".to_string());
+        }
+        if self.jobs.poll_interval_seconds == 0 {
+            errors.push("jobs.poll_interval_seconds must be positive".to_string());
+        }
+        if self.jobs.batch_size <= 0 {
+            errors.push("jobs.batch_size must be positive".to_string());
+        }
+        if self.jobs.max_attempts < 1 {
+            errors.push("jobs.max_attempts must be at least 1".to_string());
+        }
+        if self.jobs.backoff_seconds <= 0 || self.jobs.max_backoff_seconds < self.jobs.backoff_seconds {
+            errors.push("jobs.backoff_seconds must be positive and not above jobs.max_backoff_seconds".to_string());
+        }
+        if self.cache.admins_ttl_seconds == 0 {
+            errors.push("cache.admins_ttl_seconds must be positive".to_string());
+        }
+        if self.cache.reports_ttl_seconds == 0 {
+            errors.push("cache.reports_ttl_seconds must be positive".to_string());
+        }
+        if log::LevelFilter::from_str(&self.logging.level).is_err() {
+            errors.push(format!(
+                "logging.level (LOG_LEVEL): unknown level {:?}, expected off, error, warn, info, debug or trace",
+                self.logging.level
+            ));
+        }
+        for (module, level) in self.logging.modules.iter() {
+            if log::LevelFilter::from_str(level).is_err() {
+                errors.push(format!(
+                    "logging.modules.{}: unknown level {:?}, expected off, error, warn, info, debug or trace",
+                    module, level
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// Postgres and SQLite are both the `database` backend, told apart by `database_url`.
+pub const STORAGE_BACKENDS: [&str; 2] = ["database", "memory"];
+
+pub fn unknown_backend_error(backend: &str) -> String {
+    format!(
+        "storage.backend (STORAGE_BACKEND): unknown backend {:?}, expected one of: {}",
+        backend,
+        STORAGE_BACKENDS.join(", "),
+    )
+}
+
+/// The configuration in effect, handed to the services that read it. [`reload`] swaps it in place,
+/// so load it where it's used instead of keeping a copy.
+pub type SharedConfig = Arc<ArcSwap<Config>>;
+
+pub fn shared(config: Config) -> SharedConfig {
+    Arc::new(ArcSwap::from_pointee(config))
+}
+
+/// Loads the configuration once at startup and sets up logging, reporting every invalid value and exiting on errors.
+pub fn init() -> SharedConfig {
+    match Config::load() {
+        Ok(config) => {
+            lib::logging::init(&config.logging);
+            shared(config)
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Re-reads the configuration and swaps in the reloadable sections.
+/// Tokens, storage, HTTP, webhook and shutdown settings keep their startup values until restart.
+pub fn reload(config: &SharedConfig) -> Result<(), ConfigError> {
+    let loaded = Config::load()?;
+    let current = config.load();
+    let reloaded = Config {
+        telegram: current.telegram.clone(),
+        storage: current.storage.clone(),
+        http: current.http.clone(),
+        webhook: current.webhook.clone(),
+        shutdown: current.shutdown.clone(),
+        rating: loaded.rating,
+        words: loaded.words,
+        cas: loaded.cas,
+        jobs: loaded.jobs,
+        cache: loaded.cache,
+        logging: loaded.logging,
+    };
+    lib::logging::apply(&reloaded.logging);
+    config.store(Arc::new(reloaded));
+    Ok(())
+}
+
+/// Reloads the configuration on every SIGHUP, keeping the old one when the new one is invalid.
+#[cfg(unix)]
+pub async fn reload_on_sighup(config: SharedConfig) {
+    let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            log::error!("Unable to listen for SIGHUP, config reload is disabled: {}", err);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        match reload(&config) {
+            Ok(_) => log::info!("Configuration reloaded"),
+            Err(err) => log::error!("Keeping the previous configuration: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_file_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.rating.base, 100);
+        assert_eq!(config.rating.cooldown_seconds, 30);
+        assert_eq!(config.webhook.path, "/webhook");
+        assert!(config.words.politic_words.contains(&"кацап".to_string()));
+    }
+
+    #[test]
+    fn example_file_parses() {
+        let config = Config::from_toml(include_str!("../../deployment/config.example.toml")).unwrap();
+        assert_eq!(config.logging.modules.get("teloxide").map(String::as_str), Some("info"));
+    }
+
+    #[test]
+    fn validation_reports_every_invalid_value() {
+        let config = Config::from_toml(r#"
+            [storage]
+            backend = "mongo"
+
+            [rating]
+            base = 0
+
+            [logging]
+            level = "loud"
+        "#).unwrap();
+        let errors = config.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("storage.backend"));
+        assert!(errors[0].ends_with("expected one of: database, memory"));
+        assert!(errors[1].starts_with("rating.base"));
+        assert!(errors[2].starts_with("logging.level"));
+    }
+
+    #[test]
+    fn webhook_secret_token_must_not_be_empty() {
+        let config = Config::from_toml("[storage]\nbackend = \"memory\"\n[webhook]\nsecret_token = \"\"").unwrap();
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("webhook.secret_token"));
+    }
+
+    #[test]
+    fn database_is_chosen_by_url_scheme() {
+        let storage = |database_url: &str| StorageConfig { database_url: database_url.to_string(), ..Default::default() };
+        assert_eq!(storage("").database(), Ok(Database::Postgres));
+        assert_eq!(storage("postgres://bot@db/comparty").database(), Ok(Database::Postgres));
+        assert_eq!(storage("postgresql://bot@db/comparty").database(), Ok(Database::Postgres));
+        assert_eq!(storage("sqlite:comparty.db").database(), Ok(Database::Sqlite));
+        assert!(storage("mysql://bot@db/comparty").database().unwrap_err().contains("\"mysql\""));
+    }
 
-pub const BASE_RATING: i32 = 100;
-pub const BASE_RATING_ADMIN_MULTIPLIER: i32 = 5;
-pub const RATING_COOLDOWN: i32 = 30;
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err = Config::from_toml("[rating]\ncooldown = 10").unwrap_err();
+        assert!(err.to_string().contains("unknown field `cooldown`"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::str::FromStr;
+
+/// Member commands whose use each chat configures with a command policy. A new member command is added here
+/// and checked with `UpdateContext::can_use` in the handler.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Command {
+    Help,
+    Report,
+    Me,
+    Top,
+    RatingCategories,
+    RatingOptout,
+    RatingOptin,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    /// Accepts the name with or without the leading `!`.
+    fn from_str(input: &str) -> Result<Command, Self::Err> {
+        let name = input.strip_prefix('!').unwrap_or(input);
+        Command::ALL
+            .iter()
+            .find(|command| command.name() == name)
+            .copied()
+            .ok_or_else(|| anyhow::Error::msg(format!("Unknown command: {}", input)))
+    }
+}
+
+impl Command {
+    pub const ALL: [Command; 7] = [
+        Command::Help,
+        Command::Report,
+        Command::Me,
+        Command::Top,
+        Command::RatingCategories,
+        Command::RatingOptout,
+        Command::RatingOptin,
+    ];
+
+    /// The command without the leading `!`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Help => "help",
+            Command::Report => "report",
+            Command::Me => "me",
+            Command::Top => "top",
+            Command::RatingCategories => "rating_categories",
+            Command::RatingOptout => "rating_optout",
+            Command::RatingOptin => "rating_optin",
+        }
+    }
+}
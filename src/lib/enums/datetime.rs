@@ -1,5 +1,5 @@
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 const DAY_TIME_UNIT: &str = "d";
 const HOUR_TIME_UNIT: &str = "h";
@@ -25,8 +25,10 @@ impl FromStr for TimeUnits {
     fn from_str(input: &str) -> Result<TimeUnits, Self::Err> {
         let regexp = &format!(r"^(\d+)([{}])+$", TIME_UNITS.join(","));
         let re = regex::Regex::new(regexp).unwrap();
-        let substring = re.captures(input).unwrap();
-        let amount: i32 = substring[1].parse().unwrap();
+        let substring = re
+            .captures(input)
+            .ok_or_else(|| anyhow::Error::msg(format!("Unable parse time: {}", input)))?;
+        let amount: i32 = substring[1].parse()?;
         let unit: &str = &substring[2];
         match unit {
             DAY_TIME_UNIT => Ok(TimeUnits::Day(amount)),
@@ -38,7 +40,29 @@ impl FromStr for TimeUnits {
     }
 }
 
+impl fmt::Display for TimeUnits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            TimeUnits::Day(amount) => write!(f, "{}{}", amount, DAY_TIME_UNIT),
+            TimeUnits::Hour(amount) => write!(f, "{}{}", amount, HOUR_TIME_UNIT),
+            TimeUnits::Minute(amount) => write!(f, "{}{}", amount, MINUTE_TIME_UNIT),
+            TimeUnits::Second(amount) => write!(f, "{}{}", amount, SECOND_TIME_UNIT),
+        }
+    }
+}
+
 impl TimeUnits {
+    /// The largest unit holding `seconds` exactly, so `5400` is shown as `90m`.
+    pub fn from_seconds(seconds: i32) -> TimeUnits {
+        match seconds {
+            0 => TimeUnits::Second(0),
+            _ if seconds % 86400 == 0 => TimeUnits::Day(seconds / 86400),
+            _ if seconds % 3600 == 0 => TimeUnits::Hour(seconds / 3600),
+            _ if seconds % 60 == 0 => TimeUnits::Minute(seconds / 60),
+            _ => TimeUnits::Second(seconds),
+        }
+    }
+
     pub fn to_duration(&self) -> Duration {
         match &self {
             TimeUnits::Day(amount) => chrono::Duration::days(*amount as i64),
@@ -48,15 +72,6 @@ impl TimeUnits {
         }
     }
 
-    pub fn to_string(&self) -> String {
-        match &self {
-            TimeUnits::Day(amount) => format!("{}{}", amount, DAY_TIME_UNIT),
-            TimeUnits::Hour(amount) => format!("{}{}", amount, HOUR_TIME_UNIT),
-            TimeUnits::Minute(amount) => format!("{}{}", amount, MINUTE_TIME_UNIT),
-            TimeUnits::Second(amount) => format!("{}{}", amount, SECOND_TIME_UNIT),
-        }
-    }
-
     pub fn to_expire_date(&self, date: i64) -> DateTime<Utc> {
         let current_timestamp = NaiveDateTime::from_timestamp(date, 0);
         DateTime::<Utc>::from_utc(current_timestamp, Utc) + self.to_duration()
@@ -0,0 +1,75 @@
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+pub enum FileFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for FileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<FileFormat, Self::Err> {
+        match input {
+            "csv" => Ok(FileFormat::Csv),
+            "json" => Ok(FileFormat::Json),
+            _ => Err(anyhow::Error::msg(format!("Unknown file format: {}", input))),
+        }
+    }
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Json => "json",
+        }
+    }
+
+    pub fn detect(file_name: Option<&str>, data: &[u8]) -> FileFormat {
+        let extension = file_name.and_then(|name| name.rsplit('.').next());
+        match extension.map(|extension| FileFormat::from_str(&extension.to_lowercase())) {
+            Some(Ok(format)) => format,
+            _ => match data.iter().find(|byte| !byte.is_ascii_whitespace()) {
+                Some(b'[') => FileFormat::Json,
+                _ => FileFormat::Csv,
+            },
+        }
+    }
+
+    pub fn serialize<T: serde::Serialize>(&self, records: &[T]) -> Result<Vec<u8>, anyhow::Error> {
+        match self {
+            FileFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(vec![]);
+                for record in records {
+                    writer.serialize(record)?;
+                }
+                writer.into_inner().map_err(|err| anyhow::Error::msg(err.to_string()))
+            }
+            FileFormat::Json => serde_json::to_vec_pretty(records).map_err(anyhow::Error::new),
+        }
+    }
+
+    /// Deserializes every record on its own, so a malformed row does not discard the whole file.
+    pub fn deserialize<T: serde::de::DeserializeOwned>(
+        &self,
+        data: &[u8],
+    ) -> Result<Vec<Result<T, anyhow::Error>>, anyhow::Error> {
+        match self {
+            FileFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data);
+                Ok(reader
+                    .deserialize()
+                    .map(|record| record.map_err(anyhow::Error::new))
+                    .collect())
+            }
+            FileFormat::Json => {
+                let values: Vec<serde_json::Value> = serde_json::from_slice(data)?;
+                Ok(values
+                    .into_iter()
+                    .map(|value| serde_json::from_value(value).map_err(anyhow::Error::new))
+                    .collect())
+            }
+        }
+    }
+}
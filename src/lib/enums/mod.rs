@@ -1,4 +1,14 @@
+mod command;
 mod datetime;
+mod file_format;
+mod moderation_action;
+mod moderator_role;
 mod rating;
+mod reply_category;
+pub use command::Command;
 pub use datetime::TimeUnits;
-pub use rating::RatingTriggers;
+pub use file_format::FileFormat;
+pub use moderation_action::ModerationAction;
+pub use moderator_role::{ModeratorRole, Permission};
+pub use rating::{RatingCategoryTriggers, RatingTriggers};
+pub use reply_category::ReplyCategory;
@@ -0,0 +1,28 @@
+use std::str::FromStr;
+
+/// What the bot does with a message or member caught by a moderation check. Each check allows only some of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModerationAction {
+    /// Bans the member, deleting the message when there's one.
+    Ban,
+    /// Deletes the message and keeps the member.
+    Delete,
+    /// Keeps everything and calls the chat administrators.
+    Report,
+    /// Replies with a lecture.
+    Warn,
+}
+
+impl FromStr for ModerationAction {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<ModerationAction, Self::Err> {
+        match input {
+            "ban" => Ok(ModerationAction::Ban),
+            "delete" => Ok(ModerationAction::Delete),
+            "report" => Ok(ModerationAction::Report),
+            "warn" => Ok(ModerationAction::Warn),
+            _ => Err(anyhow::Error::msg(format!("Unknown moderation action: {}", input))),
+        }
+    }
+}
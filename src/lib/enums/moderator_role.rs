@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+/// What a bot-level role allows. Telegram administrators of the chat have every permission.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    /// `!warn`.
+    Warn,
+    /// `!ro`.
+    Mute,
+    /// `!ban`.
+    Ban,
+    /// `!settings`, `!get`, `!set`, `!auto_delete` and rating categories.
+    ChangeSettings,
+    /// `!grep`, the reputation of a member across every chat the bot is in.
+    ViewGlobalReputation,
+    /// `!import` and `!export` of the chat ratings.
+    ImportExport,
+    /// Cancelling a rating change of another member with the cancel button.
+    AdjustRating,
+}
+
+/// A role granted in a chat with `!promote`, independent of the Telegram administrator status.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModeratorRole {
+    /// Every permission, and may grant the other roles.
+    Owner,
+    Moderator,
+    Helper,
+}
+
+impl FromStr for ModeratorRole {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<ModeratorRole, Self::Err> {
+        ModeratorRole::ALL
+            .iter()
+            .find(|role| role.name() == input)
+            .copied()
+            .ok_or_else(|| anyhow::Error::msg(format!("Unknown moderator role: {}", input)))
+    }
+}
+
+impl ModeratorRole {
+    pub const ALL: [ModeratorRole; 3] = [ModeratorRole::Owner, ModeratorRole::Moderator, ModeratorRole::Helper];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModeratorRole::Owner => "owner",
+            ModeratorRole::Moderator => "moderator",
+            ModeratorRole::Helper => "helper",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ModeratorRole::Owner => "Владелец",
+            ModeratorRole::Moderator => "Модератор",
+            ModeratorRole::Helper => "Помощник",
+        }
+    }
+
+    /// The permission matrix.
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            ModeratorRole::Owner => &[
+                Permission::Warn,
+                Permission::Mute,
+                Permission::Ban,
+                Permission::ChangeSettings,
+                Permission::ViewGlobalReputation,
+                Permission::ImportExport,
+                Permission::AdjustRating,
+            ],
+            ModeratorRole::Moderator => &[
+                Permission::Warn,
+                Permission::Mute,
+                Permission::Ban,
+                Permission::ViewGlobalReputation,
+                Permission::ImportExport,
+                Permission::AdjustRating,
+            ],
+            ModeratorRole::Helper => &[Permission::Warn, Permission::Mute],
+        }
+    }
+
+    pub fn permits(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
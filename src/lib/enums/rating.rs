@@ -34,7 +34,25 @@ impl FromStr for RatingTriggers {
     }
 }
 
+pub trait RatingCategoryTriggers {
+    fn triggers(&self) -> &[String];
+}
+
 impl RatingTriggers {
+    pub fn from_str_with_categories<'a, C: RatingCategoryTriggers>(
+        input: &str,
+        categories: &'a [C],
+    ) -> Result<(RatingTriggers, Option<&'a C>), ()> {
+        let lowercased_input = input.to_lowercase();
+        let category = categories
+            .iter()
+            .find(|category| category.triggers().iter().any(|trigger| trigger.to_lowercase() == lowercased_input));
+        match category {
+            Some(category) => Ok((RatingTriggers::Increase(None), Some(category))),
+            None => RatingTriggers::from_str(input).map(|rating_trigger| (rating_trigger, None)),
+        }
+    }
+
     pub fn get_sign(&self) -> char {
         match self {
             Self::Increase(_) => '+',
@@ -69,3 +87,24 @@ impl RatingTriggers {
         Err(format!("Пользователь с негативным рейтингом не имеет право изменять чужой (рейтинг: {})", user_rating_amount))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Category(Vec<String>);
+
+    impl RatingCategoryTriggers for Category {
+        fn triggers(&self) -> &[String] {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn category_triggers_match_regardless_of_case() {
+        let categories = [Category(vec!["Респект".to_string()])];
+        let (trigger, category) = RatingTriggers::from_str_with_categories("РЕСПЕКТ", &categories).unwrap();
+        assert_eq!(trigger.get_sign(), '+');
+        assert!(category.is_some());
+    }
+}
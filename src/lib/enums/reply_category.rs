@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+/// Kinds of bot replies, each with its own auto-delete setting per chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplyCategory {
+    /// `!help`
+    Help,
+    /// `!me`, `!top`, `!grep`, rating categories and other lookups.
+    Info,
+    /// `!settings` and settings changes.
+    Settings,
+    /// Politics and insult lectures.
+    Warning,
+    /// Bans, mutes, reports and alerts for the admins.
+    Moderation,
+    /// Rating vote confirmations.
+    Rating,
+    /// Invalid commands and failed actions.
+    Error,
+}
+
+impl FromStr for ReplyCategory {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<ReplyCategory, Self::Err> {
+        ReplyCategory::ALL
+            .iter()
+            .find(|category| category.name() == input)
+            .copied()
+            .ok_or_else(|| anyhow::Error::msg(format!("Unknown reply category: {}", input)))
+    }
+}
+
+impl ReplyCategory {
+    pub const ALL: [ReplyCategory; 7] = [
+        ReplyCategory::Help,
+        ReplyCategory::Info,
+        ReplyCategory::Settings,
+        ReplyCategory::Warning,
+        ReplyCategory::Moderation,
+        ReplyCategory::Rating,
+        ReplyCategory::Error,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplyCategory::Help => "help",
+            ReplyCategory::Info => "info",
+            ReplyCategory::Settings => "settings",
+            ReplyCategory::Warning => "warning",
+            ReplyCategory::Moderation => "moderation",
+            ReplyCategory::Rating => "rating",
+            ReplyCategory::Error => "error",
+        }
+    }
+
+    /// TTL in seconds (0 keeps the replies) and whether the triggering message is deleted too,
+    /// until a chat changes them. Rating confirmations are removed together with the vote after a minute.
+    pub fn default_auto_delete(&self) -> (i32, bool) {
+        match self {
+            ReplyCategory::Rating => (60, true),
+            _ => (0, false),
+        }
+    }
+}
@@ -26,6 +26,24 @@ pub enum ChatError {
     ChangeSettings(#[source] anyhow::Error),
     #[error("{0}")]
     MigrateChat(#[source] anyhow::Error),
+    #[error("{0}")]
+    GetAutoDelete(#[source] anyhow::Error),
+    #[error("{0}")]
+    SetAutoDelete(#[source] anyhow::Error),
+    #[error("{0}")]
+    GetAdmins(#[source] anyhow::Error),
+    #[error("{0}")]
+    SettingsPanel(#[source] anyhow::Error),
+    #[error("{0}")]
+    ChangeActive(#[source] anyhow::Error),
+    #[error("{0}")]
+    WarnMissingRights(#[source] anyhow::Error),
+    #[error("{0}")]
+    GetRole(#[source] anyhow::Error),
+    #[error("{0}")]
+    ChangeRole(#[source] anyhow::Error),
+    #[error("{0}")]
+    CommandCooldown(#[source] anyhow::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -48,12 +66,18 @@ pub enum AdminCommandsControllerError {
     ChangeSettings(#[source] anyhow::Error),
     #[error("{0}")]
     GetSettings(#[source] anyhow::Error),
+    #[error("{0}")]
+    WarnUser(#[source] anyhow::Error),
+    #[error("{0}")]
+    ChangeRole(#[source] anyhow::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum MessageError {
     #[error("{0}")]
     GetCASStatus(#[source] anyhow::Error),
+    #[error("{0}")]
+    StartReport(#[source] anyhow::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -73,6 +97,18 @@ pub enum UserError {
     #[error("{0}")]
     RepeatingRequestDuringCooldown(String),
     #[error("{0}")]
+    VoterNotEligible(String),
+    #[error("{0}")]
+    ChangeRatingOptout(#[source] anyhow::Error),
+    #[error("{0}")]
+    ChangeMembership(#[source] anyhow::Error),
+    #[error("{0}")]
+    Export(#[source] anyhow::Error),
+    #[error("{0}")]
+    Import(#[source] anyhow::Error),
+    #[error("{0}")]
+    CountMessage(#[source] anyhow::Error),
+    #[error("{0}")]
     GetRating(#[source] anyhow::Error),
     #[error("{0}")]
     GetInfo(#[source] anyhow::Error),
@@ -83,5 +119,35 @@ pub enum UserError {
     #[error("{0}")]
     DeleteRating(#[source] anyhow::Error),
     #[error("{0}")]
-    Validation(#[source] anyhow::Error)
+    Validation(#[source] anyhow::Error),
+    #[error("{0}")]
+    GetRatingCategories(#[source] anyhow::Error),
+    #[error("{0}")]
+    SaveRatingCategory(#[source] anyhow::Error),
+    #[error("{0}")]
+    DeleteRatingCategory(#[source] anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum JobError {
+    #[error("{0}")]
+    Schedule(#[source] anyhow::Error),
+    #[error("{0}")]
+    Claim(#[source] anyhow::Error),
+    #[error("{0}")]
+    Complete(#[source] anyhow::Error),
+    #[error("{0}")]
+    Retry(#[source] anyhow::Error),
+    #[error("{0}")]
+    CountPending(#[source] anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Unable to read config file {0}: {1}")]
+    Read(String, #[source] std::io::Error),
+    #[error("Invalid config file {0}: {1}")]
+    Parse(String, #[source] toml::de::Error),
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
 }
@@ -1,48 +1,37 @@
-use anyhow::Context;
-use teloxide::prelude::Requester;
 use crate::lib;
-
-pub async fn is_admin(cx: &lib::types::MessageContext) -> Result<bool, anyhow::Error> {
-    let admins = cx.requester
-        .get_chat_administrators(cx.update.chat_id())
-        .await
-        .map_err(anyhow::Error::new)
-        .context("Failed to fetch chat admins")?;
-    Ok(admins.iter().any(|admin| cx.update.from().unwrap().id == admin.user.id))
-}
+use std::convert::TryFrom;
 
 pub fn find_url(text: &str) -> Result<String, &str> {
     let regexp = r"(http(s)?://.)?(www\.)?[-a-zA-Z0-9@:%._\+~#=]{2,256}\.[a-z]{2,6}\b([-a-zA-Z0-9@:%_\+.~#?&//=]*)";
     let re = regex::Regex::new(regexp).unwrap();
 
-    for substring in re.captures_iter(text) {
-        let url = &substring[0];
-        return Ok(String::from(url));
+    match re.find(text) {
+        Some(url) => Ok(String::from(url.as_str())),
+        None => Err("No URLs found"),
     }
-    Err("No URLs found")
 }
 
-pub fn check_is_full_name_clean(full_name: String) -> bool {
-    lib::config::STOP_FULL_NAME_WORDS
+pub fn check_is_full_name_clean(full_name: String, words: &lib::config::WordsConfig) -> bool {
+    words.full_name_stop_words
         .iter()
-        .any(|word| full_name.contains(word))
+        .any(|word| full_name.contains(word.as_str()))
 }
 
-pub fn check_is_politics_in_text(text: String) -> bool {
-    lib::config::POLITIC_WORDS
+pub fn check_is_politics_in_text(text: String, words: &lib::config::WordsConfig) -> bool {
+    words.politic_words
         .iter()
-        .any(|word| text.contains(word))
+        .any(|word| text.contains(word.as_str()))
 }
 
-pub fn check_is_insult_in_text(text: String) -> bool {
-    lib::config::INSULT_WORDS
+pub fn check_is_insult_in_text(text: String, words: &lib::config::WordsConfig) -> bool {
+    words.insult_words
         .iter()
-        .any(|word| text.contains(word))
+        .any(|word| text.contains(word.as_str()))
 }
 
-pub fn check_link_in_text(text: &str) -> Result<String, ()> {
+pub fn check_link_in_text(text: &str, words: &lib::config::WordsConfig) -> Result<String, ()> {
     let link = find_url(text).map_err(|_| {})?;
-    if lib::config::STOP_WORDS_IN_LINK.iter().any(|word| link.contains(word)) || link.len() <= 6 {
+    if words.link_stop_words.iter().any(|word| link.contains(word.as_str())) || link.len() <= 6 {
         return Ok(link);
     }
     Err(())
@@ -57,8 +46,24 @@ pub fn chars_to_float(chars: &[char]) -> Option<f64> {
         .map(|r: f64| math::round::floor(r, 2))
 }
 
-pub fn get_envvar(env: &'static str) -> String {
-    std::env::var(env).unwrap_or_else(|_| panic!("Cannot get the {} env variable!", env))
+/// Rounds half away from zero to cents. `BigDecimal::round` panics on amounts that don't fit into `i128`,
+/// which sums of converted `f64` vote powers easily exceed.
+pub fn round_amount(amount: sqlx::types::BigDecimal) -> sqlx::types::BigDecimal {
+    let half_cent = sqlx::types::BigDecimal::new(5.into(), 3);
+    if amount < sqlx::types::BigDecimal::from(0) {
+        (amount - half_cent).with_scale(2)
+    } else {
+        (amount + half_cent).with_scale(2)
+    }
+}
+
+/// SQLite has no decimal type, so amounts are stored as `real` and rounded back to cents.
+pub fn decimal_from_f64(amount: f64) -> sqlx::types::BigDecimal {
+    round_amount(sqlx::types::BigDecimal::try_from(amount).unwrap_or_default())
+}
+
+pub fn f64_from_decimal(amount: &sqlx::types::BigDecimal) -> f64 {
+    amount.to_string().parse().unwrap_or_default()
 }
 
 pub fn bool_to_string_switch(item: bool) -> &'static str {
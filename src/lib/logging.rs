@@ -0,0 +1,62 @@
+use crate::lib::config::LoggingConfig;
+use arc_swap::ArcSwap;
+use lazy_static::lazy_static;
+use pretty_env_logger::env_logger;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Hands every record to a logger built from `[logging]`. `log` only takes one logger per process,
+/// so a reload builds a new one with the new module filters and swaps it in.
+struct ReloadableLogger {
+    inner: ArcSwap<env_logger::Logger>,
+}
+
+impl log::Log for ReloadableLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.load().enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.inner.load().log(record)
+    }
+
+    fn flush(&self) {
+        self.inner.load().flush()
+    }
+}
+
+lazy_static! {
+    static ref LOGGER: ReloadableLogger = ReloadableLogger {
+        inner: ArcSwap::from_pointee(build(&LoggingConfig::default())),
+    };
+}
+
+fn level(value: &str) -> log::LevelFilter {
+    log::LevelFilter::from_str(value).unwrap_or(log::LevelFilter::Trace)
+}
+
+fn build(config: &LoggingConfig) -> env_logger::Logger {
+    let mut builder = pretty_env_logger::formatted_builder();
+    builder
+        .write_style(env_logger::WriteStyle::Auto)
+        .filter(Some(&env!("CARGO_PKG_NAME").replace("-", "_")), level(&config.level));
+    for (module, module_level) in config.modules.iter() {
+        builder.filter(Some(module), level(module_level));
+    }
+    builder.build()
+}
+
+/// Installs the logger, called once at startup.
+pub fn init(config: &LoggingConfig) {
+    if log::set_logger(&*LOGGER).is_err() {
+        log::warn!("Logger is already installed");
+    }
+    apply(config);
+}
+
+/// Replaces the bot level and the module filters.
+pub fn apply(config: &LoggingConfig) {
+    let logger = build(config);
+    log::set_max_level(logger.filter());
+    LOGGER.inner.store(Arc::new(logger));
+}
@@ -0,0 +1,62 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec, Encoder, Histogram,
+    HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+
+lazy_static! {
+    pub static ref UPDATES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "comparty_updates_total",
+        "Updates processed, by handler and result",
+        &["handler", "result"]
+    ).unwrap();
+
+    /// `action` is `ban`, `mute`, `warn`, `delete` or `report`,
+    /// `reason` is `cas`, `link`, `name`, `insult`, `politics` or `manual`.
+    pub static ref MODERATION_ACTIONS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "comparty_moderation_actions_total",
+        "Users banned, muted, warned or reported and messages deleted by the bot, by action and reason",
+        &["action", "reason"]
+    ).unwrap();
+
+    pub static ref RATING_VOTES_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "comparty_rating_votes_total",
+        "Rating votes recorded, by direction",
+        &["direction"]
+    ).unwrap();
+
+    pub static ref RATING_COOLDOWN_REJECTIONS_TOTAL: IntCounter = register_int_counter!(
+        "comparty_rating_cooldown_rejections_total",
+        "Rating votes rejected because the voter is on cooldown"
+    ).unwrap();
+
+    pub static ref TELEGRAM_API_ERRORS_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "comparty_telegram_api_errors_total",
+        "Failed Telegram Bot API calls, by method",
+        &["method"]
+    ).unwrap();
+
+    pub static ref POSTGRES_QUERY_SECONDS: HistogramVec = register_histogram_vec!(
+        "comparty_postgres_query_duration_seconds",
+        "Postgres query latency, by repository method",
+        &["query"]
+    ).unwrap();
+
+    pub static ref REDIS_COMMAND_SECONDS: HistogramVec = register_histogram_vec!(
+        "comparty_redis_command_duration_seconds",
+        "Redis command latency, including getting a connection",
+        &["command"]
+    ).unwrap();
+
+    pub static ref CAS_LOOKUP_SECONDS: Histogram = register_histogram!(
+        "comparty_cas_lookup_duration_seconds",
+        "CAS API lookup latency"
+    ).unwrap();
+}
+
+/// All registered metrics in the Prometheus text format.
+pub fn render() -> Result<String, anyhow::Error> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
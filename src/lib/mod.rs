@@ -1,6 +1,12 @@
+pub mod auto_delete;
+pub mod bot_actions;
 pub mod config;
 pub mod enums;
 pub mod helpers;
+pub mod logging;
+pub mod metrics;
+#[cfg(test)]
+pub mod test_helpers;
 pub mod tg_helpers;
 pub mod types;
 pub mod errors;
\ No newline at end of file
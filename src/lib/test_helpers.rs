@@ -0,0 +1,125 @@
+use crate::lib::{bot_actions, types};
+use serde_json::json;
+use std::sync::Arc;
+
+pub const CHAT_ID: i64 = -1001234567890;
+pub const MESSAGE_DATE: i64 = 1640995200;
+
+pub fn user(id: i64, first_name: &str) -> teloxide::types::User {
+    serde_json::from_value(json!({ "id": id, "is_bot": false, "first_name": first_name })).unwrap()
+}
+
+fn message_json(message_id: i32, from: &teloxide::types::User, text: &str) -> serde_json::Value {
+    json!({
+        "message_id": message_id,
+        "date": MESSAGE_DATE,
+        "chat": { "id": CHAT_ID, "type": "supergroup", "title": "Test chat" },
+        "from": from,
+        "text": text,
+    })
+}
+
+pub fn message(message_id: i32, from: &teloxide::types::User, text: &str) -> teloxide::types::Message {
+    serde_json::from_value(message_json(message_id, from, text)).unwrap()
+}
+
+pub fn reply(
+    message_id: i32,
+    from: &teloxide::types::User,
+    text: &str,
+    reply_to: &teloxide::types::User,
+) -> teloxide::types::Message {
+    let mut value = message_json(message_id, from, text);
+    value["reply_to_message"] = message_json(message_id - 1, reply_to, "original message");
+    serde_json::from_value(value).unwrap()
+}
+
+pub fn message_context(
+    bot: &Arc<bot_actions::RecordingBotActions>,
+    message: teloxide::types::Message,
+) -> types::MessageContext {
+    let requester: Arc<dyn bot_actions::BotActions> = bot.clone();
+    teloxide::prelude::UpdateWithCx { requester, update: message }
+}
+
+/// Status of an administrator as Telegram sends it, with all the other rights granted.
+pub fn administrator(can_delete_messages: bool, can_restrict_members: bool) -> serde_json::Value {
+    json!({
+        "status": "administrator",
+        "can_be_edited": false,
+        "is_anonymous": false,
+        "can_manage_chat": true,
+        "can_change_info": true,
+        "can_delete_messages": can_delete_messages,
+        "can_manage_voice_chats": true,
+        "can_manage_video_chats": true,
+        "can_invite_users": true,
+        "can_restrict_members": can_restrict_members,
+        "can_pin_messages": true,
+        "can_promote_members": false,
+    })
+}
+
+/// `old_status` and `new_status` are chat members without the user, e.g. `json!({ "status": "left" })`.
+pub fn chat_member_updated(
+    user: &teloxide::types::User,
+    old_status: serde_json::Value,
+    new_status: serde_json::Value,
+) -> teloxide::types::ChatMemberUpdated {
+    let mut old_chat_member = old_status;
+    old_chat_member["user"] = json!(user);
+    let mut new_chat_member = new_status;
+    new_chat_member["user"] = json!(user);
+    serde_json::from_value(json!({
+        "chat": { "id": CHAT_ID, "type": "supergroup", "title": "Test chat" },
+        "from": user,
+        "date": MESSAGE_DATE,
+        "old_chat_member": old_chat_member,
+        "new_chat_member": new_chat_member,
+    }))
+        .unwrap()
+}
+
+pub fn chat_member_context(
+    bot: &Arc<bot_actions::RecordingBotActions>,
+    update: teloxide::types::ChatMemberUpdated,
+) -> types::ChatMemberContext {
+    let requester: Arc<dyn bot_actions::BotActions> = bot.clone();
+    teloxide::prelude::UpdateWithCx { requester, update }
+}
+
+/// A press on a button with `data` under the bot's `message`.
+pub fn callback_query(
+    from: &teloxide::types::User,
+    message: &teloxide::types::Message,
+    data: &str,
+) -> teloxide::types::CallbackQuery {
+    serde_json::from_value(json!({
+        "id": format!("callback-{}", message.id),
+        "from": from,
+        "message": message,
+        "chat_instance": "1",
+        "data": data,
+    }))
+        .unwrap()
+}
+
+pub fn callback_context(
+    bot: &Arc<bot_actions::RecordingBotActions>,
+    update: teloxide::types::CallbackQuery,
+) -> types::CallbackContext {
+    let requester: Arc<dyn bot_actions::BotActions> = bot.clone();
+    teloxide::prelude::UpdateWithCx { requester, update }
+}
+
+pub fn config() -> crate::lib::config::SharedConfig {
+    crate::lib::config::shared(crate::lib::config::Config::default())
+}
+
+/// Auto-deletion of the chat domain, scheduling its jobs in `store`.
+pub async fn auto_delete(store: &Arc<crate::core::persistence::MemoryStore>)
+    -> Arc<dyn crate::lib::auto_delete::AutoDelete> {
+    let storage = crate::core::persistence::Storage::Memory(store.clone());
+    let jobs = crate::domains::job::new_job_domain(&storage, config()).await.service;
+    crate::domains::chat::new_chat_domain(&storage, jobs, config()).await.auto_delete
+}
@@ -1,26 +1,72 @@
 use anyhow::Context;
 use itertools::Itertools;
-use teloxide::types::{Chat, User};
-use crate::{lib::types::MessageContext, Request, Requester};
+use teloxide::types::{Chat, InlineKeyboardMarkup, User};
+use crate::lib::{auto_delete::AutoDelete, bot_actions::BotActions, enums::ReplyCategory, types::MessageContext};
 
-pub async fn reply_to(cx: &MessageContext, msg_text: String) -> Result<(), anyhow::Error> {
-    cx
-        .reply_to(msg_text)
-        .send()
+/// Replies to the current message. The reply is auto-deleted according to the chat settings of its `category`.
+pub async fn reply_to(cx: &MessageContext, auto_delete: &dyn AutoDelete, category: ReplyCategory, msg_text: String)
+    -> Result<(), anyhow::Error> {
+    send_reply(cx, auto_delete, category, msg_text, Some(cx.update.id), None).await.map(|_| ())
+}
+
+pub async fn send_message(
+    cx: &MessageContext,
+    auto_delete: &dyn AutoDelete,
+    category: ReplyCategory,
+    msg_text: String,
+) -> Result<(), anyhow::Error> {
+    send_reply(cx, auto_delete, category, msg_text, None, None).await.map(|_| ())
+}
+
+/// Answers the current message in its chat, as a reply to `reply_to_message_id` when given,
+/// and returns the id of the sent message. The current message is the trigger for auto-deletion.
+pub async fn send_reply(
+    cx: &MessageContext,
+    auto_delete: &dyn AutoDelete,
+    category: ReplyCategory,
+    msg_text: String,
+    reply_to_message_id: Option<i32>,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<i32, anyhow::Error> {
+    let message_id = cx.requester
+        .send_message(cx.update.chat_id(), msg_text, reply_to_message_id, keyboard)
+        .await
+        .context("Telegram API error")?;
+    auto_delete.reply_sent(&cx.update.chat, Some(cx.update.id), category, message_id).await;
+    Ok(message_id)
+}
+
+/// Sends a message to `chat` on an update that isn't a message, like a change of the bot's rights.
+pub async fn send_to_chat(
+    requester: &dyn BotActions,
+    auto_delete: &dyn AutoDelete,
+    chat: &Chat,
+    category: ReplyCategory,
+    msg_text: String,
+) -> Result<(), anyhow::Error> {
+    let message_id = requester
+        .send_message(chat.id, msg_text, None, None)
+        .await
+        .context("Telegram API error")?;
+    auto_delete.reply_sent(chat, None, category, message_id).await;
+    Ok(())
+}
+
+pub async fn send_document(
+    cx: &MessageContext,
+    chat_id: i64,
+    file_name: String,
+    data: Vec<u8>,
+) -> Result<(), anyhow::Error> {
+    cx.requester
+        .send_document(chat_id, file_name, data)
         .await
-        .map(|_| ())
-        .map_err(anyhow::Error::new)
-        .context("Telegram API error")
 }
 
-pub async fn send_message(cx: &MessageContext, msg_text: String) -> Result<(), anyhow::Error> {
-    cx
-        .answer(msg_text)
-        .send()
+pub async fn download_file(cx: &MessageContext, file_id: &str) -> Result<Vec<u8>, anyhow::Error> {
+    cx.requester
+        .download_file(file_id)
         .await
-        .map(|_| ())
-        .map_err(anyhow::Error::new)
-        .context("Telegram API error")
 }
 
 pub fn get_user_as_chat(sender_chat: &Chat) -> User {
@@ -38,15 +84,10 @@ pub fn get_user_to_interact(user: User, sender_chat: Option<&Chat>) -> User {
     sender_chat.map_or(user, get_user_as_chat)
 }
 
-pub async fn get_chat_administrator_mentions(cx: &MessageContext) -> Result<Vec<String>, anyhow::Error> {
-    let admins = cx.requester
-        .get_chat_administrators(cx.update.chat_id())
-        .await
-        .map_err(anyhow::Error::new)?;
-
-    Ok(admins
+/// Invisible mentions notifying `admins`, resolve them with the cached chat administrators.
+pub fn get_chat_administrator_mentions(admins: &[i64]) -> Vec<String> {
+    admins
         .iter()
-        .filter(|admin| !admin.user.is_bot)
-        .map(|admin| format!("<a href=\"tg://user?id={}\">&#8288;</a>", admin.user.id))
-        .collect_vec())
+        .map(|admin_id| format!("<a href=\"tg://user?id={}\">&#8288;</a>", admin_id))
+        .collect_vec()
 }
\ No newline at end of file
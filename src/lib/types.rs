@@ -1,6 +1,10 @@
+use std::sync::Arc;
 use teloxide;
 
 pub type ConfBot = teloxide::prelude::AutoSend<teloxide::adaptors::DefaultParseMode<teloxide::Bot>>;
-pub type MessageContext = teloxide::prelude::UpdateWithCx<ConfBot, teloxide::prelude::Message>;
+pub type MessageContext =
+    teloxide::prelude::UpdateWithCx<Arc<dyn crate::lib::bot_actions::BotActions>, teloxide::prelude::Message>;
 pub type CallbackContext =
-    teloxide::prelude::UpdateWithCx<ConfBot, teloxide::prelude::CallbackQuery>;
+    teloxide::prelude::UpdateWithCx<Arc<dyn crate::lib::bot_actions::BotActions>, teloxide::prelude::CallbackQuery>;
+pub type ChatMemberContext =
+    teloxide::prelude::UpdateWithCx<Arc<dyn crate::lib::bot_actions::BotActions>, teloxide::types::ChatMemberUpdated>;
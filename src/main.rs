@@ -1,58 +1,191 @@
+#![allow(special_module_name)]
+
 extern crate openssl;
 
 use std::sync::Arc;
-use teloxide::prelude::{OnError, Request, Requester, RequesterExt, StreamExt};
+use teloxide::dispatching::update_listeners;
+use teloxide::prelude::{LoggingErrorHandler, OnError, RequesterExt, StreamExt, UpdateWithCx};
+use teloxide::types::{CallbackQuery, ChatMemberUpdated, Message};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+mod cli;
 mod core;
 mod domains;
 mod injected;
 mod lib;
 
-async fn run() {
-    teloxide::enable_logging!();
+async fn run(shared_config: lib::config::SharedConfig) {
     log::info!("Starting bot...");
-    core::persistence::run_pg_migrations().await;
-    let bot = teloxide::Bot::from_env()
+    // tokens, HTTP, webhook and shutdown settings aren't reloaded, so a startup snapshot is enough here
+    let config = shared_config.load_full();
+    if config.telegram.token.is_empty() {
+        log::error!("telegram.token (TELOXIDE_TOKEN) is required to run the bot");
+        std::process::exit(1);
+    }
+    #[cfg(unix)]
+    tokio::spawn(lib::config::reload_on_sighup(shared_config.clone()));
+
+    let bot = teloxide::Bot::new(config.telegram.token.clone())
         .parse_mode(teloxide::types::ParseMode::Html)
         .auto_send();
 
-    let app_environment = injected::setup_environment().await;
+    let bot_actions = lib::bot_actions::new_bot_actions(bot.clone());
+    let bot_actions_callback = bot_actions.clone();
+    let bot_actions_chat_member = bot_actions.clone();
+    let bot_actions_my_chat_member = bot_actions.clone();
+
+    let app_environment = match injected::setup_environment(shared_config).await {
+        Ok(app_environment) => app_environment,
+        Err(err) => {
+            log::error!("{}", err);
+            std::process::exit(1);
+        }
+    };
     let domain_holder = Arc::new(app_environment.domain_holder);
     let domain_holder_callback = domain_holder.clone();
+    let domain_holder_chat_member = domain_holder.clone();
+    let domain_holder_my_chat_member = domain_holder.clone();
+    let domain_holder_shutdown = domain_holder.clone();
+    let chat_queue = core::chat_queue::ChatQueue::new();
+    let chat_queue_callback = chat_queue.clone();
+    let chat_queue_chat_member = chat_queue.clone();
+    let chat_queue_my_chat_member = chat_queue.clone();
+    let job_worker = core::jobs::spawn_worker(domain_holder.clone(), bot_actions.clone());
+    let storage = app_environment.storage;
+    let http_routes = core::http::service_routes(storage.clone());
 
-    teloxide::prelude::Dispatcher::new(bot)
+    let mut dispatcher = teloxide::prelude::Dispatcher::new(bot.clone())
         .messages_handler(|rx| {
-            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx| {
+            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx: UpdateWithCx<_, Message>| {
                 let domain_holder_clone = domain_holder.clone();
-                async move {
-                    core::handlers::message_handler(&cx, domain_holder_clone)
-                        .await
-                        .log_on_error()
-                        .await;
-                }
+                let cx = UpdateWithCx { requester: bot_actions.clone(), update: cx.update };
+                let chat_id = cx.update.chat_id();
+                let guard = core::shutdown::track_handler();
+                chat_queue.run(chat_id, async move {
+                    let _guard = guard;
+                    let result = core::handlers::message_handler(&cx, domain_holder_clone).await;
+                    lib::metrics::UPDATES_TOTAL
+                        .with_label_values(&["message", if result.is_ok() { "ok" } else { "error" }])
+                        .inc();
+                    result.log_on_error().await;
+                })
             },
             )
         })
         .callback_queries_handler(|rx| {
-            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx| {
+            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx: UpdateWithCx<_, CallbackQuery>| {
                 let domain_holder_clone_callback = domain_holder_callback.clone();
-                async move {
-                    core::handlers::callback_handler(&cx, domain_holder_clone_callback)
-                        .await
-                        .log_on_error()
-                        .await;
-                }
+                let cx = UpdateWithCx { requester: bot_actions_callback.clone(), update: cx.update };
+                let chat_id = cx.update.message.as_ref().map_or(cx.update.from.id, |message| message.chat.id);
+                let guard = core::shutdown::track_handler();
+                chat_queue_callback.run(chat_id, async move {
+                    let _guard = guard;
+                    let result = core::handlers::callback_handler(&cx, domain_holder_clone_callback).await;
+                    lib::metrics::UPDATES_TOTAL
+                        .with_label_values(&["callback", if result.is_ok() { "ok" } else { "error" }])
+                        .inc();
+                    result.log_on_error().await;
+                })
             }
             )
         })
-        .dispatch()
-        .await;
+        .chat_members_handler(|rx| {
+            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx: UpdateWithCx<_, ChatMemberUpdated>| {
+                let domain_holder_clone = domain_holder_chat_member.clone();
+                let cx = UpdateWithCx { requester: bot_actions_chat_member.clone(), update: cx.update };
+                let chat_id = cx.update.chat.id;
+                let guard = core::shutdown::track_handler();
+                chat_queue_chat_member.run(chat_id, async move {
+                    let _guard = guard;
+                    let result = core::handlers::chat_member_handler(&cx, domain_holder_clone).await;
+                    lib::metrics::UPDATES_TOTAL
+                        .with_label_values(&["chat_member", if result.is_ok() { "ok" } else { "error" }])
+                        .inc();
+                    result.log_on_error().await;
+                })
+            })
+        })
+        .my_chat_members_handler(|rx| {
+            UnboundedReceiverStream::new(rx).for_each_concurrent(None, move |cx: UpdateWithCx<_, ChatMemberUpdated>| {
+                let domain_holder_clone = domain_holder_my_chat_member.clone();
+                let cx = UpdateWithCx { requester: bot_actions_my_chat_member.clone(), update: cx.update };
+                let chat_id = cx.update.chat.id;
+                let guard = core::shutdown::track_handler();
+                chat_queue_my_chat_member.run(chat_id, async move {
+                    let _guard = guard;
+                    let result = core::handlers::my_chat_member_handler(&cx, domain_holder_clone).await;
+                    lib::metrics::UPDATES_TOTAL
+                        .with_label_values(&["my_chat_member", if result.is_ok() { "ok" } else { "error" }])
+                        .inc();
+                    result.log_on_error().await;
+                })
+            })
+        });
+
+    let shutdown_token = dispatcher.shutdown_token();
+    let deadline = std::time::Duration::from_secs(config.shutdown.deadline_seconds);
+    let drained = match &config.webhook.url {
+        Some(_) => {
+            let listener = core::webhook::webhook_listener(&config.webhook, config.http.address, http_routes).await;
+            if let Err(err) = core::webhook::set_webhook(&config.telegram.token, &config.webhook).await {
+                log::error!("Unable to register webhook: {:#}", err);
+                std::process::exit(1);
+            }
+            let dispatching = dispatcher.dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("An error from the webhook listener"),
+            );
+            let drained = core::shutdown::dispatch_until_signal(dispatching, shutdown_token, deadline).await;
+            core::webhook::delete_webhook(&config.telegram.token).await.log_on_error().await;
+            drained
+        }
+        None => {
+            // a webhook left behind by a crash in webhook mode makes getUpdates fail with 409
+            if let Err(err) = core::webhook::delete_webhook(&config.telegram.token).await {
+                log::error!("Unable to remove webhook before polling: {:#}", err);
+                std::process::exit(1);
+            }
+            core::http::spawn_server(config.http.address, http_routes);
+            let listener = update_listeners::polling(bot, None, None, Some(core::handlers::allowed_updates()));
+            let dispatching = dispatcher.dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("An error from the update listener"),
+            );
+            core::shutdown::dispatch_until_signal(dispatching, shutdown_token, deadline).await
+        }
+    };
 
+    job_worker.stop().await;
+    let pending_jobs = domain_holder_shutdown.job.controller.count_pending().await.unwrap_or_else(|err| {
+        log::error!("{:#}", err);
+        0
+    });
+    if tokio::time::timeout(deadline, storage.close()).await.is_err() {
+        log::warn!("Storage connections weren't released in {}s", deadline.as_secs());
+    }
+    log::info!(
+        "Shutdown summary: {} updates handled, {} handlers interrupted, {} jobs pending",
+        core::shutdown::handled(),
+        if drained { 0 } else { core::shutdown::in_flight() },
+        pending_jobs,
+    );
     log::info!("Closing bot... Goodbye!");
 }
 
 #[tokio::main]
 async fn main() {
-    run().await;
+    let config = lib::config::init();
+    let args = std::env::args().collect::<Vec<_>>();
+    let result = match args.get(1).map(String::as_str) {
+        Some("import-history") => cli::import_history(&args[2..], config).await,
+        Some("replay") => cli::replay(&args[2..], config).await,
+        _ => {
+            run(config).await;
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        log::error!("{:?}", err);
+        std::process::exit(1);
+    }
 }